@@ -3,11 +3,7 @@ use rodio::{Decoder, OutputStreamBuilder, Sink, Source};
 use std::{
     error::Error,
     io::Cursor,
-    sync::{
-        OnceLock,
-        atomic::{AtomicBool, Ordering},
-        mpsc,
-    },
+    sync::{OnceLock, mpsc},
     thread,
     time::Duration,
 };
@@ -28,18 +24,12 @@ pub enum AudioCommand {
     Pause,
     Resume,
     // Stop,
-    TogglePause,
     // Shutdown,
 }
 
 // global Sender to call from anywhere after `init()`
 // (onceLock only to init the thread once).
 static AUDIO_SENDER: OnceLock<mpsc::Sender<AudioCommand>> = OnceLock::new();
-static IS_PAUSED: AtomicBool = AtomicBool::new(false);
-
-pub fn get_is_paused() -> bool {
-    IS_PAUSED.load(Ordering::Relaxed)
-}
 
 // initialize the audio thread, subsequent calls return the same sender
 pub fn init_audio_thread() -> mpsc::Sender<AudioCommand> {
@@ -68,7 +58,6 @@ pub fn init_audio_thread() -> mpsc::Sender<AudioCommand> {
         for cmd in rx {
             match cmd {
                 AudioCommand::Play { name, loop_forever } => {
-                    IS_PAUSED.store(false, Ordering::Relaxed);
                     // stop previous sink if present
                     if let Some(s) = current_sink.take() {
                         s.stop();
@@ -99,28 +88,15 @@ pub fn init_audio_thread() -> mpsc::Sender<AudioCommand> {
                 }
 
                 AudioCommand::Pause => {
-                    IS_PAUSED.store(true, Ordering::Relaxed);
                     if let Some(s) = current_sink.as_ref() {
                         s.pause();
                     }
                 }
 
                 AudioCommand::Resume => {
-                    IS_PAUSED.store(false, Ordering::Relaxed);
                     if let Some(s) = current_sink.as_ref() {
                         s.play();
                     }
-                }
-
-                AudioCommand::TogglePause => {
-                    IS_PAUSED.store(!IS_PAUSED.load(Ordering::Relaxed), Ordering::Relaxed);
-                    if let Some(s) = current_sink.as_ref() {
-                        if s.is_paused() {
-                            s.play();
-                        } else {
-                            s.pause();
-                        }
-                    }
                 } /*
                                   AudioCommand::Stop => {
                                       if let Some(s) = current_sink.take() {
@@ -162,20 +138,12 @@ pub fn play(name: &str, loop_forever: bool) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-pub fn toggle() -> Result<(), Box<dyn Error>> {
-    let tx = init_audio_thread();
-    tx.send(AudioCommand::TogglePause)?;
-    Ok(())
-}
-
-#[allow(dead_code)]
 pub fn pause() -> Result<(), Box<dyn Error>> {
     let tx = init_audio_thread();
     tx.send(AudioCommand::Pause)?;
     Ok(())
 }
 
-#[allow(dead_code)]
 pub fn resume() -> Result<(), Box<dyn Error>> {
     let tx = init_audio_thread();
     tx.send(AudioCommand::Resume)?;
@@ -1,7 +1,24 @@
+use crate::bot;
+use crate::bot::Bot;
+use crate::bot::Placement;
+use crate::engine::Action;
+use crate::engine::Command;
+use crate::engine::Finesse;
+use crate::engine::Game;
+use crate::engine::GameSettings;
+use crate::engine::Mode;
+use crate::engine::Timing;
+use crate::garbage::GarbageMeter;
+use crate::garbage::GarbageStyle;
 use crate::ids;
 use crate::piece::Piece;
-use crate::piece::PieceBag;
 use crate::piece::PieceView;
+use crate::piece_set::PieceSet;
+use crate::position::Position;
+use crate::replay::Input;
+use crate::replay::Replay;
+use crate::replay::Summary;
+use crate::scores;
 use crate::tetrs;
 use crate::tetrs::get_starting_level;
 use crate::text_art::BLOCK_CHAR;
@@ -23,16 +40,65 @@ use cursive::views::HideableView;
 use cursive::views::LinearLayout;
 use cursive::views::OnEventView;
 use cursive::views::TextView;
+use serde::Deserialize;
+use serde::Serialize;
 use std::cmp::min;
+use std::sync::Arc;
 use std::time;
 use std::time::Instant;
 
 pub const BOARD_WIDTH: usize = 10;
-pub const BOARD_HEIGHT: usize = 20;
-pub const PIECE_START_X: i8 = 4;
-pub const PIECE_START_Y: i8 = -1;
+pub const BOARD_HEIGHT: usize = 20; // visible rows
+pub const MIN_BOARD_WIDTH: usize = 4; // the I piece has to fit lying down
+pub const MAX_BOARD_WIDTH: usize = 20;
+pub const MIN_BOARD_HEIGHT: usize = 8;
+pub const MAX_BOARD_HEIGHT: usize = 40;
+// hidden rows above the visible ones, pieces spawn at the bottom of it and a stack pushed up into it
+// isn't lost yet
+pub const BUFFER_HEIGHT: usize = 20;
+pub const PIECE_START_Y: i8 = BUFFER_HEIGHT as i8 - 1;
 
-const MAX_LEVEL: u8 = u8::MAX; //theoretically...
+// left edge of a piece's drawing at spawn, just left of center like on the standard 10 wide board
+pub fn piece_start_x(width: usize, piece_width: usize) -> i8 {
+    min(width / 2 - 1, width.saturating_sub(piece_width)) as i8
+}
+
+// longest stretch of wall time a single refresh may add to the game clock, anything longer means
+// the board wasn't on top (paused or a dialog was up) and shouldn't count
+const MAX_REFRESH_STEP_MILLIS: u64 = 100;
+
+// where the bot would put the current piece, drawn over the board for learning to stack
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hints {
+    #[default]
+    Off,
+    Best,
+    Top3, // with the scores next to the board
+}
+
+impl Hints {
+    pub const ALL: [Hints; 3] = [
+        Hints::Off,
+        Hints::Best,
+        Hints::Top3,
+    ];
+    pub fn title(&self) -> &'static str {
+        match self {
+            Hints::Off => "Off",
+            Hints::Best => "Best",
+            Hints::Top3 => "Top 3",
+        }
+    }
+    // placements shown
+    fn count(&self) -> usize {
+        match self {
+            Hints::Off => 0,
+            Hints::Best => 1,
+            Hints::Top3 => 3,
+        }
+    }
+}
 
 #[derive(PartialEq, Clone, Copy)]
 enum ScaleMode {
@@ -54,46 +120,18 @@ impl ScaleMode {
     }
 }
 
-struct ClearedLineCounts {
-    singles: u32,
-    doubles: u32,
-    triples: u32,
-    tetrses: u32,
-}
-
-impl ClearedLineCounts {
-    fn new() -> Self {
-        Self {
-            singles: 0,
-            doubles: 0,
-            triples: 0,
-            tetrses: 0,
-        }
-    }
-}
-
 pub struct Board {
     // board layout things
     scale_mode: ScaleMode,
-    tiles: [[Tile; BOARD_WIDTH]; BOARD_HEIGHT],
     needs_relayout: bool,
-    loss_state: LossState,
 
-    // piece things
-    current_piece: Piece,
-    piece_bag: PieceBag,
-    held_piece: Option<Piece>,
-    can_hold: bool,
+    // the actual game
+    game: Game,
 
-    // ticking
-    last_tick: time::Instant,
-    tick_time: time::Duration, // make this vary by level/difficulty
+    // ticking, wall time is turned into game time on every refresh
+    last_refresh: time::Instant,
 
     //game stat
-    score: u32,
-    lines: u32,
-    level: u8,
-    starting_level: u8,
     high_score: u32,
 
     // settable settings,
@@ -102,15 +140,103 @@ pub struct Board {
     // syncing settings
     synced: bool,
 
-    // advanced/extra stats,
-    start_time: time::Instant, // for getting elapsed later
-    cleared_line_counts: ClearedLineCounts,
+    // everything pressed so far, saved as a replay when the game ends
+    inputs: Vec<Input>,
+    // set for every game of the session when launched with a fixed seed
+    fixed_seed: Option<u64>,
+    // when watching a replay its inputs drive the game instead of the keyboard
+    playback: Option<Playback>,
+    // whose views and keys, 0 for the plain ones and 1 or 2 for a side of a local versus game
+    player: usize,
+    // part of a versus game, which decides what happens when it ends
+    versus: bool,
+    // plays the board instead of the keyboard, its games don't count for scores
+    bot: Option<Bot>,
+    // best first, for the piece in play as told by pieces dealt and the held kind
+    hints: Vec<Placement>,
+    hints_for: Option<(u32, Option<usize>, Hints)>,
+    // drills single placements instead of playing a game, see `update_trainer`
+    trainer: Option<Trainer>,
+    // a practice game, which can be stepped back and forth a piece at a time
+    practice: Option<Practice>,
+}
+
+// the game as it was when each piece came into play, only a practice game isn't recorded so it can
+// be put back to any of them
+#[derive(Default)]
+struct Practice {
+    undo: Vec<Game>,
+    redo: Vec<Game>,
+    piece_start: Option<Game>, // the piece in play as it spawned
+    locked: u32,               // pieces locked when last looked at
+    setup: PracticeSetup,
+    position: Option<Position>, // where it started from, if not from an empty board
+    pages: Vec<Position>,       // a fumen's pages to go through, position is one of them
+    page: usize,
+}
+
+// what a practice game deals and whether pieces fall by themselves
+#[derive(Clone, Default)]
+pub struct PracticeSetup {
+    pub queue: String, // piece names like "TIJLOSZ", empty for random pieces
+    pub repeat: bool,  // the queue over and over instead of random pieces after it
+    pub gravity: bool,
+}
+
+// one piece at a time on an empty board, each with a target to put it on in as few presses as it
+// takes
+#[derive(Default)]
+struct Trainer {
+    target: Option<Placement>,
+    target_for: Option<(u32, Option<usize>)>, // pieces dealt and the held kind, like the hints
+    fewest: Vec<Action>,                      // to reach the target from where the piece spawned
+    placed: u32,
+    clean: u32, // on target without a wasted press
+    verdict: String,
+}
+
+impl Trainer {
+    fn judge(&mut self, target: &Placement, finesse: &Finesse) {
+        let cells = |piece: &Piece| {
+            let mut cells: Vec<(i8, i8)> = piece.cells().collect();
+            cells.sort();
+            cells
+        };
+        self.placed += 1;
+        self.verdict = match (
+            cells(&finesse.landing) == cells(&target.landing),
+            finesse.faults(),
+        ) {
+            (false, _) => "Missed".to_string(),
+            (true, 0) => {
+                self.clean += 1;
+                "Clean!".to_string()
+            }
+            (true, 1) => "1 extra press".to_string(),
+            (true, n) => format!("{} extra presses", n),
+        };
+    }
+    fn text(&self) -> String {
+        let mut lines = vec![
+            format!("Placed: {}", self.placed),
+            format!("Clean: {}", self.clean),
+        ];
+        if !self.verdict.is_empty() {
+            lines.push(self.verdict.clone());
+        }
+        lines.push("Fewest:".to_string());
+        match self.fewest.is_empty() {
+            true => lines.push(" Hard Drop".to_string()),
+            false => lines.extend(self.fewest.iter().map(|a| format!(" {}", a.title()))),
+        }
+        lines.join("\n")
+    }
 }
 
-#[derive(Clone, Copy)]
-enum LossState {
-    NotLost,
-    Lost,
+struct Playback {
+    replay: Replay,
+    next_input: usize,
+    ended: bool,
 }
 
 enum TickState {
@@ -119,62 +245,281 @@ enum TickState {
 }
 
 pub struct BoardSettings {
+    pub mode: Mode,
     pub starting_level: u8,
     pub ghost_piece_on: bool,
     pub high_score: u32,
+    pub seed: Option<u64>, // None for a random game
+    pub timing: Timing,
+    pub width: usize,
+    pub height: usize,
+    pub pieces: Arc<PieceSet>,
+    pub garbage_style: GarbageStyle,
 }
 
 impl Board {
     pub fn new(settings: BoardSettings) -> Self {
-        const STARTING_TICK_TIME_MILLIS: u64 = 1000;
         let mut board = Board {
             // static board stuff
             scale_mode: ScaleMode::default(),
-            tiles: [[None; BOARD_WIDTH]; BOARD_HEIGHT],
             needs_relayout: false,
-            loss_state: LossState::NotLost,
-
-            current_piece: Piece::random_new().at(PIECE_START_X, PIECE_START_Y),
-            piece_bag: PieceBag::new(),
-            held_piece: None,
-            can_hold: true,
-            last_tick: time::Instant::now(),
-            tick_time: time::Duration::from_millis(STARTING_TICK_TIME_MILLIS),
-
-            // game state
-            score: 0,
-            lines: 0,
-            // set up things dependent on settings
-            level: settings.starting_level,
-            starting_level: settings.starting_level,
+
+            game: Game::new(GameSettings {
+                mode: settings.mode,
+                starting_level: settings.starting_level,
+                seed: settings.seed.unwrap_or_else(rand::random),
+                timing: settings.timing,
+                width: settings.width,
+                height: settings.height,
+                pieces: settings.pieces,
+                garbage_style: settings.garbage_style,
+            }),
+            last_refresh: time::Instant::now(),
+
             high_score: settings.high_score,
             // toggle-ables
             ghost_piece_on: settings.ghost_piece_on,
             synced: false,
-            // advanced stats
-            start_time: Instant::now(), // needs to be reset when synced
-            cleared_line_counts: ClearedLineCounts::new(),
+            inputs: Vec::new(),
+            fixed_seed: settings.seed,
+            playback: None,
+            player: 0,
+            versus: false,
+            bot: None,
+            hints: Vec::new(),
+            hints_for: None,
+            trainer: None,
+            practice: None,
         };
-        board.update_tick_time();
+        board.match_finesse_to_keys();
+        board
+    }
+    // one side of a versus game, the versus screen decides its settings and what happens when it
+    // ends
+    pub fn versus(settings: BoardSettings, player: usize) -> Self {
+        let mut board = Board::new(settings);
+        board.synced = true;
+        board.player = player;
+        board.versus = true;
+        board.match_finesse_to_keys();
+        board
+    }
+    pub fn with_bot(mut self, bot: Bot) -> Self {
+        self.bot = Some(bot);
+        self
+    }
+    // a game to try things in, nothing it scores is kept
+    pub fn practice(settings: BoardSettings) -> Self {
+        let mut board = Board::new(settings);
+        board.synced = true; // always marathon, whatever the settings say
+        board.practice = Some(Practice::default());
+        board.game.set_gravity(false);
+        board
+    }
+    // practice from a position made in the editor, its first piece is the one in play and the rest
+    // of its queue comes after. the settings have to be for its board and pieces
+    pub fn from_position(settings: BoardSettings, position: Position) -> Result<Self, String> {
+        let mut queue = position.queue_kinds()?;
+        let mut board = Board::practice(settings);
+        board.set_position(position)?;
+        // the first piece was put in play by the position
+        if !queue.is_empty() {
+            queue.remove(0);
+        }
+        let names: Vec<&str> = queue
+            .iter()
+            .map(|kind| board.game.piece_set().shape(*kind).name.as_str())
+            .collect();
+        let separator = match names.iter().all(|n| n.chars().count() == 1) {
+            true => "",
+            false => " ",
+        };
+        board.set_practice_setup(PracticeSetup {
+            queue: names.join(separator),
+            ..PracticeSetup::default()
+        })?;
+        Ok(board)
+    }
+    // practice through the pages of a fumen, one at a time from the first
+    pub fn from_pages(settings: BoardSettings, pages: Vec<Position>) -> Result<Self, String> {
+        let first = pages.first().cloned().ok_or("there are no pages")?;
+        let mut board = Board::from_position(settings, first)?;
+        if let Some(practice) = board.practice.as_mut() {
+            practice.pages = pages;
+        }
+        Ok(board)
+    }
+    // the page being played from and how many there are
+    pub fn page(&self) -> Option<(usize, usize)> {
+        let practice = self.practice.as_ref()?;
+        match practice.pages.len() {
+            0 => None,
+            n => Some((practice.page, n)),
+        }
+    }
+    // starts over from the page `step` pages on or back
+    pub fn turn_page(&mut self, step: isize) -> Result<(), String> {
+        let (Some((page, count)), Some(practice)) = (self.page(), self.practice.as_ref()) else {
+            return Ok(());
+        };
+        let page = (page as isize + step).clamp(0, count as isize - 1) as usize;
+        let pages = practice.pages.clone();
+        let mut board = Board::from_position(self.get_settings(), pages[page].clone())?;
+        if let Some(practice) = board.practice.as_mut() {
+            practice.pages = pages;
+            practice.page = page;
+        }
+        *self = board;
+        Ok(())
+    }
+    // the stack, held piece and piece in play of a position, remembered for restarting
+    fn set_position(&mut self, position: Position) -> Result<(), String> {
+        let tiles = position.tiles()?;
+        let first = position.queue_kinds()?.first().copied();
+        self.game.set_stack(&tiles);
+        self.game.set_held_piece(position.held_kind()?);
+        if let Some(kind) = first {
+            self.game.replace_current_piece(kind);
+        }
+        if let Some(practice) = self.practice.as_mut() {
+            practice.position = Some(position);
+            practice.piece_start = None;
+        }
+        self.track_practice();
+        Ok(())
+    }
+    pub fn practice_setup(&self) -> Option<PracticeSetup> {
+        self.practice.as_ref().map(|p| p.setup.clone())
+    }
+    // deals the queue from the next piece on, fails on pieces the set doesn't have
+    pub fn set_practice_setup(&mut self, setup: PracticeSetup) -> Result<(), String> {
+        let Some(practice) = self.practice.as_mut() else {
+            return Ok(());
+        };
+        let kinds = self.game.piece_set().parse_queue(&setup.queue)?;
+        self.game.set_queue(&kinds, setup.repeat);
+        self.game.set_gravity(setup.gravity);
+        practice.setup = setup;
+        // undo comes back to the piece as it is now
+        practice.piece_start = None;
+        self.track_practice();
+        Ok(())
+    }
+    // a finesse trainer instead of a game, nothing it scores is kept
+    pub fn trainer(settings: BoardSettings) -> Self {
+        let mut board = Board::new(settings);
+        board.trainer = Some(Trainer::default());
+        board
+    }
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+    pub fn player(&self) -> usize {
+        self.player
+    }
+    pub fn is_over(&self) -> bool {
+        self.game.is_over()
+    }
+    // a board that plays back a recorded game
+    pub fn from_replay(replay: Replay, ghost_piece_on: bool) -> Self {
+        let mut board = Board::new(BoardSettings {
+            mode: replay.mode,
+            starting_level: replay.starting_level,
+            ghost_piece_on,
+            high_score: tetrs::get_high_score(),
+            seed: Some(replay.seed),
+            timing: replay.timing,
+            width: replay.width,
+            height: replay.height,
+            pieces: Arc::new(replay.pieces.clone()),
+            garbage_style: replay.garbage_style,
+        });
+        board.synced = true; // the replay decides the settings
+        board.playback = Some(Playback {
+            replay,
+            next_input: 0,
+            ended: false,
+        });
         board
     }
     // to be called only once when setting up the board afet a restart
     pub fn reset_starting_and_current_level(&mut self, lvl: u8) {
-        self.starting_level = lvl;
-        self.level = lvl;
+        self.game = Game::new(GameSettings {
+            starting_level: lvl,
+            ..self.game.settings()
+        });
+        self.match_finesse_to_keys();
+        if let Some(practice) = self.practice.take() {
+            self.practice = Some(Practice::default());
+            let _ = self.set_practice_setup(practice.setup);
+        }
+        if let Some(bot) = self.bot.as_mut() {
+            bot.reset();
+        }
+    }
+    // finesse is counted against the keys this player has, moves without one aren't expected
+    fn match_finesse_to_keys(&mut self) {
+        let keys = tetrs::get_player_key_bindings(self.player);
+        self.game
+            .set_pressable(|action| !keys.keys_for(action).is_empty());
+    }
+    // preview boxes have to fit every piece of the set
+    pub fn preview_size(&self) -> (usize, usize) {
+        let (w, h) = self.game.piece_set().preview_size();
+        (w.max(4), h.max(2))
     }
     // get current settings, useful for preserve settings when reseting the board
     pub fn get_settings(&self) -> BoardSettings {
         BoardSettings {
-            starting_level: self.starting_level,
+            mode: self.game.mode(),
+            starting_level: self.game.starting_level(),
             ghost_piece_on: self.ghost_piece_on,
             high_score: self.high_score,
+            seed: self.fixed_seed,
+            timing: self.game.settings().timing,
+            width: self.game.width(),
+            height: self.game.height(),
+            pieces: self.game.settings().pieces,
+            garbage_style: self.game.settings().garbage_style,
+        }
+    }
+    // attack from someone else, recorded like an input so the replay still plays out the same
+    pub fn receive_garbage(&mut self, lines: u32) {
+        if self.game.is_over() || lines == 0 {
+            return;
         }
+        self.game.receive_garbage(lines);
+        self.inputs.push(Input {
+            ms: self.game.clock_ms(),
+            command: Command::Garbage(lines),
+        });
+    }
+    // everything pressed and taken in so far, a battle royale server plays it along
+    pub fn inputs(&self) -> &[Input] {
+        &self.inputs
+    }
+    // attack this board has sent since the last call
+    pub fn take_outgoing_garbage(&mut self) -> u32 {
+        self.game.take_outgoing_garbage()
     }
     // logically restart the board/game
     fn restart(&mut self) {
+        if let Some(mut bot) = self.bot.take() {
+            bot.reset();
+            *self = Board::new(self.get_settings()).with_bot(bot);
+            return;
+        }
+        if self.trainer.is_some() {
+            *self = Board::trainer(self.get_settings());
+            return;
+        }
+        if self.practice.is_some() {
+            self.restart_practice();
+            return;
+        }
+        self.record_finished_game();
         let old_high_score = self.high_score;
-        let latest_score = self.score;
+        let latest_score = self.game.score();
         *self = Board::new(self.get_settings());
         self.high_score = match latest_score > old_high_score {
             true => latest_score,
@@ -183,6 +528,11 @@ impl Board {
         // record high score
         tetrs::set_high_score(self.high_score);
     }
+    // save the replay and put the game on the leaderboard
+    fn record_finished_game(&mut self) {
+        let replay = Replay::from_game(&self.game, std::mem::take(&mut self.inputs));
+        let _ = scores::record(&replay);
+    }
     // draw a tile of the board (scaled)
     fn draw_tile(&self, printer: &Printer, tile: Tile, row: usize, col: usize) {
         let i = self.scale_mode.get_scale() * row;
@@ -206,6 +556,24 @@ impl Board {
             }
         }
     }
+    // an outline for the best hint and the rank for the others, so they don't pass for blocks
+    fn draw_hint_tile(&self, printer: &Printer, block: Block, rank: usize, row: usize, col: usize) {
+        let (left, right) = match rank {
+            0 => ('[', ']'),
+            _ => {
+                let digit = char::from_digit(rank as u32 + 1, 10).unwrap_or('?');
+                (digit, digit)
+            }
+        };
+        let scale = self.scale_mode.get_scale();
+        let (i, j) = (scale * row, scale * col * 2);
+        printer.with_style(block.get_color(), |p| {
+            for dy in 0..scale {
+                p.print((j, i + dy), &left.to_string());
+                p.print((j + 2 * scale - 1, i + dy), &right.to_string());
+            }
+        });
+    }
     // draw a char that comprises a piece of a scaled tile
     fn draw_tile_char(printer: &Printer, tile: Option<Block>, coord: (usize, usize)) {
         match tile {
@@ -220,230 +588,362 @@ impl Board {
         match event {
             // refresh handles gravity logic
             Event::Refresh => self.on_refresh(),
-            Event::Key(Key::Left) => {
-                self.try_current_piece_movement(&Piece::move_left);
-                EventResult::with_cb(|s| {
-                    s.call_on_name(ids::ACTION, |t: &mut TextView| {
-                        t.set_content("Move Left!");
-                    });
-                })
-            }
-            Event::Key(Key::Right) => {
-                self.try_current_piece_movement(&Piece::move_right);
-                EventResult::with_cb(|s| {
-                    s.call_on_name(ids::ACTION, |t: &mut TextView| {
-                        t.set_content("Move Right!");
-                    });
-                })
-            }
-            Event::Key(Key::Down) => {
-                if !self.try_current_piece_movement(&Piece::move_down) {
-                    self.consume_piece();
-                }
-                EventResult::with_cb(|s| {
-                    s.call_on_name(ids::ACTION, |t: &mut TextView| {
-                        t.set_content("Move Down!");
-                    });
-                })
-            }
-            Event::Key(Key::Up) => {
-                while self.try_current_piece_movement(&Piece::move_down) {}
-                self.consume_piece();
-                EventResult::with_cb(|s| {
-                    s.call_on_name(ids::ACTION, |t: &mut TextView| {
-                        t.set_content("Fast Drop!");
-                    });
-                })
-            }
-
-            Event::Char('z') => {
-                self.try_current_piece_movement(&Piece::rotate_left);
-                EventResult::with_cb(|s| {
-                    s.call_on_name(ids::ACTION, |t: &mut TextView| {
-                        t.set_content("Rotate Left!");
-                    });
-                })
-            }
-            Event::Char('x') => {
-                self.try_current_piece_movement(&Piece::rotate_right);
-                EventResult::with_cb(|s| {
-                    s.call_on_name(ids::ACTION, |t: &mut TextView| {
-                        t.set_content("Rotate Right!");
-                    });
-                })
-            }
-            Event::Char('c') => {
-                self.try_hold_piece();
-                EventResult::with_cb(|s| {
-                    s.call_on_name(ids::ACTION, |t: &mut TextView| {
-                        t.set_content("Hold!");
-                    });
-                })
-            }
-            _ => EventResult::Ignored,
+            // the keyboard doesn't drive a replay or a bot
+            _ if self.playback.is_some() || self.bot.is_some() => EventResult::Ignored,
+            _ => match tetrs::action_for_event(self.player, &event) {
+                Some(action) => self.perform(action),
+                None => EventResult::Ignored,
+            },
+        }
+    }
+    // feed an input to the engine and remember it for the replay
+    fn perform(&mut self, action: Action) -> EventResult {
+        if self.game.is_over() {
+            return EventResult::Consumed(None); // game over is handled on the next refresh
+        }
+        if action == Action::Restart && !self.versus {
+            // an abandoned game isn't put on the leaderboard
+            match (self.trainer.is_some(), self.practice.is_some()) {
+                (true, _) => *self = Board::trainer(self.get_settings()),
+                (_, true) => self.restart_practice(),
+                _ => *self = Board::new(self.get_settings()),
+            };
+            return self.handle_tick(); // redraw the stats for the fresh game
+        }
+        if let Action::Undo | Action::Redo = action {
+            return self.step_practice(action);
         }
+        if action == Action::PickPiece {
+            return self.pick_piece();
+        }
+        self.track_practice();
+        self.game.apply(action);
+        self.track_practice();
+        self.inputs.push(Input {
+            ms: self.game.clock_ms(),
+            command: Command::Act(action),
+        });
+        let announcement = action.announcement();
+        let player = self.player;
+        EventResult::with_cb(move |s| {
+            s.call_on_name(&ids::for_player(ids::ACTION, player), |t: &mut TextView| {
+                t.set_content(announcement);
+            });
+        })
     }
     // handle refresh logic, like what to do relayout is needed
     fn on_refresh(&mut self) -> EventResult {
+        if self.playback.as_ref().is_some_and(|p| p.ended) {
+            return EventResult::Consumed(None); // nothing left to watch
+        }
         // check to move down current piece
-        let tick_state: TickState = self.check_to_tick_down_piece();
-        match tick_state {
-            TickState::NotTicked => self.handle_no_tick(),
-            TickState::Ticked => self.handle_tick(),
+        let tick_state: TickState = self.advance_game_clock();
+        if self.playback_just_ended() {
+            return self.handle_playback_end();
+        }
+        // the bot presses its keys as the clock moves on, like a very steady player
+        while let Some(action) = self.bot.as_mut().and_then(|b| b.next_action(&self.game)) {
+            let _ = self.perform(action);
+        }
+        self.track_practice();
+        self.update_trainer();
+        self.update_hints(tetrs::get_hints());
+        match (tick_state, self.game.is_over()) {
+            (TickState::NotTicked, false) => self.handle_no_tick(),
+            _ => self.handle_tick(),
         }
     }
 
-    // when a tick has passed, this will move down the current piece and consume it if it is
-    // obstructed
-    fn check_to_tick_down_piece(&mut self) -> TickState {
-        let now = Instant::now();
-        if now < self.last_tick + self.tick_time {
-            return TickState::NotTicked; // we haven't ticked yet
-        }
-        self.last_tick = now;
-        // only consume piece and check loss if it can't move
-        if !self.try_current_piece_movement(&Piece::move_down) {
-            self.consume_piece();
-        }
-        TickState::Ticked
-    }
-    // sets self.LossState and also returns true if lost
-    fn consume_piece(&mut self) -> bool {
-        let piece = &self.current_piece;
-        for i in 0..piece.layout().len() {
-            for j in 0..piece.layout()[i].len() {
-                let piece_tile = piece.layout()[i][j];
-                if piece_tile.is_none() {
-                    continue; // we do not care, no block in this tile
-                }
-                let x = j as i8 + piece.coord().0;
-                let y = i as i8 + piece.coord().1;
-                // piece too high, loss
-                if y < 0 {
-                    self.loss_state = LossState::Lost;
-                    return true;
-                }
-                self.tiles[y as usize][x as usize] = piece_tile;
+    // remembers the game whenever a piece comes into play, and moves the last one onto the undo
+    // history once that piece locks. locking a piece after stepping back drops what was ahead
+    fn track_practice(&mut self) {
+        let Some(practice) = self.practice.as_mut() else {
+            return;
+        };
+        if self.game.pieces_locked() != practice.locked {
+            practice.locked = self.game.pieces_locked();
+            practice.undo.extend(practice.piece_start.take());
+            practice.redo.clear();
+        }
+        if practice.piece_start.is_none() && self.game.current_piece().is_some() {
+            practice.piece_start = Some(self.game.clone());
+        }
+    }
+    // the same queue from the start, to run through an opener again
+    fn restart_practice(&mut self) {
+        let Practice {
+            setup,
+            position,
+            pages,
+            page,
+            ..
+        } = self.practice.take().unwrap_or_default();
+        *self = Board::practice(self.get_settings());
+        if let Some(position) = position {
+            let _ = self.set_position(position);
+        }
+        let _ = self.set_practice_setup(setup);
+        if let Some(practice) = self.practice.as_mut() {
+            practice.pages = pages;
+            practice.page = page;
+        }
+    }
+    // the piece in play becomes the next kind of the set
+    fn pick_piece(&mut self) -> EventResult {
+        let (Some(practice), Some(piece)) = (self.practice.as_mut(), self.game.current_piece())
+        else {
+            return EventResult::Consumed(None);
+        };
+        let kind = (piece.kind() + 1) % self.game.piece_set().len();
+        self.game.replace_current_piece(kind);
+        practice.piece_start = Some(self.game.clone());
+        EventResult::Consumed(None)
+    }
+    // back to before the last piece locked, or forward again to where undo was pressed
+    fn step_practice(&mut self, action: Action) -> EventResult {
+        let Some(practice) = self.practice.as_mut() else {
+            return EventResult::Consumed(None); // only a practice game has any history
+        };
+        let (from, to) = match action {
+            Action::Undo => (&mut practice.undo, &mut practice.redo),
+            _ => (&mut practice.redo, &mut practice.undo),
+        };
+        let Some(game) = from.pop() else {
+            return EventResult::Consumed(None);
+        };
+        to.push(
+            practice
+                .piece_start
+                .take()
+                .unwrap_or_else(|| self.game.clone()),
+        );
+        practice.locked = game.pieces_locked();
+        self.game = game;
+        self.track_practice();
+        let announcement = action.announcement();
+        let player = self.player;
+        self.handle_tick().and(EventResult::with_cb(move |s| {
+            s.call_on_name(&ids::for_player(ids::ACTION, player), |t: &mut TextView| {
+                t.set_content(announcement);
+            });
+        }))
+    }
+    // the board is kept empty, so anything on it is the piece that just locked. it's judged and
+    // cleared away, then the next piece gets a target picked from where it could go
+    fn update_trainer(&mut self) {
+        let Some(trainer) = self.trainer.as_mut() else {
+            return;
+        };
+        if self.game.tiles().iter().flatten().any(|t| t.is_some()) {
+            if let (Some(target), Some(finesse)) = (trainer.target.take(), self.game.last_finesse())
+            {
+                trainer.judge(&target, finesse);
             }
+            self.game.clear_stack();
         }
-        // let us hold again since we just consumed a piece
-        self.can_hold = true;
-        // book keeping and handle transition to next piece
-        self.current_piece = self.piece_bag.pop();
-        self.score += 1; // give pity point
-        // check to clear any lines that are now full after consuming a piece
-        self.clear_any_full_lines();
-        // update level and tick time accordingly
-        self.level = self.starting_level
-            + min(
-                MAX_LEVEL as u32,
-                self.lines as u32 / (10 * self.starting_level as u32),
-            ) as u8;
-        self.update_tick_time();
-        false
-    }
-    // helper
-    fn update_tick_time(&mut self) {
-        const CURVE: u64 = 15; // so it's not too hard
-        let millis = match self.level {
-            0 => 800,
-            1 => 717,
-            2 => 633,
-            3 => 550,
-            4 => 467,
-            5 => 383,
-            6 => 300,
-            7 => 217,
-            8 => 133,
-            9 => 100,
-            10..=12 => 83 + CURVE,
-            13..=15 => 67 + CURVE,
-            16..=18 => 50 + CURVE,
-            19..=28 => 33 + CURVE,
-            _ => 17 + CURVE,
+        let Some(piece) = self.game.current_piece() else {
+            return;
         };
-        self.tick_time = std::time::Duration::from_millis(millis);
-    }
-    // clears any full lines on the board
-    fn clear_any_full_lines(&mut self) {
-        let mut num_cleared = 0;
-        let mut i = BOARD_HEIGHT as isize - 1;
-        while i >= 0 {
-            if self.tiles[i as usize].iter().all(|t| t.is_some()) {
-                num_cleared += 1;
-                self.clear_line_and_shift_down(i as usize);
-                i += 1; // recheck the same row after shifting down
+        let key = (
+            self.game.pieces_dealt(),
+            self.game.held_piece().map(|p| p.kind()),
+        );
+        if trainer.target_for == Some(key) {
+            return;
+        }
+        trainer.target_for = Some(key);
+        // holding would only put a different piece on the same empty board
+        let mut targets: Vec<Placement> = bot::ranked_placements(&self.game, 1)
+            .into_iter()
+            .filter(|p| p.actions.first() != Some(&Action::Hold))
+            .collect();
+        trainer.target = match targets.is_empty() {
+            true => None,
+            false => Some(targets.swap_remove(rand::random_range(0..targets.len()))),
+        };
+        trainer.fewest = trainer
+            .target
+            .as_ref()
+            .and_then(|t| self.game.fewest_presses(*piece, &t.landing))
+            .unwrap_or_default();
+    }
+    // searched once per piece, nobody is learning while a replay or the bot plays
+    fn update_hints(&mut self, hints: Hints) {
+        if let Some(trainer) = self.trainer.as_ref() {
+            // the target is drawn like the best hint
+            self.hints = trainer.target.iter().cloned().collect();
+            return;
+        }
+        let watching = self.playback.is_some() || self.bot.is_some();
+        if hints == Hints::Off || watching || self.game.current_piece().is_none() {
+            self.hints.clear();
+            self.hints_for = None;
+            return;
+        }
+        let key = (
+            self.game.pieces_dealt(),
+            self.game.held_piece().map(|p| p.kind()),
+            hints,
+        );
+        if self.hints_for == Some(key) {
+            return;
+        }
+        self.hints_for = Some(key);
+        self.hints = bot::ranked_placements(&self.game, bot::DEFAULT_LOOKAHEAD);
+        self.hints.truncate(hints.count());
+    }
+    // the scores of the hints, a line each, or how the trainer is going
+    fn hints_text(&self) -> String {
+        if let Some(trainer) = self.trainer.as_ref() {
+            return trainer.text();
+        }
+        let lines: Vec<String> = self
+            .hints
+            .iter()
+            .enumerate()
+            .map(|(rank, hint)| {
+                let score = match hint.score.is_finite() {
+                    true => format!("{:.1}", hint.score),
+                    false => "tops out".to_string(),
+                };
+                let hold = match hint.actions.first() == Some(&Action::Hold) {
+                    true => " hold",
+                    false => "",
+                };
+                format!("{}. {}{}", rank + 1, score, hold)
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    // moves the game clock forward by the wall time since the last refresh, which ticks the
+    // current piece down (and consumes it when obstructed) whenever enough time has passed
+    fn advance_game_clock(&mut self) -> TickState {
+        let now = Instant::now();
+        let step_millis = (now - self.last_refresh).as_millis() as u64;
+        self.last_refresh = match step_millis > MAX_REFRESH_STEP_MILLIS {
+            true => now,
+            false => self.last_refresh + time::Duration::from_millis(step_millis),
+        };
+        let step_millis = min(step_millis, MAX_REFRESH_STEP_MILLIS);
+        let target_ms = self.game.clock_ms() + step_millis;
+        let mut ticked = false;
+        // replayed inputs are applied at exactly the time they were recorded at
+        if let Some(playback) = self.playback.as_mut() {
+            let inputs = &playback.replay.inputs;
+            while playback.next_input < inputs.len() && inputs[playback.next_input].ms <= target_ms
+            {
+                let input = inputs[playback.next_input];
+                ticked |= self.game.advance_to(input.ms);
+                self.game.handle(input.command);
+                playback.next_input += 1;
             }
-            i -= 1;
-        }
-        self.award_points_from_lines_cleared(num_cleared);
-        self.lines += num_cleared as u32;
-        match num_cleared {
-            1 => self.cleared_line_counts.singles += 1,
-            2 => self.cleared_line_counts.doubles += 1,
-            3 => self.cleared_line_counts.triples += 1,
-            4 => self.cleared_line_counts.tetrses += 1,
-            _ => {} // nothing, not possible
-        }
-    }
-    // helper for clear_any_full_lines
-    fn clear_line_and_shift_down(&mut self, row: usize) {
-        for i in (1..=row).rev() {
-            self.tiles[i] = self.tiles[i - 1];
-        }
-        self.tiles[0] = [None; BOARD_WIDTH];
-    }
-    // awards points based on lines clear
-    fn award_points_from_lines_cleared(&mut self, num_cleared: u8) {
-        let mut points: u32 = match num_cleared {
-            1 => 100,
-            2 => 300,
-            3 => 500,
-            4 => 800,
-            _ => 0, // not possible
+        }
+        ticked |= self.game.advance_to(target_ms);
+        match ticked {
+            true => TickState::Ticked,
+            false => TickState::NotTicked,
+        }
+    }
+    // true exactly once, on the refresh where a replay runs out
+    fn playback_just_ended(&mut self) -> bool {
+        let game_over = self.game.is_over();
+        let clock_ms = self.game.clock_ms();
+        match self.playback.as_mut() {
+            Some(playback) if !playback.ended => {
+                playback.ended = game_over
+                    || (playback.next_input == playback.replay.inputs.len()
+                        && clock_ms >= playback.replay.summary.time_ms);
+                playback.ended
+            }
+            _ => false,
+        }
+    }
+    fn handle_playback_end(&mut self) -> EventResult {
+        let summary = Summary::of(&self.game);
+        let replay = match self.playback.as_ref() {
+            Some(playback) => playback.replay.clone(),
+            None => return EventResult::Consumed(None),
         };
-        points = points * self.level as u32;
-        self.score += points;
+        EventResult::with_cb(move |s| {
+            let replay = replay.clone();
+            s.add_layer(
+                Dialog::around(
+                    LinearLayout::vertical()
+                        .child(
+                            Dialog::around(TextView::new(summary.to_string()).center())
+                                .title("Result"),
+                        )
+                        .child(Button::new("Watch Again", move |s| {
+                            s.pop_layer();
+                            let replay = replay.clone();
+                            s.call_on_name(ids::BOARD, move |b: &mut Board| {
+                                *b = Board::from_replay(replay, b.ghost_piece_on);
+                            });
+                        }))
+                        .child(Button::new("Return to Title", |s| {
+                            s.pop_layer();
+                            s.pop_layer();
+                            tetrs::show_title_menu(s);
+                        })),
+                )
+                .title("Replay Finished"),
+            );
+        })
     }
     // handle the case when the board is refreshing and a tick has elapsed
     fn handle_tick(&mut self) -> EventResult {
         if self.needs_relayout {
             self.needs_relayout = false; //reset 
         }
-        let score = self.score;
-        let level = self.level;
-        let lines = self.lines;
+        let score = self.game.score();
+        let level = self.game.level();
+        let lines = self.game.lines();
         let high_score = self.high_score;
-        let loss_state = self.loss_state;
+        let over = self.game.is_over();
+        let finished = self.game.is_finished();
+        let mode = self.game.mode();
+        let elapsed = self.get_elapsed_as_string();
 
-        let next_piece = self.piece_bag.get(0);
-        let piece_in_2 = self.piece_bag.get(1);
-        let piece_in_3 = self.piece_bag.get(2);
-        let piece_in_4 = self.piece_bag.get(3);
+        let next_piece = self.game.upcoming_piece(0);
+        let piece_in_2 = self.game.upcoming_piece(1);
+        let piece_in_3 = self.game.upcoming_piece(2);
+        let piece_in_4 = self.game.upcoming_piece(3);
 
-        let held_piece = self.held_piece;
+        let held_piece = self.game.held_piece();
+        let pending_garbage = self.game.pending_garbage();
+        let rows = self.game.height();
+        let scale = self.scale_mode;
 
         // specific line cleared counts
 
-        let singles = self.cleared_line_counts.singles;
-        let doubles = self.cleared_line_counts.doubles;
-        let triples = self.cleared_line_counts.triples;
-        let tetrses = self.cleared_line_counts.tetrses;
+        let counts = self.game.cleared_line_counts();
+        let singles = counts.singles;
+        let doubles = counts.doubles;
+        let triples = counts.triples;
+        let tetrses = counts.tetrses;
 
-        let tetrs_rate = self.get_tetrs_rate();
+        let tetrs_rate = self.game.tetrs_rate();
+        let finesse_faults = self.game.finesse_faults();
+        let player = self.player;
+        // nobody's scores to beat when the bot plays, in the trainer or in practice
+        let is_practice = self.bot.is_some() || self.trainer.is_some() || self.practice.is_some();
+        // a versus game is ended by the versus screen
+        let solo_over = over && !self.versus;
 
-        match loss_state {
-            LossState::NotLost => {}
-            LossState::Lost => self.restart(),
+        if solo_over {
+            self.restart();
         }
 
         EventResult::with_cb(move |s| {
-            match loss_state {
-                LossState::NotLost => {}
-                LossState::Lost => {
-                    let game_over_title = match score > high_score {
-                        true => "New High Score!",
-                        false => "Game Over!",
+            match solo_over {
+                false => {}
+                true => {
+                    let game_over_title = match (finished, mode) {
+                        (true, Mode::Sprint) => "Sprint Complete!",
+                        (true, _) => "Time's Up!",
+                        _ if score > high_score && !is_practice => "New High Score!",
+                        _ => "Game Over!",
                     };
                     s.add_layer(
                         OnEventView::new(
@@ -452,8 +952,8 @@ impl Board {
                                     .child(
                                         Dialog::around(
                                             TextView::new(format!(
-                                                "Score: {}\nLines: {} \nLevel: {}",
-                                                score, lines, level
+                                                "Score: {}\nLines: {} \nLevel: {}\nTime: {}",
+                                                score, lines, level, elapsed
                                             ))
                                             .center(),
                                         )
@@ -485,58 +985,107 @@ impl Board {
             }
 
             // update line cleared counts for singles, doubles, etc.
-            s.call_on_name(ids::SINGLES, |t: &mut TextView| {
-                t.set_content(singles.to_string());
-            });
+            s.call_on_name(
+                &ids::for_player(ids::SINGLES, player),
+                |t: &mut TextView| {
+                    t.set_content(singles.to_string());
+                },
+            );
 
-            s.call_on_name(ids::DOUBLES, |t: &mut TextView| {
-                t.set_content(doubles.to_string());
-            });
+            s.call_on_name(
+                &ids::for_player(ids::DOUBLES, player),
+                |t: &mut TextView| {
+                    t.set_content(doubles.to_string());
+                },
+            );
 
-            s.call_on_name(ids::TRIPLES, |t: &mut TextView| {
-                t.set_content(triples.to_string());
-            });
+            s.call_on_name(
+                &ids::for_player(ids::TRIPLES, player),
+                |t: &mut TextView| {
+                    t.set_content(triples.to_string());
+                },
+            );
 
-            s.call_on_name(ids::TETRSES, |t: &mut TextView| {
-                t.set_content(tetrses.to_string());
-            });
+            s.call_on_name(
+                &ids::for_player(ids::TETRSES, player),
+                |t: &mut TextView| {
+                    t.set_content(tetrses.to_string());
+                },
+            );
 
-            s.call_on_name(ids::TETRS_RATE, |t: &mut TextView| {
-                t.set_content(tetrs_rate.to_string() + "%");
-            });
+            s.call_on_name(
+                &ids::for_player(ids::FINESSE, player),
+                |t: &mut TextView| {
+                    t.set_content(finesse_faults.to_string());
+                },
+            );
+
+            s.call_on_name(
+                &ids::for_player(ids::TETRS_RATE, player),
+                |t: &mut TextView| {
+                    t.set_content(tetrs_rate.to_string() + "%");
+                },
+            );
 
             // next pieces
-            s.call_on_name(ids::NEXT_PIECE, |n: &mut PieceView| {
-                n.set_piece(next_piece);
-            });
-            s.call_on_name(ids::PIECE_IN_2, |n: &mut PieceView| {
-                n.set_piece(piece_in_2);
-            });
-            s.call_on_name(ids::PIECE_IN_3, |n: &mut PieceView| {
-                n.set_piece(piece_in_3);
-            });
-            s.call_on_name(ids::PIECE_IN_4, |n: &mut PieceView| {
-                n.set_piece(piece_in_4);
-            });
+            s.call_on_name(
+                &ids::for_player(ids::NEXT_PIECE, player),
+                |n: &mut PieceView| {
+                    n.set_piece(next_piece);
+                },
+            );
+            s.call_on_name(
+                &ids::for_player(ids::PIECE_IN_2, player),
+                |n: &mut PieceView| {
+                    n.set_piece(piece_in_2);
+                },
+            );
+            s.call_on_name(
+                &ids::for_player(ids::PIECE_IN_3, player),
+                |n: &mut PieceView| {
+                    n.set_piece(piece_in_3);
+                },
+            );
+            s.call_on_name(
+                &ids::for_player(ids::PIECE_IN_4, player),
+                |n: &mut PieceView| {
+                    n.set_piece(piece_in_4);
+                },
+            );
 
             // held piece
-            s.call_on_name(ids::HELD_PIECE, |n: &mut PieceView| {
-                n.set_piece_optional(held_piece);
-            });
+            s.call_on_name(
+                &ids::for_player(ids::HELD_PIECE, player),
+                |n: &mut PieceView| {
+                    n.set_piece_optional(held_piece);
+                },
+            );
 
-            s.call_on_name(ids::SCORE, |t: &mut TextView| {
+            s.call_on_name(
+                &ids::for_player(ids::GARBAGE_METER, player),
+                |m: &mut GarbageMeter| {
+                    m.set_pending(pending_garbage);
+                    m.set_rows(rows);
+                    m.set_scale(scale == ScaleMode::Large);
+                },
+            );
+
+            s.call_on_name(&ids::for_player(ids::SCORE, player), |t: &mut TextView| {
                 t.set_content(format!("{}", score));
             });
 
-            s.call_on_name(ids::LEVEL, |t: &mut TextView| {
+            s.call_on_name(&ids::for_player(ids::LEVEL, player), |t: &mut TextView| {
                 t.set_content(format!("{}", level));
             });
-            s.call_on_name(ids::LINES, |t: &mut TextView| {
+            s.call_on_name(&ids::for_player(ids::LINES, player), |t: &mut TextView| {
                 t.set_content(format!("{}", lines));
             });
-            s.call_on_name(ids::HIGH_SCORE, |t: &mut TextView| {
-                t.set_content(format!("{}", high_score));
-            });
+            s.call_on_name(
+                &ids::for_player(ids::HIGH_SCORE, player),
+                |t: &mut TextView| {
+                    t.set_content(format!("{}", high_score));
+                },
+            );
         })
     }
     // handle the case when the board is refreshing but enough time for a tick has not elapsed
@@ -546,194 +1095,187 @@ impl Board {
         let do_update_from_settings = !self.synced;
 
         // UPDATE SYNCABLE SETTINS THAT NEED TO BE DISPLAYED HERE
-        let mut level = self.level;
+        let mut level = self.game.level();
 
         if do_update_from_settings {
             self.synced = true;
-            self.reset_starting_and_current_level(tetrs::get_starting_level()); // synchronize
-            self.update_tick_time(); // make sure tick time/gravity is set accordingly
+            // synchronize, this also sets tick time/gravity accordingly
+            self.game = Game::new(GameSettings {
+                mode: tetrs::get_mode(),
+                starting_level: tetrs::get_starting_level(),
+                seed: self.game.seed(),
+                timing: tetrs::get_timing(tetrs::get_mode()),
+                width: tetrs::get_board_size().0,
+                height: tetrs::get_board_size().1,
+                pieces: tetrs::get_piece_set(),
+                garbage_style: self.game.settings().garbage_style,
+            });
+            self.match_finesse_to_keys();
             self.ghost_piece_on = tetrs::get_ghost_piece_on();
             level = get_starting_level();
-            self.start_time = time::Instant::now();
+            self.last_refresh = time::Instant::now();
         }
         // ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
 
         // we need to update these every frame for a sufficiently responsive UI ~~~
-        let next_piece = self.piece_bag.get(0);
-        let piece_in_2 = self.piece_bag.get(1);
-        let piece_in_3 = self.piece_bag.get(2);
-        let piece_in_4 = self.piece_bag.get(3);
-        let held_piece = self.held_piece;
+        let next_piece = self.game.upcoming_piece(0);
+        let piece_in_2 = self.game.upcoming_piece(1);
+        let piece_in_3 = self.game.upcoming_piece(2);
+        let piece_in_4 = self.game.upcoming_piece(3);
+        let held_piece = self.game.held_piece();
         let scale = self.scale_mode;
         let timer_string = self.get_elapsed_as_string();
+        let pending_garbage = self.game.pending_garbage();
+        let rows = self.game.height();
+        let player = self.player;
+        let hints_text = self.hints_text();
+        let show_hint_scores =
+            self.trainer.is_some() || (tetrs::get_hints() == Hints::Top3 && !self.hints.is_empty());
         // ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
         match do_update_from_settings {
             false => EventResult::with_cb(move |s| {
+                s.call_on_name(&ids::for_player(ids::HINTS, player), |t: &mut TextView| {
+                    t.set_content(hints_text.clone());
+                });
+                s.call_on_name(
+                    &ids::for_player(ids::HIDE_HINTS, player),
+                    |h: &mut HideableView<Dialog>| h.set_visible(show_hint_scores),
+                );
                 // timer
                 let timer_string_clone = timer_string.clone();
                 // timer
-                s.call_on_name(ids::ELAPSED, |t: &mut TextView| {
-                    t.set_content(timer_string_clone);
-                });
+                s.call_on_name(
+                    &ids::for_player(ids::ELAPSED, player),
+                    |t: &mut TextView| {
+                        t.set_content(timer_string_clone);
+                    },
+                );
 
-                s.call_on_name(ids::NEXT_PIECE, |n: &mut PieceView| {
-                    n.set_piece(next_piece);
-                    match scale {
-                        ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
-                        ScaleMode::Large => n.set_scale(true),
-                    }
-                });
-                s.call_on_name(ids::HELD_PIECE, |n: &mut PieceView| {
-                    n.set_piece_optional(held_piece);
-                    match scale {
-                        ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
-                        ScaleMode::Large => n.set_scale(true),
-                    }
-                });
-                s.call_on_name(ids::PIECE_IN_2, |n: &mut PieceView| {
-                    n.set_piece(piece_in_2);
-                    match scale {
-                        ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
-                        ScaleMode::Large => n.set_scale(true),
-                    }
-                });
+                s.call_on_name(
+                    &ids::for_player(ids::GARBAGE_METER, player),
+                    |m: &mut GarbageMeter| {
+                        m.set_pending(pending_garbage);
+                        m.set_rows(rows);
+                        m.set_scale(scale == ScaleMode::Large);
+                    },
+                );
+
+                s.call_on_name(
+                    &ids::for_player(ids::NEXT_PIECE, player),
+                    |n: &mut PieceView| {
+                        n.set_piece(next_piece);
+                        match scale {
+                            ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
+                            ScaleMode::Large => n.set_scale(true),
+                        }
+                    },
+                );
+                s.call_on_name(
+                    &ids::for_player(ids::HELD_PIECE, player),
+                    |n: &mut PieceView| {
+                        n.set_piece_optional(held_piece);
+                        match scale {
+                            ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
+                            ScaleMode::Large => n.set_scale(true),
+                        }
+                    },
+                );
+                s.call_on_name(
+                    &ids::for_player(ids::PIECE_IN_2, player),
+                    |n: &mut PieceView| {
+                        n.set_piece(piece_in_2);
+                        match scale {
+                            ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
+                            ScaleMode::Large => n.set_scale(true),
+                        }
+                    },
+                );
                 match scale {
                     ScaleMode::Large => {
-                        s.call_on_name(ids::PIECE_IN_3, |n: &mut PieceView| {
-                            n.set_piece(piece_in_3);
-                            n.set_scale(true); // large, when visible
-                        });
-                        s.call_on_name(ids::PIECE_IN_4, |n: &mut PieceView| {
-                            n.set_piece(piece_in_4);
-                            n.set_scale(true); // large, when visible
-                        });
-                        s.call_on_name(ids::HIDE_IN_3, |h: &mut HideableView<Dialog>| {
-                            h.unhide();
-                        });
-                        s.call_on_name(ids::HIDE_IN_4, |h: &mut HideableView<Dialog>| {
-                            h.unhide();
-                        });
+                        s.call_on_name(
+                            &ids::for_player(ids::PIECE_IN_3, player),
+                            |n: &mut PieceView| {
+                                n.set_piece(piece_in_3);
+                                n.set_scale(true); // large, when visible
+                            },
+                        );
+                        s.call_on_name(
+                            &ids::for_player(ids::PIECE_IN_4, player),
+                            |n: &mut PieceView| {
+                                n.set_piece(piece_in_4);
+                                n.set_scale(true); // large, when visible
+                            },
+                        );
+                        s.call_on_name(
+                            &ids::for_player(ids::HIDE_IN_3, player),
+                            |h: &mut HideableView<Dialog>| {
+                                h.unhide();
+                            },
+                        );
+                        s.call_on_name(
+                            &ids::for_player(ids::HIDE_IN_4, player),
+                            |h: &mut HideableView<Dialog>| {
+                                h.unhide();
+                            },
+                        );
                     }
                     ScaleMode::TooSmall | ScaleMode::Small => {
-                        s.call_on_name(ids::HIDE_IN_3, |h: &mut HideableView<Dialog>| {
-                            h.hide();
-                        });
-                        s.call_on_name(ids::HIDE_IN_4, |h: &mut HideableView<Dialog>| {
-                            h.hide();
-                        });
+                        s.call_on_name(
+                            &ids::for_player(ids::HIDE_IN_3, player),
+                            |h: &mut HideableView<Dialog>| {
+                                h.hide();
+                            },
+                        );
+                        s.call_on_name(
+                            &ids::for_player(ids::HIDE_IN_4, player),
+                            |h: &mut HideableView<Dialog>| {
+                                h.hide();
+                            },
+                        );
                     }
                 }
             }),
             true => EventResult::with_cb(move |s| {
-                s.call_on_name(ids::NEXT_PIECE, |n: &mut PieceView| {
-                    n.set_piece(next_piece);
-                    match scale {
-                        ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
-                        ScaleMode::Large => n.set_scale(true),
-                    }
-                });
+                s.call_on_name(
+                    &ids::for_player(ids::NEXT_PIECE, player),
+                    |n: &mut PieceView| {
+                        n.set_piece(next_piece);
+                        match scale {
+                            ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
+                            ScaleMode::Large => n.set_scale(true),
+                        }
+                    },
+                );
 
-                s.call_on_name(ids::HELD_PIECE, |n: &mut PieceView| {
-                    n.set_piece_optional(held_piece);
-                    match scale {
-                        ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
-                        ScaleMode::Large => n.set_scale(true),
-                    }
-                });
+                s.call_on_name(
+                    &ids::for_player(ids::HELD_PIECE, player),
+                    |n: &mut PieceView| {
+                        n.set_piece_optional(held_piece);
+                        match scale {
+                            ScaleMode::TooSmall | ScaleMode::Small => n.set_scale(false),
+                            ScaleMode::Large => n.set_scale(true),
+                        }
+                    },
+                );
 
-                s.call_on_name(ids::LEVEL, |t: &mut TextView| {
+                s.call_on_name(&ids::for_player(ids::LEVEL, player), |t: &mut TextView| {
                     t.set_content(format!("{}", level));
                 });
-            }),
-        }
-    }
-    fn try_current_piece_movement<F>(&mut self, mut f: F) -> bool
-    where
-        F: FnMut(&mut Piece),
-    {
-        let mut temp = self.current_piece.clone();
-        // try movement by transforming temp
-        f(&mut temp);
 
-        if self.valid_piece(&temp) {
-            self.current_piece = temp;
-            true
-        } else {
-            //don't transform current piece
-            false
-        }
-    }
-    fn try_piece_movement<F>(&self, piece: &mut Piece, mut f: F) -> bool
-    where
-        F: FnMut(&mut Piece),
-    {
-        let mut temp = piece.clone();
-        // try movement by transforming temp
-        f(&mut temp);
-
-        if self.valid_piece(&temp) {
-            *piece = temp;
-            true
-        } else {
-            //don't transform current piece
-            false
-        }
-    }
-    fn valid_piece(&self, piece: &Piece) -> bool {
-        // this order matters, checking intersection can get an out of bounds error if
-        // we don't check bounds first
-        !piece.is_out_of_bounds() && !self.check_if_piece_intersects_any_blocks(piece)
-    }
-    fn check_if_piece_intersects_any_blocks(&self, piece: &Piece) -> bool {
-        for i in 0..piece.layout().len() {
-            for j in 0..piece.layout()[i].len() {
-                let tile = piece.layout()[i][j];
-                if tile.is_none() {
-                    continue; // we do not care, no block
-                }
-                let x = j as i8 + piece.coord().0;
-                let y = i as i8 + piece.coord().1;
-
-                // out of bounds guard to be extra safe
-                if x < 0 || y < 0 || x >= BOARD_WIDTH as i8 || y >= BOARD_HEIGHT as i8 {
-                    continue;
-                }
-                match self.tiles[y as usize][x as usize] {
-                    None => continue,
-                    Some(_) => return true,
-                }
-            }
-        }
-        false
-    }
-    fn try_hold_piece(&mut self) {
-        if !self.can_hold {
-            return;
-        }
-        let orig_held_piece = self.held_piece;
-        // construct new piece to get orginal, non-rotated layout
-        // also go remember to set starting coords for it with `at`
-        self.held_piece =
-            Some(Piece::new(self.current_piece.piece_type()).at(PIECE_START_X, PIECE_START_Y));
-        match orig_held_piece {
-            None => {
-                self.current_piece = self.piece_bag.pop();
-            }
-            Some(p) => {
-                self.current_piece = p;
-            }
+                s.call_on_name(
+                    &ids::for_player(ids::GARBAGE_METER, player),
+                    |m: &mut GarbageMeter| {
+                        m.set_pending(pending_garbage);
+                        m.set_rows(rows);
+                        m.set_scale(scale == ScaleMode::Large);
+                    },
+                );
+            }),
         }
-        self.can_hold = false; // just held, this has to get reset when we consume the next piece
     }
-
-    // helper
-    fn calculate_elapsed_duration(&self) -> time::Duration {
-        time::Instant::now() - self.start_time
-    }
-
     // gets a string holding elapsed time to easily display a timer for the game
     fn get_elapsed_as_string(&self) -> String {
-        let total_seconds = self.calculate_elapsed_duration().as_secs();
+        let total_seconds = self.game.clock_ms() / 1000;
         let minutes = total_seconds / 60;
         let seconds = total_seconds % 60;
         let minutes_str = match minutes < 10 {
@@ -747,18 +1289,6 @@ impl Board {
         };
         minutes_str + ":" + &seconds_str
     }
-
-    // gets the current rate of tetrses out of total kinds of line clears as a percentage
-    fn get_tetrs_rate(&self) -> u32 {
-        let tot = self.cleared_line_counts.singles
-            + self.cleared_line_counts.doubles
-            + self.cleared_line_counts.triples
-            + self.cleared_line_counts.tetrses;
-        if tot == 0 {
-            return 0;
-        }
-        100 * self.cleared_line_counts.tetrses / tot
-    }
 }
 
 impl View for Board {
@@ -772,11 +1302,12 @@ impl View for Board {
     fn required_size(&mut self, constraint: cursive::XY<usize>) -> cursive::XY<usize> {
         let starting_scale = self.scale_mode.clone();
 
-        let small_x = BOARD_WIDTH * 2 * ScaleMode::Small.get_scale();
-        let small_y = BOARD_HEIGHT * ScaleMode::Small.get_scale();
+        let (width, height) = (self.game.width(), self.game.height());
+        let small_x = width * 2 * ScaleMode::Small.get_scale();
+        let small_y = height * ScaleMode::Small.get_scale();
 
-        let large_x = BOARD_WIDTH * 2 * ScaleMode::Large.get_scale();
-        let large_y = BOARD_HEIGHT * ScaleMode::Large.get_scale();
+        let large_x = width * 2 * ScaleMode::Large.get_scale();
+        let large_y = height * ScaleMode::Large.get_scale();
 
         if small_x > constraint.pair().0 || small_y > constraint.pair().1 {
             self.scale_mode = ScaleMode::TooSmall;
@@ -792,59 +1323,72 @@ impl View for Board {
             self.needs_relayout = true;
         }
 
-        let dimen_x = BOARD_WIDTH * 2 * self.scale_mode.get_scale();
-        let dimen_y = BOARD_HEIGHT * self.scale_mode.get_scale();
+        let dimen_x = width * 2 * self.scale_mode.get_scale();
+        let dimen_y = height * self.scale_mode.get_scale();
         (dimen_x, dimen_y).into()
     }
 
     fn draw(&self, printer: &Printer) {
         // rendering logic for static board
-        for i in 0..self.tiles.len() {
-            for j in 0..self.tiles[i].len() {
-                let tile = self.tiles[i][j];
-                self.draw_tile(printer, tile, i, j);
+        for (i, row) in self.game.tiles().iter().skip(BUFFER_HEIGHT).enumerate() {
+            for (j, tile) in row.iter().enumerate() {
+                self.draw_tile(printer, *tile, i, j);
             }
         }
-        // draw stateless ghost piece
-        let mut ghost_piece = self.current_piece.clone();
-        // shift ghost piece down all the way
-        if self.ghost_piece_on {
-            while self.try_piece_movement(&mut ghost_piece, &Piece::move_down) {}
-
-            for i in 0..ghost_piece.layout().len() {
-                for j in 0..ghost_piece.layout()[i].len() {
-                    let tile = ghost_piece.layout()[i][j];
-                    let row = ghost_piece.coord().1 + i as i8;
-                    let col = ghost_piece.coord().0 + j as i8;
-                    // don't attempt to print negatives
-                    if row < 0 || col < 0 {
-                        continue;
-                    }
-                    match tile {
-                        // draw non-none tiles as gray for ghostly appearance
-                        None => {}
-                        _ => self.draw_tile(printer, Some(Block::Gray), row as usize, col as usize),
-                    }
+        // full rows light up while they wait to be cleared
+        for i in self.game.clearing_rows() {
+            if i < BUFFER_HEIGHT {
+                continue; // hidden
+            }
+            let i = i - BUFFER_HEIGHT;
+            for j in 0..self.game.width() {
+                self.draw_tile(printer, Some(Block::White), i, j);
+            }
+        }
+        // draw stateless ghost piece, shifted down all the way
+        if let (true, Some(ghost_piece)) = (self.ghost_piece_on, self.game.ghost_piece()) {
+            for (x, y) in ghost_piece.cells() {
+                let row = y - BUFFER_HEIGHT as i8;
+                // don't attempt to print negatives
+                if row < 0 || x < 0 {
+                    continue;
+                }
+                // draw as gray for ghostly appearance
+                self.draw_tile(printer, Some(Block::Gray), row as usize, x as usize);
+            }
+        }
+        // the best hint last so it's on top where they overlap
+        for (rank, hint) in self.hints.iter().enumerate().rev() {
+            for (x, y) in hint.landing.cells() {
+                let row = y - BUFFER_HEIGHT as i8;
+                if row < 0 || x < 0 {
+                    continue;
                 }
+                self.draw_hint_tile(
+                    printer,
+                    hint.landing.block(),
+                    rank,
+                    row as usize,
+                    x as usize,
+                );
             }
         }
         // draw piece AFTER board and ghost piece, simply "project" it onto everything, should
         // never be obstructed
-        for i in 0..self.current_piece.layout().len() {
-            for j in 0..self.current_piece.layout()[i].len() {
-                let tile = self.current_piece.layout()[i][j];
-                let row = self.current_piece.coord().1 + i as i8;
-                let col = self.current_piece.coord().0 + j as i8;
+        // nothing to draw while the next piece is waiting to spawn
+        if let Some(current_piece) = self.game.current_piece() {
+            for (x, y) in current_piece.cells() {
+                let row = y - BUFFER_HEIGHT as i8;
                 // don't attempt to print negatives
-                if row < 0 || col < 0 {
+                if row < 0 || x < 0 {
                     continue;
                 }
-                match tile {
-                    // don't draw black tiles on None becuz we don't want to overwrite anything on
-                    // static board
-                    None => {}
-                    _ => self.draw_tile(printer, tile, row as usize, col as usize),
-                }
+                self.draw_tile(
+                    printer,
+                    Some(current_piece.block()),
+                    row as usize,
+                    x as usize,
+                );
             }
         }
         match self.scale_mode {
@@ -860,3 +1404,133 @@ impl View for Board {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::MAX_DELAY_MS;
+
+    const T: usize = 6;
+    const I: usize = 0;
+    const O: usize = 1;
+
+    // practice dealing T, I and O and then the same again, with gravity off
+    fn practice_board() -> Board {
+        let mut board = Board::practice(BoardSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            ghost_piece_on: true,
+            high_score: 0,
+            seed: Some(1),
+            timing: Mode::Marathon.timing(),
+            width: BOARD_WIDTH,
+            height: BOARD_HEIGHT,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        board.game.replace_current_piece(T);
+        board
+            .set_practice_setup(PracticeSetup {
+                queue: "IO".to_string(),
+                repeat: true,
+                gravity: false,
+            })
+            .unwrap();
+        board
+    }
+
+    // drops the piece in play and waits for the next one to spawn
+    fn drop_piece(board: &mut Board) {
+        board.perform(Action::HardDrop);
+        let clock = board.game.clock_ms();
+        board.game.advance_to(clock + MAX_DELAY_MS * 2);
+        board.track_practice();
+    }
+
+    fn in_play(board: &Board) -> Option<usize> {
+        board.game.current_piece().map(|p| p.kind())
+    }
+
+    #[test]
+    fn top_3_hints_are_ranked_best_first() {
+        let mut board = Board::new(BoardSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            ghost_piece_on: true,
+            high_score: 0,
+            seed: Some(1),
+            timing: Mode::Marathon.timing(),
+            width: BOARD_WIDTH,
+            height: BOARD_HEIGHT,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        board.update_hints(Hints::Top3);
+        assert_eq!(board.hints.len(), 3);
+        assert!(board.hints.windows(2).all(|w| w[0].score >= w[1].score));
+        let ranks: Vec<String> = board
+            .hints_text()
+            .lines()
+            .map(|line| line.chars().take(3).collect())
+            .collect();
+        assert_eq!(ranks, ["1. ", "2. ", "3. "]);
+
+        board.update_hints(Hints::Best);
+        assert_eq!(board.hints.len(), 1);
+        board.update_hints(Hints::Off);
+        assert!(board.hints.is_empty());
+    }
+
+    #[test]
+    fn undo_and_redo_step_a_piece_at_a_time() {
+        let mut board = practice_board();
+        drop_piece(&mut board);
+        drop_piece(&mut board);
+        assert_eq!(board.game.pieces_locked(), 2);
+        assert_eq!(in_play(&board), Some(O));
+
+        board.perform(Action::Undo);
+        assert_eq!((board.game.pieces_locked(), in_play(&board)), (1, Some(I)));
+        board.perform(Action::Undo);
+        assert_eq!((board.game.pieces_locked(), in_play(&board)), (0, Some(T)));
+        // nothing before the first piece
+        board.perform(Action::Undo);
+        assert_eq!(board.game.pieces_locked(), 0);
+
+        board.perform(Action::Redo);
+        board.perform(Action::Redo);
+        assert_eq!((board.game.pieces_locked(), in_play(&board)), (2, Some(O)));
+        board.perform(Action::Redo);
+        assert_eq!(board.game.pieces_locked(), 2);
+    }
+
+    #[test]
+    fn locking_after_undo_drops_what_was_ahead() {
+        let mut board = practice_board();
+        drop_piece(&mut board);
+        drop_piece(&mut board);
+        board.perform(Action::Undo);
+        board.perform(Action::Undo);
+        board.perform(Action::MoveLeft);
+        drop_piece(&mut board);
+        board.perform(Action::Redo);
+        assert_eq!((board.game.pieces_locked(), in_play(&board)), (1, Some(I)));
+        // the T went down where it was moved this time
+        board.perform(Action::Undo);
+        board.perform(Action::Redo);
+        let bottom = board.game.tiles().last().unwrap();
+        assert!(bottom[piece_start_x(BOARD_WIDTH, 3) as usize - 1].is_some());
+    }
+
+    #[test]
+    fn restarting_practice_deals_the_same_queue() {
+        let mut board = practice_board();
+        drop_piece(&mut board);
+        board.perform(Action::Restart);
+        assert_eq!(board.game.pieces_locked(), 0);
+        let setup = board.practice_setup().unwrap();
+        assert_eq!((setup.queue.as_str(), setup.repeat), ("IO", true));
+        assert_eq!(board.game.upcoming_piece(0).kind(), I);
+        assert_eq!(board.game.upcoming_piece(1).kind(), O);
+    }
+}
@@ -0,0 +1,579 @@
+// a player made of code. it tries every placement of the current piece (and of the held or next
+// one) on copies of the game, scores the boards they leave and then presses the same actions a
+// person would to get the piece there
+use crate::engine::Action;
+use crate::engine::Game;
+use crate::engine::GameSettings;
+use crate::engine::Phase;
+use crate::external_bot::ExternalBot;
+use crate::piece::Piece;
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+// weights of the board evaluation
+const AGGREGATE_HEIGHT_WEIGHT: f64 = -0.510066;
+const LINES_WEIGHT: f64 = 0.760666;
+const HOLES_WEIGHT: f64 = -0.35663;
+const BUMPINESS_WEIGHT: f64 = -0.184483;
+// placements looked further into at every step of the lookahead, the rest are dropped early
+const BEAM_WIDTH: usize = 6;
+// placements behind the best a mistake picks from
+const MISTAKE_CHOICES: usize = 4;
+
+// pieces the search looks at, the current one included
+pub const DEFAULT_LOOKAHEAD: usize = 2;
+// game time between two actions
+pub const DEFAULT_ACTION_MS: u64 = 40;
+
+// where a piece can end up and what to press to get it there
+#[derive(Clone)]
+pub struct Placement {
+    pub actions: Vec<Action>,
+    pub landing: Piece, // the piece where it locks
+    pub score: f64,     // of the best board it leads to, higher is better
+}
+
+// the placements of the current piece best first, each scored by the best board it leads to
+// within lookahead pieces
+pub fn ranked_placements(game: &Game, lookahead: usize) -> Vec<Placement> {
+    let mut ranked: Vec<Placement> = candidates(game)
+        .into_iter()
+        .map(|(actions, landing, after)| Placement {
+            score: evaluate(game, &after),
+            actions,
+            landing,
+        })
+        .collect();
+    sort_best_first(&mut ranked, |p| p.score);
+    if lookahead > 1 {
+        for placement in ranked.iter_mut().take(BEAM_WIDTH) {
+            let mut after = lookahead_copy(game);
+            for action in placement.actions.iter() {
+                play(&mut after, *action);
+            }
+            placement.score = best_score(game, after, lookahead - 1);
+        }
+        // what's past the beam was never looked into, so it stays behind what was
+        ranked.truncate(BEAM_WIDTH);
+        sort_best_first(&mut ranked, |p| p.score);
+    }
+    ranked
+}
+
+pub fn best_placement(game: &Game, lookahead: usize) -> Option<Placement> {
+    ranked_placements(game, lookahead).into_iter().next()
+}
+
+fn sort_best_first<T>(items: &mut [T], score: impl Fn(&T) -> f64) {
+    items.sort_by(|a, b| score(b).total_cmp(&score(a)));
+}
+
+// the best board reachable from after with depth more pieces, scored against root
+fn best_score(root: &Game, mut after: Game, depth: usize) -> f64 {
+    if after.is_over() {
+        return f64::NEG_INFINITY;
+    }
+    wait_for_piece(&mut after);
+    let mut next: Vec<(f64, Game)> = candidates(&after)
+        .into_iter()
+        .map(|(_, _, game)| (evaluate(root, &game), game))
+        .collect();
+    if next.is_empty() {
+        return evaluate(root, &after);
+    }
+    sort_best_first(&mut next, |n| n.0);
+    match depth > 1 {
+        false => next[0].0,
+        true => next
+            .into_iter()
+            .take(BEAM_WIDTH)
+            .map(|(_, game)| best_score(root, game, depth - 1))
+            .fold(f64::NEG_INFINITY, f64::max),
+    }
+}
+
+// runs the clock up to when the next piece is in play
+fn wait_for_piece(game: &mut Game) {
+    while !game.is_over() && game.current_piece().is_none() {
+        match game.phase() {
+            Phase::Clearing { until_ms } => game.advance_to(until_ms),
+            Phase::Spawning { at_ms } => game.advance_to(at_ms),
+            _ => break,
+        };
+    }
+}
+
+// like pressing the action, except a held piece that isn't in play yet is waited for
+fn play(game: &mut Game, action: Action) {
+    if action != Action::Hold {
+        wait_for_piece(game);
+    }
+    game.apply(action);
+}
+
+// a copy of the game to try placements on, how they're pressed doesn't matter there
+fn lookahead_copy(game: &Game) -> Game {
+    let mut copy = game.clone();
+    copy.set_judging_finesse(false);
+    copy
+}
+
+// every distinct placement as its actions, where the piece lands and the game after it locked
+fn candidates(game: &Game) -> Vec<(Vec<Action>, Piece, Game)> {
+    let mut candidates = Vec::new();
+    if game.current_piece().is_none() {
+        return candidates;
+    }
+    let mut seen = HashSet::new();
+    for hold in [false, true] {
+        let mut base = lookahead_copy(game);
+        let mut prefix = Vec::new();
+        if hold {
+            play(&mut base, Action::Hold);
+            wait_for_piece(&mut base);
+            // holding didn't happen or swapped in the same piece
+            let held = |g: &Game| g.held_piece().map(|p| p.kind());
+            if held(&base) == held(game) || base.current_piece().is_none() {
+                continue;
+            }
+            prefix.push(Action::Hold);
+        }
+        let turns: [&[Action]; 4] = [
+            &[],
+            &[Action::RotateRight],
+            &[Action::Rotate180],
+            &[Action::RotateLeft],
+        ];
+        for turn in turns {
+            let mut turned = base.clone();
+            let mut actions = prefix.clone();
+            for action in turn {
+                play(&mut turned, *action);
+                actions.push(*action);
+            }
+            play(&mut turned, Action::InstantLeft);
+            actions.push(Action::InstantLeft);
+            while let Some(landing) = turned.ghost_piece() {
+                let mut cells: Vec<(i8, i8)> = landing.cells().collect();
+                cells.sort();
+                if seen.insert((hold, cells)) {
+                    let mut after = turned.clone();
+                    play(&mut after, Action::HardDrop);
+                    let mut placed = actions.clone();
+                    placed.push(Action::HardDrop);
+                    candidates.push((placed, landing, after));
+                }
+                // against the right wall
+                let coord = turned.current_piece().map(|p| p.coord());
+                play(&mut turned, Action::MoveRight);
+                if turned.current_piece().map(|p| p.coord()) == coord {
+                    break;
+                }
+                actions.push(Action::MoveRight);
+            }
+        }
+    }
+    candidates
+}
+
+// how good the board is, lines count everything cleared since before
+pub fn evaluate(before: &Game, after: &Game) -> f64 {
+    if after.is_over() {
+        return f64::NEG_INFINITY;
+    }
+    // rows that are full but haven't collapsed yet count as cleared
+    let rows: Vec<&Vec<_>> = after
+        .tiles()
+        .iter()
+        .filter(|row| !row.iter().all(|t| t.is_some()))
+        .collect();
+    let full = after.tiles().len() - rows.len();
+    let lines = (after.lines() - before.lines()) as usize + full;
+    let mut heights = vec![0; after.width()];
+    let mut holes = 0;
+    for (x, height) in heights.iter_mut().enumerate() {
+        let top = rows.iter().position(|row| row[x].is_some());
+        if let Some(top) = top {
+            *height = rows.len() - top;
+            holes += rows[top..].iter().filter(|row| row[x].is_none()).count();
+        }
+    }
+    let aggregate_height: usize = heights.iter().sum();
+    let bumpiness: usize = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+    AGGREGATE_HEIGHT_WEIGHT * aggregate_height as f64
+        + LINES_WEIGHT * lines as f64
+        + HOLES_WEIGHT * holes as f64
+        + BUMPINESS_WEIGHT * bumpiness as f64
+}
+
+// how well the built in bot plays
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        Difficulty::ALL.into_iter().find(|d| d.name() == name)
+    }
+    pub fn title(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Expert => "Expert",
+        }
+    }
+    pub fn lookahead(&self) -> usize {
+        match self {
+            Difficulty::Easy | Difficulty::Medium => 1,
+            Difficulty::Hard | Difficulty::Expert => 2,
+        }
+    }
+    // game time from one piece to the next at the least, 0 leaves only the pace of the actions
+    pub fn piece_ms(&self) -> u64 {
+        match self {
+            Difficulty::Easy => 1000,
+            Difficulty::Medium => 500,
+            Difficulty::Hard => 300,
+            Difficulty::Expert => 0,
+        }
+    }
+    // chance of taking one of the next best placements instead of the best
+    pub fn mistake_percent(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 30,
+            Difficulty::Medium => 10,
+            Difficulty::Hard => 3,
+            Difficulty::Expert => 0,
+        }
+    }
+}
+
+// a bot as it's given on the command line, a difficulty of the built in one or a program to run
+#[derive(Clone, Debug, PartialEq)]
+pub enum BotSpec {
+    Builtin(Difficulty),
+    External(String), // the command line
+}
+
+impl BotSpec {
+    pub fn parse(spec: &str) -> BotSpec {
+        match Difficulty::from_name(spec) {
+            Some(difficulty) => BotSpec::Builtin(difficulty),
+            None => BotSpec::External(spec.to_string()),
+        }
+    }
+    pub fn name(&self) -> &str {
+        match self {
+            BotSpec::Builtin(difficulty) => difficulty.name(),
+            BotSpec::External(command) => command,
+        }
+    }
+    // a bot for a game with these settings, external ones are started for it. with waits the game
+    // waits for every answer of an external bot, for playing without a screen
+    pub fn start(&self, settings: &GameSettings, seed: u64, waits: bool) -> Result<Bot, String> {
+        match self {
+            BotSpec::Builtin(difficulty) => Ok(Bot::with_difficulty(*difficulty, seed)),
+            BotSpec::External(command) => {
+                let bot = ExternalBot::start(command, settings)?;
+                Ok(Bot::external(match waits {
+                    true => bot.waiting(),
+                    false => bot,
+                }))
+            }
+        }
+    }
+}
+
+// what decides the placements
+enum Brain {
+    Search {
+        lookahead: usize,
+        mistake_percent: u32,
+    },
+    External(ExternalBot),
+}
+
+// plays a game one action at a time at a steady pace
+pub struct Bot {
+    brain: Brain,
+    rng: ChaCha8Rng, // for the mistakes
+    action_ms: u64,
+    piece_ms: u64,
+    plan: VecDeque<Action>,
+    next_action_ms: u64,
+    next_piece_ms: u64,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Bot::new(DEFAULT_LOOKAHEAD, DEFAULT_ACTION_MS)
+    }
+}
+
+impl Bot {
+    // always takes the best placement, as fast as action_ms allows
+    pub fn new(lookahead: usize, action_ms: u64) -> Self {
+        Bot::with_brain(
+            Brain::Search {
+                lookahead: lookahead.max(1),
+                mistake_percent: 0,
+            },
+            0,
+            action_ms,
+            0,
+        )
+    }
+    // the seed picks the mistakes
+    pub fn with_difficulty(difficulty: Difficulty, seed: u64) -> Self {
+        Bot::with_brain(
+            Brain::Search {
+                lookahead: difficulty.lookahead(),
+                mistake_percent: difficulty.mistake_percent(),
+            },
+            seed,
+            DEFAULT_ACTION_MS,
+            difficulty.piece_ms(),
+        )
+    }
+    pub fn external(bot: ExternalBot) -> Self {
+        Bot::with_brain(Brain::External(bot), 0, DEFAULT_ACTION_MS, 0)
+    }
+    fn with_brain(brain: Brain, seed: u64, action_ms: u64, piece_ms: u64) -> Self {
+        Bot {
+            brain,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            action_ms,
+            piece_ms,
+            plan: VecDeque::new(),
+            next_action_ms: 0,
+            next_piece_ms: 0,
+        }
+    }
+    // who's playing, for showing next to the board
+    pub fn name(&self) -> String {
+        match &self.brain {
+            Brain::Search { .. } => "Bot".to_string(),
+            Brain::External(bot) => bot.name().to_string(),
+        }
+    }
+    // why an external bot gave up, if it did
+    pub fn error(&self) -> Option<&str> {
+        match &self.brain {
+            Brain::Search { .. } => None,
+            Brain::External(bot) => bot.error(),
+        }
+    }
+    // forgets the game it was playing
+    pub fn reset(&mut self) {
+        self.plan.clear();
+        self.next_action_ms = 0;
+        self.next_piece_ms = 0;
+        if let Brain::External(bot) = &mut self.brain {
+            bot.reset();
+        }
+    }
+    // game time when it's worth asking for the next action again
+    fn wake_ms(&self) -> u64 {
+        match self.plan.is_empty() {
+            true => self.next_action_ms.max(self.next_piece_ms),
+            false => self.next_action_ms,
+        }
+    }
+    // what to press now, None while it's not time yet or there's no piece to move
+    pub fn next_action(&mut self, game: &Game) -> Option<Action> {
+        if game.is_over() || game.clock_ms() < self.next_action_ms {
+            return None;
+        }
+        if self.plan.is_empty() {
+            game.current_piece()?;
+            if game.clock_ms() < self.next_piece_ms {
+                return None;
+            }
+            self.plan = self.choose(game)?.into();
+            self.next_piece_ms = game.clock_ms() + self.piece_ms;
+        }
+        // after holding into an empty hold the next piece has to spawn first
+        if self.plan.front() != Some(&Action::Hold) {
+            game.current_piece()?;
+        }
+        let action = self.plan.pop_front()?;
+        self.next_action_ms = game.clock_ms() + self.action_ms;
+        Some(action)
+    }
+    fn choose(&mut self, game: &Game) -> Option<Vec<Action>> {
+        match &mut self.brain {
+            Brain::Search {
+                lookahead,
+                mistake_percent,
+            } => {
+                let rng = &mut self.rng;
+                let mut ranked = ranked_placements(game, *lookahead);
+                // a slip is one of the few placements behind the best, never one that tops out
+                let safe = ranked.iter().filter(|p| p.score.is_finite()).count();
+                let pick = match safe > 1 && rng.random_range(0..100) < *mistake_percent {
+                    true => rng.random_range(1..safe.min(MISTAKE_CHOICES + 1)),
+                    false => 0,
+                };
+                (pick < ranked.len()).then(|| ranked.swap_remove(pick).actions)
+            }
+            Brain::External(bot) => bot.suggest(game),
+        }
+    }
+}
+
+// plays a game without a board until it's over or max_pieces were placed, returns the pieces placed
+pub fn play_out(game: &mut Game, bot: &mut Bot, max_pieces: usize) -> usize {
+    let mut pieces = 0;
+    while !game.is_over() && pieces < max_pieces && bot.error().is_none() {
+        match bot.next_action(game) {
+            Some(action) => {
+                game.apply(action);
+                if action == Action::HardDrop {
+                    pieces += 1;
+                }
+            }
+            None => {
+                let ms = bot.wake_ms().max(game.clock_ms() + 1);
+                game.advance_to(ms);
+            }
+        }
+    }
+    pieces
+}
+
+// two bots against each other without a board, with the same pieces and garbage sent across. the
+// side that's left wins, None when both go out together or both placed max_pieces. a bot that gives
+// up is out
+pub fn play_versus(
+    settings: &GameSettings,
+    bots: [&mut Bot; 2],
+    max_pieces: usize,
+) -> Option<usize> {
+    let mut games = [
+        Game::new(settings.clone()),
+        Game::new(settings.clone()),
+    ];
+    let mut pieces = [0, 0];
+    let mut clock_ms = 0;
+    loop {
+        for side in 0..2 {
+            games[side].advance_to(clock_ms);
+            while let Some(action) = bots[side].next_action(&games[side]) {
+                games[side].apply(action);
+                if action == Action::HardDrop {
+                    pieces[side] += 1;
+                }
+            }
+        }
+        let sent = [0, 1].map(|side| games[side].take_outgoing_garbage());
+        games[0].receive_garbage(sent[1]);
+        games[1].receive_garbage(sent[0]);
+        let out = [0, 1].map(|side| games[side].is_over() || bots[side].error().is_some());
+        match out {
+            [true, true] => return None,
+            [true, false] => return Some(1),
+            [false, true] => return Some(0),
+            _ if pieces.iter().all(|p| *p >= max_pieces) => return None,
+            _ => {}
+        }
+        // a side that's done with its pieces waits for the other one
+        let wake = [0, 1].map(|side| match pieces[side] >= max_pieces {
+            true => u64::MAX,
+            false => bots[side].wake_ms(),
+        });
+        clock_ms = wake[0].min(wake[1]).max(clock_ms + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Mode;
+    use crate::garbage::GarbageStyle;
+    use crate::piece_set::PieceSet;
+    use crate::tile::Block;
+    use crate::tile::Tile;
+    use std::sync::Arc;
+
+    const I: usize = 0;
+    const O: usize = 1;
+
+    // I in play and O next, over four rows full but for the right column
+    fn well_game() -> Game {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 1,
+            timing: Mode::Marathon.timing(),
+            width: 10,
+            height: 20,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        let mut rows: Vec<Vec<Tile>> = vec![vec![None; 10]; 20];
+        for row in rows[16..].iter_mut() {
+            row[..9].fill(Some(Block::Garbage));
+        }
+        game.set_stack(&rows);
+        game.set_queue(&[O], true);
+        game.replace_current_piece(I);
+        game
+    }
+
+    #[test]
+    fn placements_come_best_first_and_end_in_a_hard_drop() {
+        let game = well_game();
+        let ranked = ranked_placements(&game, 1);
+        // 2 ways up for each piece, 7 and 10 columns across for the I, 9 for the O
+        assert_eq!(ranked.len(), 7 + 10 + 9);
+        assert!(ranked.windows(2).all(|w| w[0].score >= w[1].score));
+        for placement in ranked.iter() {
+            assert_eq!(placement.actions.last(), Some(&Action::HardDrop));
+            let drops = placement.actions.iter().filter(|a| **a == Action::HardDrop);
+            assert_eq!(drops.count(), 1);
+        }
+        assert!(ranked.iter().any(|p| p.actions[0] == Action::Hold));
+    }
+
+    #[test]
+    fn the_well_is_filled() {
+        let game = well_game();
+        for lookahead in [1, DEFAULT_LOOKAHEAD] {
+            let best = best_placement(&game, lookahead).unwrap();
+            let mut cells: Vec<(i8, i8)> = best.landing.cells().collect();
+            cells.sort();
+            let bottom = game.tiles().len() as i8 - 1;
+            assert_eq!(
+                cells,
+                (bottom - 3..=bottom).map(|y| (9, y)).collect::<Vec<_>>()
+            );
+        }
+        assert!(ranked_placements(&game, DEFAULT_LOOKAHEAD).len() <= BEAM_WIDTH);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::board;
+use crate::board::Hints;
+use crate::bot::Difficulty;
+use crate::controls::KeyBindings;
+use crate::controls::VersusControls;
+use crate::engine;
+use crate::engine::Mode;
+use crate::save;
+use serde::{Deserialize, Serialize};
+use std::io;
+
+pub const MIN_STARTING_LEVEL: u8 = 1;
+pub const MAX_STARTING_LEVEL: u8 = 9;
+
+// every user setting, (de)serialized as toml so it stays human-editable
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub mode: Mode,
+    pub starting_level: u8,
+    pub ghost_piece_on: bool,
+    pub music_on: bool,
+    pub controls: KeyBindings,
+    pub versus_controls: VersusControls,
+    // override the mode's own delays when set
+    pub entry_delay_ms: Option<u64>,
+    pub line_clear_delay_ms: Option<u64>,
+    pub board_width: usize,
+    pub board_height: usize, // visible rows
+    pub pieces: String,      // built in piece set name or path to a set file
+    pub bot_difficulty: Difficulty,
+    pub hints: Hints,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            ghost_piece_on: true,
+            music_on: true,
+            controls: KeyBindings::default(),
+            versus_controls: VersusControls::default(),
+            entry_delay_ms: None,
+            line_clear_delay_ms: None,
+            board_width: board::BOARD_WIDTH,
+            board_height: board::BOARD_HEIGHT,
+            pieces: "tetrominoes".to_string(),
+            bot_difficulty: Difficulty::default(),
+            hints: Hints::default(),
+        }
+    }
+}
+
+impl Config {
+    // catch values that parse fine but make no sense for the game
+    fn validate(&self) -> Result<(), String> {
+        if !(MIN_STARTING_LEVEL..=MAX_STARTING_LEVEL).contains(&self.starting_level) {
+            return Err(format!(
+                "starting_level must be between {} and {}, got {}",
+                MIN_STARTING_LEVEL, MAX_STARTING_LEVEL, self.starting_level
+            ));
+        }
+        validate_board_size(self.board_width, self.board_height)?;
+        let delays = [
+            ("entry_delay_ms", self.entry_delay_ms),
+            ("line_clear_delay_ms", self.line_clear_delay_ms),
+        ];
+        for (name, delay) in delays {
+            if delay.is_some_and(|ms| ms > engine::MAX_DELAY_MS) {
+                return Err(format!(
+                    "{} must be at most {}, got {}",
+                    name,
+                    engine::MAX_DELAY_MS,
+                    delay.unwrap_or_default()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn validate_board_size(width: usize, height: usize) -> Result<(), String> {
+    if !(board::MIN_BOARD_WIDTH..=board::MAX_BOARD_WIDTH).contains(&width) {
+        return Err(format!(
+            "board width must be between {} and {}, got {}",
+            board::MIN_BOARD_WIDTH,
+            board::MAX_BOARD_WIDTH,
+            width
+        ));
+    }
+    if !(board::MIN_BOARD_HEIGHT..=board::MAX_BOARD_HEIGHT).contains(&height) {
+        return Err(format!(
+            "board height must be between {} and {}, got {}",
+            board::MIN_BOARD_HEIGHT,
+            board::MAX_BOARD_HEIGHT,
+            height
+        ));
+    }
+    Ok(())
+}
+
+pub fn parse(content: &str) -> Result<Config, String> {
+    let config: Config = toml::from_str(content).map_err(|e| e.to_string())?;
+    config.validate()?;
+    Ok(config)
+}
+
+// a missing config file is not an error, we just fall back to the defaults, a broken one is
+// moved aside and its backup used instead, the caller gets told so it can tell the player
+pub fn load() -> io::Result<save::Loaded<Config>> {
+    save::read_settings(parse)
+}
+
+pub fn save(config: &Config) -> io::Result<()> {
+    let content = toml::to_string(config).map_err(io::Error::other)?;
+    save::write_settings(&content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_override_the_mode_up_to_the_limit() {
+        let config = parse("entry_delay_ms = 0\nline_clear_delay_ms = 1000\n").unwrap();
+        assert_eq!(config.entry_delay_ms, Some(0));
+        assert_eq!(config.line_clear_delay_ms, Some(engine::MAX_DELAY_MS));
+        assert_eq!(parse("").unwrap().entry_delay_ms, None);
+        assert!(parse("entry_delay_ms = 1001\n").is_err());
+        assert!(parse("line_clear_delay_ms = -1\n").is_err());
+    }
+
+    #[test]
+    fn board_sizes_at_the_limits() {
+        let (min_w, max_w) = (board::MIN_BOARD_WIDTH, board::MAX_BOARD_WIDTH);
+        let (min_h, max_h) = (board::MIN_BOARD_HEIGHT, board::MAX_BOARD_HEIGHT);
+        assert!(validate_board_size(min_w, min_h).is_ok());
+        assert!(validate_board_size(max_w, max_h).is_ok());
+        assert!(validate_board_size(min_w - 1, min_h).is_err());
+        assert!(validate_board_size(max_w + 1, min_h).is_err());
+        assert!(validate_board_size(min_w, min_h - 1).is_err());
+        assert!(validate_board_size(min_w, max_h + 1).is_err());
+        assert!(parse("board_width = 21\n").is_err());
+        assert_eq!(parse("board_height = 40\n").unwrap().board_height, 40);
+    }
+}
@@ -0,0 +1,417 @@
+use crate::engine::Action;
+use cursive::event::{Event, Key};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// keys that always mean something else and can't be bound
+const RESERVED_KEYS: [Event; 1] = [Event::Key(Key::Esc)]; // pause menu
+
+// which keys trigger which action, an action can have any number of keys but a key only ever
+// maps to one action
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<String, Vec<String>>",
+    into = "BTreeMap<String, Vec<String>>"
+)]
+pub struct KeyBindings {
+    keys: Vec<(Action, Vec<Event>)>, // in `Action::ALL` order
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::from_defaults(|action| match action {
+            Action::MoveLeft => vec![Event::Key(Key::Left)],
+            Action::MoveRight => vec![Event::Key(Key::Right)],
+            Action::SoftDrop => vec![Event::Key(Key::Down)],
+            Action::HardDrop => vec![Event::Key(Key::Up)],
+            Action::RotateLeft => vec![Event::Char('z')],
+            Action::RotateRight => vec![Event::Char('x')],
+            Action::Rotate180 => vec![Event::Char('a')],
+            Action::SonicDrop | Action::InstantLeft | Action::InstantRight => Vec::new(),
+            Action::Hold => vec![Event::Char('c')],
+            Action::Restart => vec![Event::Char('r')],
+            Action::Undo => vec![Event::Char('u')],
+            Action::Redo => vec![Event::Char('i')],
+            Action::PickPiece => vec![Event::Char('p')],
+        })
+    }
+}
+
+impl KeyBindings {
+    fn from_defaults(defaults: impl Fn(Action) -> Vec<Event>) -> Self {
+        Self {
+            keys: Action::ALL.iter().map(|a| (*a, defaults(*a))).collect(),
+        }
+    }
+    // the default keys of either side of a versus game, player 1 on the left of the keyboard and
+    // player 2 on the right
+    pub fn versus(player: usize) -> Self {
+        let char_keys = |c: char| vec![Event::Char(c)];
+        match player {
+            1 => KeyBindings::from_defaults(|action| match action {
+                Action::MoveLeft => char_keys('a'),
+                Action::MoveRight => char_keys('d'),
+                Action::SoftDrop => char_keys('s'),
+                Action::HardDrop => char_keys('w'),
+                Action::RotateLeft => char_keys('q'),
+                Action::RotateRight => char_keys('e'),
+                Action::Hold => char_keys('f'),
+                _ => Vec::new(),
+            }),
+            _ => KeyBindings::from_defaults(|action| match action {
+                Action::MoveLeft => vec![Event::Key(Key::Left)],
+                Action::MoveRight => vec![Event::Key(Key::Right)],
+                Action::SoftDrop => vec![Event::Key(Key::Down)],
+                Action::HardDrop => vec![Event::Key(Key::Up)],
+                Action::RotateLeft => char_keys(','),
+                Action::RotateRight => char_keys('.'),
+                Action::Hold => char_keys('/'),
+                _ => Vec::new(),
+            }),
+        }
+    }
+    pub fn action_for(&self, event: &Event) -> Option<Action> {
+        self.keys
+            .iter()
+            .find(|(_, keys)| keys.contains(event))
+            .map(|(action, _)| *action)
+    }
+    pub fn keys_for(&self, action: Action) -> &[Event] {
+        self.keys
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or(&[])
+    }
+    // "left, a" or "unbound", for showing to the player
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self.keys_for(action).iter().filter_map(key_name).collect();
+        match names.is_empty() {
+            true => "unbound".to_string(),
+            false => names.join(", "),
+        }
+    }
+    // adds a key to an action, refusing keys that are reserved or already used elsewhere
+    pub fn bind(&mut self, action: Action, key: Event) -> Result<(), BindError> {
+        if RESERVED_KEYS.contains(&key) || key_name(&key).is_none() {
+            return Err(BindError::Unbindable);
+        }
+        match self.action_for(&key) {
+            Some(other) if other == action => Ok(()),
+            Some(other) => Err(BindError::Conflict(other)),
+            None => {
+                self.keys_mut(action).push(key);
+                Ok(())
+            }
+        }
+    }
+    // takes the key away from whatever action had it and gives it to this one
+    pub fn rebind(&mut self, action: Action, key: Event) -> Result<(), BindError> {
+        for (_, keys) in self.keys.iter_mut() {
+            keys.retain(|k| *k != key);
+        }
+        self.bind(action, key)
+    }
+    pub fn clear(&mut self, action: Action) {
+        self.keys_mut(action).clear();
+    }
+    fn keys_mut(&mut self, action: Action) -> &mut Vec<Event> {
+        let idx = match self.keys.iter().position(|(a, _)| *a == action) {
+            Some(idx) => idx,
+            None => {
+                self.keys.push((action, Vec::new()));
+                self.keys.len() - 1
+            }
+        };
+        &mut self.keys[idx].1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BindError {
+    Unbindable,
+    Conflict(Action),
+}
+
+// config file form: `move_left = ["left", "a"]`, actions that are left out keep their defaults
+// unless the file gives one of their keys to another action
+impl TryFrom<BTreeMap<String, Vec<String>>> for KeyBindings {
+    type Error = String;
+    fn try_from(map: BTreeMap<String, Vec<String>>) -> Result<Self, Self::Error> {
+        with_overrides(KeyBindings::default(), map)
+    }
+}
+
+fn with_overrides(
+    mut bindings: KeyBindings,
+    map: BTreeMap<String, Vec<String>>,
+) -> Result<KeyBindings, String> {
+    let mut listed = Vec::new();
+    for (action_name, key_names) in map.iter() {
+        let action = Action::from_name(action_name)
+            .ok_or_else(|| format!("unknown action `{}` in controls", action_name))?;
+        for key_name in key_names {
+            let key = parse_key(key_name)
+                .ok_or_else(|| format!("unknown key `{}` for {}", key_name, action_name))?;
+            listed.push((action, key_name, key));
+        }
+    }
+    for (action, keys) in bindings.keys.iter_mut() {
+        match map.contains_key(action.name()) {
+            true => keys.clear(),
+            false => keys.retain(|k| !listed.iter().any(|(_, _, key)| key == k)),
+        }
+    }
+    for (action, key_name, key) in listed {
+        match bindings.bind(action, key) {
+            Ok(()) => {}
+            Err(BindError::Unbindable) => {
+                return Err(format!("`{}` can't be bound", key_name));
+            }
+            Err(BindError::Conflict(other)) => {
+                return Err(format!(
+                    "`{}` is bound to both {} and {}",
+                    key_name,
+                    other.name(),
+                    action.name()
+                ));
+            }
+        }
+    }
+    Ok(bindings)
+}
+
+impl From<KeyBindings> for BTreeMap<String, Vec<String>> {
+    fn from(bindings: KeyBindings) -> Self {
+        bindings
+            .keys
+            .iter()
+            .map(|(action, keys)| {
+                (
+                    action.name().to_string(),
+                    keys.iter().filter_map(key_name).collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+// the keys of both sides of a local versus game, each side starts from its own defaults and a
+// key can only belong to one of them
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "VersusControlsFile", into = "VersusControlsFile")]
+pub struct VersusControls {
+    players: [KeyBindings; 2],
+}
+
+impl Default for VersusControls {
+    fn default() -> Self {
+        Self {
+            players: [
+                KeyBindings::versus(1),
+                KeyBindings::versus(2),
+            ],
+        }
+    }
+}
+
+impl VersusControls {
+    // player 1 or 2
+    pub fn player(&self, player: usize) -> &KeyBindings {
+        &self.players[player.clamp(1, 2) - 1]
+    }
+}
+
+// config file form: a `[versus_controls.player1]` and `[versus_controls.player2]` table, each
+// like `[controls]`
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct VersusControlsFile {
+    player1: BTreeMap<String, Vec<String>>,
+    player2: BTreeMap<String, Vec<String>>,
+}
+
+impl TryFrom<VersusControlsFile> for VersusControls {
+    type Error = String;
+    fn try_from(file: VersusControlsFile) -> Result<Self, Self::Error> {
+        let players = [
+            with_overrides(KeyBindings::versus(1), file.player1)?,
+            with_overrides(KeyBindings::versus(2), file.player2)?,
+        ];
+        for (_, keys) in players[0].keys.iter() {
+            if let Some(key) = keys.iter().find(|k| players[1].action_for(k).is_some()) {
+                return Err(format!(
+                    "`{}` is bound for both players",
+                    key_name(key).unwrap_or_default()
+                ));
+            }
+        }
+        Ok(Self { players })
+    }
+}
+
+impl From<VersusControls> for VersusControlsFile {
+    fn from(controls: VersusControls) -> Self {
+        let [player1, player2] = controls.players;
+        Self {
+            player1: player1.into(),
+            player2: player2.into(),
+        }
+    }
+}
+
+const NAMED_KEYS: [(&str, Key); 26] = [
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("enter", Key::Enter),
+    ("tab", Key::Tab),
+    ("backspace", Key::Backspace),
+    ("insert", Key::Ins),
+    ("delete", Key::Del),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("esc", Key::Esc),
+    ("f1", Key::F1),
+    ("f2", Key::F2),
+    ("f3", Key::F3),
+    ("f4", Key::F4),
+    ("f5", Key::F5),
+    ("f6", Key::F6),
+    ("f7", Key::F7),
+    ("f8", Key::F8),
+    ("f9", Key::F9),
+    ("f10", Key::F10),
+    ("f11", Key::F11),
+    ("f12", Key::F12),
+];
+
+fn named_key(name: &str) -> Option<Key> {
+    NAMED_KEYS.iter().find(|(n, _)| *n == name).map(|(_, k)| *k)
+}
+fn key_to_name(key: Key) -> Option<&'static str> {
+    NAMED_KEYS.iter().find(|(_, k)| *k == key).map(|(n, _)| *n)
+}
+
+// names as written in the config file: "a", "Z" (shift+z), "space", "left", "ctrl+a",
+// "shift+left" and so on
+pub fn parse_key(name: &str) -> Option<Event> {
+    if name == "space" {
+        return Some(Event::Char(' '));
+    }
+    let single_char = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    if let Some(c) = single_char(name) {
+        return Some(Event::Char(c));
+    }
+    if let Some(key) = named_key(name) {
+        return Some(Event::Key(key));
+    }
+    let (modifier, rest) = name.split_once('+')?;
+    match (modifier, single_char(rest), named_key(rest)) {
+        ("ctrl", Some(c), _) => Some(Event::CtrlChar(c)),
+        ("alt", Some(c), _) => Some(Event::AltChar(c)),
+        ("ctrl", None, Some(key)) => Some(Event::Ctrl(key)),
+        ("alt", None, Some(key)) => Some(Event::Alt(key)),
+        ("shift", None, Some(key)) => Some(Event::Shift(key)),
+        _ => None,
+    }
+}
+
+// inverse of `parse_key`, None for events that aren't keys
+pub fn key_name(event: &Event) -> Option<String> {
+    match event {
+        Event::Char(' ') => Some("space".to_string()),
+        Event::Char(c) => Some(c.to_string()),
+        Event::CtrlChar(c) => Some(format!("ctrl+{}", c)),
+        Event::AltChar(c) => Some(format!("alt+{}", c)),
+        Event::Key(key) => key_to_name(*key).map(|n| n.to_string()),
+        Event::Ctrl(key) => key_to_name(*key).map(|n| format!("ctrl+{}", n)),
+        Event::Alt(key) => key_to_name(*key).map(|n| format!("alt+{}", n)),
+        Event::Shift(key) => key_to_name(*key).map(|n| format!("shift+{}", n)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(entries: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(action, keys)| {
+                (
+                    action.to_string(),
+                    keys.iter().map(|k| k.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_key_belongs_to_one_action() {
+        let mut bindings = KeyBindings::default();
+        assert_eq!(
+            bindings.bind(Action::Hold, Event::Char('z')),
+            Err(BindError::Conflict(Action::RotateLeft))
+        );
+        assert_eq!(bindings.bind(Action::RotateLeft, Event::Char('z')), Ok(()));
+        assert_eq!(
+            bindings.bind(Action::Hold, Event::Key(Key::Esc)),
+            Err(BindError::Unbindable)
+        );
+        assert_eq!(bindings.rebind(Action::Hold, Event::Char('z')), Ok(()));
+        assert_eq!(bindings.action_for(&Event::Char('z')), Some(Action::Hold));
+        assert_eq!(bindings.describe(Action::RotateLeft), "unbound");
+    }
+
+    #[test]
+    fn the_file_takes_keys_from_the_defaults() {
+        let bindings = KeyBindings::try_from(overrides(&[("hold", &["z", "shift+left"])])).unwrap();
+        assert_eq!(bindings.action_for(&Event::Char('z')), Some(Action::Hold));
+        assert_eq!(bindings.keys_for(Action::RotateLeft), &[]);
+        assert_eq!(bindings.action_for(&Event::Char('c')), None);
+        let written = BTreeMap::from(bindings.clone());
+        assert_eq!(KeyBindings::try_from(written).unwrap(), bindings);
+    }
+
+    #[test]
+    fn the_file_cant_give_a_key_twice() {
+        let twice = overrides(&[
+            ("hold", &["z"]),
+            ("rotate_left", &["z"]),
+        ]);
+        assert!(KeyBindings::try_from(twice).is_err());
+        assert!(KeyBindings::try_from(overrides(&[("hold", &["esc"])])).is_err());
+        assert!(KeyBindings::try_from(overrides(&[("fly", &["f"])])).is_err());
+    }
+
+    #[test]
+    fn versus_players_keep_their_keys_apart() {
+        let controls = VersusControls::default();
+        assert_eq!(
+            controls.player(1).action_for(&Event::Char('a')),
+            Some(Action::MoveLeft)
+        );
+        assert_eq!(controls.player(2).action_for(&Event::Char('a')), None);
+        let shared = VersusControlsFile {
+            player1: overrides(&[("hold", &["/"])]),
+            player2: BTreeMap::new(),
+        };
+        assert!(VersusControls::try_from(shared).is_err());
+        let moved = VersusControlsFile {
+            player1: overrides(&[("hold", &["/"])]),
+            player2: overrides(&[("hold", &["enter"])]),
+        };
+        assert!(VersusControls::try_from(moved).is_ok());
+    }
+}
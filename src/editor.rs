@@ -0,0 +1,199 @@
+// the board editor, cells are painted with the cursor keys or the mouse in the color of a piece of
+// the set (or garbage) to build a position to practice from
+use crate::ids;
+use crate::piece_set::PieceSet;
+use crate::position::Position;
+use crate::text_art::BLOCK_CHAR;
+use crate::tile::Block;
+use crate::tile::Tile;
+use cursive::Printer;
+use cursive::Vec2;
+use cursive::View;
+use cursive::direction::Direction;
+use cursive::event::Event;
+use cursive::event::EventResult;
+use cursive::event::Key;
+use cursive::event::MouseButton;
+use cursive::event::MouseEvent;
+use cursive::theme::BaseColor;
+use cursive::theme::Color;
+use cursive::utils::markup::StyledString;
+use cursive::view::CannotFocus;
+use cursive::views::SelectView;
+use std::sync::Arc;
+
+// what painting a cell puts there
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Brush {
+    Piece(usize), // the color of a kind of the set
+    Garbage,
+    Erase,
+}
+
+pub struct Editor {
+    pieces: Arc<PieceSet>,
+    tiles: Vec<Vec<Tile>>, // the visible rows from the top
+    cursor: (usize, usize),
+    brush: Brush,
+}
+
+impl Editor {
+    pub fn new(pieces: Arc<PieceSet>, width: usize, height: usize) -> Self {
+        Editor {
+            pieces,
+            tiles: vec![vec![None; width]; height],
+            cursor: (0, height - 1),
+            brush: Brush::Garbage,
+        }
+    }
+    pub fn from_position(position: &Position) -> Result<Self, String> {
+        let mut editor = Editor::new(
+            Arc::new(position.pieces.clone()),
+            position.width(),
+            position.height(),
+        );
+        editor.tiles = position.tiles()?;
+        Ok(editor)
+    }
+    pub fn position(&self, queue: &str, hold: &str) -> Position {
+        Position::from_tiles(&self.pieces, &self.tiles, queue, hold)
+    }
+    pub fn clear(&mut self) {
+        for row in self.tiles.iter_mut() {
+            row.fill(None);
+        }
+    }
+    pub fn set_brush(&mut self, brush: Brush) {
+        self.brush = brush;
+    }
+    // every brush in order, the pieces' colors first
+    pub fn brushes(&self) -> Vec<Brush> {
+        let mut brushes: Vec<Brush> = (0..self.pieces.len()).map(Brush::Piece).collect();
+        brushes.extend([Brush::Garbage, Brush::Erase]);
+        brushes
+    }
+    // a palette to pick the brush from, each piece's name in its color
+    pub fn palette(&self) -> SelectView<Brush> {
+        let mut palette = SelectView::new();
+        for brush in self.brushes() {
+            let label = match brush {
+                Brush::Piece(kind) => {
+                    let shape = self.pieces.shape(kind);
+                    StyledString::styled(
+                        format!("{} {}", BLOCK_CHAR, shape.name),
+                        shape.block.get_color(),
+                    )
+                }
+                Brush::Garbage => StyledString::styled(
+                    format!("{} Garbage", BLOCK_CHAR),
+                    Block::Garbage.get_color(),
+                ),
+                Brush::Erase => StyledString::plain("  Erase"),
+            };
+            palette.add_item(label, brush);
+        }
+        palette.set_selection(self.pieces.len());
+        palette
+    }
+    fn paint(&mut self, (x, y): (usize, usize), brush: Brush) {
+        let tile = match brush {
+            Brush::Piece(kind) => Some(self.pieces.shape(kind).block),
+            Brush::Garbage => Some(Block::Garbage),
+            Brush::Erase => None,
+        };
+        if let Some(cell) = self.tiles.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *cell = tile;
+        }
+    }
+    // the next or previous brush, shown as picked in the palette
+    fn cycle_brush(&mut self, step: isize) -> EventResult {
+        let brushes = self.brushes();
+        let at = brushes.iter().position(|b| *b == self.brush).unwrap_or(0);
+        let next = (at as isize + step).rem_euclid(brushes.len() as isize) as usize;
+        self.brush = brushes[next];
+        EventResult::with_cb(move |s| {
+            s.call_on_name(ids::EDITOR_PALETTE, |p: &mut SelectView<Brush>| {
+                let _ = p.set_selection(next);
+            });
+        })
+    }
+    fn move_cursor(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.tiles[0].len() as isize, self.tiles.len() as isize);
+        let x = (self.cursor.0 as isize + dx).clamp(0, width - 1);
+        let y = (self.cursor.1 as isize + dy).clamp(0, height - 1);
+        self.cursor = (x as usize, y as usize);
+    }
+    // the cell under a mouse position, cells are two chars wide
+    fn cell_at(&self, position: Vec2, offset: Vec2) -> Option<(usize, usize)> {
+        let local = position.checked_sub(offset)?;
+        let cell = (local.x / 2, local.y);
+        match cell.1 < self.tiles.len() && cell.0 < self.tiles[0].len() {
+            true => Some(cell),
+            false => None,
+        }
+    }
+}
+
+impl View for Editor {
+    fn draw(&self, printer: &Printer) {
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                let color = match tile {
+                    Some(block) => block.get_color(),
+                    None => Color::Dark(BaseColor::Black),
+                };
+                printer.with_style(color, |p| {
+                    p.print((x * 2, y), BLOCK_CHAR);
+                    p.print((x * 2 + 1, y), BLOCK_CHAR);
+                });
+            }
+        }
+        let (x, y) = self.cursor;
+        printer.with_style(Color::Light(BaseColor::White), |p| {
+            p.print((x * 2, y), "[");
+            p.print((x * 2 + 1, y), "]");
+        });
+    }
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        Vec2::new(self.tiles[0].len() * 2, self.tiles.len())
+    }
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+    fn on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Key(Key::Left) => self.move_cursor(-1, 0),
+            Event::Key(Key::Right) => self.move_cursor(1, 0),
+            Event::Key(Key::Up) => self.move_cursor(0, -1),
+            Event::Key(Key::Down) => self.move_cursor(0, 1),
+            Event::Char(' ') | Event::Key(Key::Enter) => self.paint(self.cursor, self.brush),
+            Event::Key(Key::Backspace) | Event::Key(Key::Del) => {
+                self.paint(self.cursor, Brush::Erase)
+            }
+            Event::Char('[') => return self.cycle_brush(-1),
+            Event::Char(']') => return self.cycle_brush(1),
+            // dragging paints every cell it passes over, the right button erases
+            Event::Mouse {
+                offset,
+                position,
+                event,
+            } => {
+                let brush = match event {
+                    MouseEvent::Press(MouseButton::Left) | MouseEvent::Hold(MouseButton::Left) => {
+                        self.brush
+                    }
+                    MouseEvent::Press(MouseButton::Right)
+                    | MouseEvent::Hold(MouseButton::Right) => Brush::Erase,
+                    _ => return EventResult::Ignored,
+                };
+                let Some(cell) = self.cell_at(position, offset) else {
+                    return EventResult::Ignored;
+                };
+                self.cursor = cell;
+                self.paint(cell, brush);
+            }
+            _ => return EventResult::Ignored,
+        }
+        EventResult::Consumed(None)
+    }
+}
@@ -0,0 +1,1230 @@
+use crate::board;
+use crate::board::BUFFER_HEIGHT;
+use crate::board::PIECE_START_Y;
+use crate::garbage;
+use crate::garbage::GarbageQueue;
+use crate::garbage::GarbageStyle;
+use crate::garbage::MAX_GARBAGE_PER_LOCK;
+use crate::piece::Piece;
+use crate::piece::PieceBag;
+use crate::piece_set::PieceSet;
+use crate::tile::Block;
+use crate::tile::Tile;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+const MAX_LEVEL: u8 = u8::MAX; //theoretically...
+// offsets tried in order when a 180 rotation is blocked, sideways first and then up so a piece
+// can flip out of a well or over a ledge (y grows downwards)
+const ROTATE_180_KICKS: [(i8, i8); 6] = [
+    (0, 0),
+    (1, 0),
+    (-1, 0),
+    (0, -1),
+    (1, -1),
+    (-1, -1),
+];
+
+// every input the engine understands, the ui translates keys into these and replays store them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateLeft,
+    RotateRight,
+    Rotate180,
+    SonicDrop,    // drop to the floor but keep the piece in play
+    InstantLeft,  // shift all the way to the wall
+    InstantRight, // shift all the way to the wall
+    Hold,
+    Restart, // handled by the front end, the engine ignores it
+    Undo,    // practice only, handled by the front end like restart
+    Redo,
+    PickPiece, // practice only, the piece in play becomes the next kind of the set
+}
+
+impl Action {
+    pub const ALL: [Action; 15] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::Rotate180,
+        Action::SonicDrop,
+        Action::InstantLeft,
+        Action::InstantRight,
+        Action::Hold,
+        Action::Restart,
+        Action::Undo,
+        Action::Redo,
+        Action::PickPiece,
+    ];
+    // stable name used in files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::RotateLeft => "rotate_left",
+            Action::RotateRight => "rotate_right",
+            Action::Rotate180 => "rotate_180",
+            Action::SonicDrop => "sonic_drop",
+            Action::InstantLeft => "instant_left",
+            Action::InstantRight => "instant_right",
+            Action::Hold => "hold",
+            Action::Restart => "restart",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::PickPiece => "pick_piece",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+    pub fn title(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::SoftDrop => "Soft Drop",
+            Action::HardDrop => "Hard Drop",
+            Action::RotateLeft => "Rotate Left",
+            Action::RotateRight => "Rotate Right",
+            Action::Rotate180 => "Rotate 180",
+            Action::SonicDrop => "Sonic Drop",
+            Action::InstantLeft => "Instant Left",
+            Action::InstantRight => "Instant Right",
+            Action::Hold => "Hold",
+            Action::Restart => "Restart",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::PickPiece => "Pick Piece",
+        }
+    }
+    // shown in the "Last Action" bubble
+    pub fn announcement(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move Left!",
+            Action::MoveRight => "Move Right!",
+            Action::SoftDrop => "Move Down!",
+            Action::HardDrop => "Fast Drop!",
+            Action::RotateLeft => "Rotate Left!",
+            Action::RotateRight => "Rotate Right!",
+            Action::Rotate180 => "Rotate 180!",
+            Action::SonicDrop => "Sonic Drop!",
+            Action::InstantLeft => "Instant Left!",
+            Action::InstantRight => "Instant Right!",
+            Action::Hold => "Hold!",
+            Action::Restart => "Restart!",
+            Action::Undo => "Undo!",
+            Action::Redo => "Redo!",
+            Action::PickPiece => "Pick Piece!",
+        }
+    }
+}
+
+// anything that happens to a game from outside of it, a replay is a list of these
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Act(Action),
+    Garbage(u32), // lines of attack sent by someone else
+}
+
+// what a game is played for, marathon is the classic endless game
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    #[default]
+    Marathon,
+    Sprint, // clear SPRINT_LINES as fast as possible
+    Ultra,  // as many points as possible in ULTRA_TIME_MS
+}
+
+pub const SPRINT_LINES: u32 = 40;
+// the longest delay the config may ask for, anything more is unplayable
+pub const MAX_DELAY_MS: u64 = 1000;
+pub const ULTRA_TIME_MS: u64 = 2 * 60 * 1000;
+
+impl Mode {
+    pub const ALL: [Mode; 3] = [
+        Mode::Marathon,
+        Mode::Sprint,
+        Mode::Ultra,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Marathon => "marathon",
+            Mode::Sprint => "sprint",
+            Mode::Ultra => "ultra",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|m| m.name() == name)
+    }
+    pub fn title(&self) -> &'static str {
+        match self {
+            Mode::Marathon => "Marathon",
+            Mode::Sprint => "Sprint",
+            Mode::Ultra => "Ultra",
+        }
+    }
+    // the delays the mode is played with unless the config says otherwise, the timed modes are
+    // snappier
+    pub fn timing(&self) -> Timing {
+        match self {
+            Mode::Marathon => Timing {
+                entry_delay_ms: 100,
+                line_clear_delay_ms: 200,
+            },
+            Mode::Sprint | Mode::Ultra => Timing {
+                entry_delay_ms: 50,
+                line_clear_delay_ms: 100,
+            },
+        }
+    }
+}
+
+// the pauses between one piece locking and the next spawning, rotate and hold inputs made during
+// them are buffered and applied as the piece spawns (IRS/IHS)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timing {
+    pub entry_delay_ms: u64, // ARE, from the lock (or the end of a clear) to the spawn
+    pub line_clear_delay_ms: u64, // full rows stay on the board this long before collapsing
+}
+
+// where the game is between one piece and the next
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Falling,
+    Locking,                    // resting on the stack, locks on the next gravity tick
+    Clearing { until_ms: u64 }, // full rows are still on the board
+    Spawning { at_ms: u64 },
+}
+
+// everything that has to be known up front to (re)play the same game
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameSettings {
+    pub mode: Mode,
+    pub starting_level: u8,
+    pub seed: u64,
+    pub timing: Timing,
+    pub width: usize,
+    pub height: usize, // visible rows, the hidden buffer comes on top
+    pub pieces: Arc<PieceSet>,
+    pub garbage_style: GarbageStyle,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ClearedLineCounts {
+    pub singles: u32,
+    pub doubles: u32,
+    pub triples: u32,
+    pub tetrses: u32,
+}
+
+// the moves and rotations finesse counts, a hard drop is needed either way and drops or holds
+// aren't judged
+const FINESSE_ACTIONS: [Action; 7] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::RotateLeft,
+    Action::RotateRight,
+    Action::Rotate180,
+    Action::InstantLeft,
+    Action::InstantRight,
+];
+
+// how a piece was placed against the fewest presses that would have put it in the same spot
+#[derive(Clone)]
+pub struct Finesse {
+    pub landing: Piece,
+    pub presses: u32,
+    pub fewest: Option<Vec<Action>>, // None when a hard drop can't get there, it isn't judged then
+}
+
+impl Finesse {
+    pub fn faults(&self) -> u32 {
+        match &self.fewest {
+            Some(fewest) => self.presses.saturating_sub(fewest.len() as u32),
+            None => 0,
+        }
+    }
+}
+
+// the headless game, everything that decides what happens lives here and none of it knows about
+// cursive or the wall clock, so the same inputs at the same times always give the same game
+#[derive(Clone)]
+pub struct Game {
+    settings: GameSettings,
+    tiles: Vec<Vec<Tile>>, // rows of width tiles, the hidden buffer is the top BUFFER_HEIGHT rows
+    lost: bool,            // topped out
+    finished: bool,        // reached the goal of the mode
+
+    // piece things
+    current_piece: Piece,
+    piece_bag: PieceBag,
+    held_piece: Option<Piece>,
+    can_hold: bool,
+    phase: Phase,       // current_piece is stale unless Falling or Locking
+    buffered_turns: u8, // clockwise quarter turns for the next piece
+    buffered_hold: bool,
+    pieces_dealt: u32, // taken from the bag, the current one included
+    pieces_locked: u32,
+
+    // finesse things
+    spawned_piece: Piece, // the current piece as it came into play, before any input
+    piece_presses: u32,   // moves and rotations since then
+    soft_dropped: bool,   // the piece may be tucked somewhere a hard drop can't reach
+    judges_finesse: bool,
+    pressable: [bool; FINESSE_ACTIONS.len()], // which of FINESSE_ACTIONS have keys
+    finesse_faults: u32,
+    last_finesse: Option<Finesse>,
+
+    // garbage things
+    garbage: GarbageQueue,
+    garbage_rng: ChaCha8Rng, // picks the holes, seeded apart from the pieces
+    outgoing_garbage: u32,   // attack left over after cancelling, waiting to be sent
+
+    // logical clock, ms since the game started
+    clock_ms: u64,
+    last_tick_ms: u64,
+    tick_time_ms: u64,
+    gravity: bool, // practice can turn it off, pieces then only go down when dropped
+
+    //game stat
+    score: u32,
+    lines: u32,
+    level: u8,
+    cleared_line_counts: ClearedLineCounts,
+}
+
+impl Game {
+    pub fn new(settings: GameSettings) -> Self {
+        let mut piece_bag = PieceBag::new(settings.seed, settings.pieces.clone());
+        let first_piece = piece_bag.pop();
+        let mut garbage_rng = ChaCha8Rng::seed_from_u64(settings.seed);
+        garbage_rng.set_stream(1);
+        let mut game = Game {
+            tiles: vec![vec![None; settings.width]; BUFFER_HEIGHT + settings.height],
+            level: settings.starting_level,
+            settings,
+            lost: false,
+            finished: false,
+            current_piece: first_piece,
+            piece_bag,
+            held_piece: None,
+            can_hold: true,
+            phase: Phase::Falling,
+            buffered_turns: 0,
+            buffered_hold: false,
+            pieces_dealt: 1,
+            pieces_locked: 0,
+            spawned_piece: first_piece,
+            piece_presses: 0,
+            soft_dropped: false,
+            judges_finesse: true,
+            pressable: [true; FINESSE_ACTIONS.len()],
+            finesse_faults: 0,
+            last_finesse: None,
+            garbage: GarbageQueue::default(),
+            garbage_rng,
+            outgoing_garbage: 0,
+            clock_ms: 0,
+            last_tick_ms: 0,
+            tick_time_ms: 0,
+            gravity: true,
+            score: 0,
+            lines: 0,
+            cleared_line_counts: ClearedLineCounts::default(),
+        };
+        game.current_piece = game.at_spawn(first_piece);
+        game.spawned_piece = game.current_piece;
+        game.update_tick_time();
+        game
+    }
+    // getters
+    pub fn tiles(&self) -> &[Vec<Tile>] {
+        &self.tiles
+    }
+    pub fn width(&self) -> usize {
+        self.settings.width
+    }
+    // visible rows
+    pub fn height(&self) -> usize {
+        self.settings.height
+    }
+    // None between a piece locking and the next one spawning
+    pub fn current_piece(&self) -> Option<&Piece> {
+        match self.piece_in_play() {
+            true => Some(&self.current_piece),
+            false => None,
+        }
+    }
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+    // rows that are full and about to collapse, empty unless Clearing
+    pub fn clearing_rows(&self) -> Vec<usize> {
+        match self.phase {
+            Phase::Clearing { .. } => self.full_rows(),
+            _ => Vec::new(),
+        }
+    }
+    pub fn held_piece(&self) -> Option<Piece> {
+        self.held_piece
+    }
+    // presses beyond the fewest that would have placed every piece so far the same way
+    pub fn finesse_faults(&self) -> u32 {
+        self.finesse_faults
+    }
+    // finesse is searched for every piece that locks, copies that are only looked ahead in can
+    // skip it
+    pub fn set_judging_finesse(&mut self, judges: bool) {
+        self.judges_finesse = judges;
+        if !judges {
+            self.last_finesse = None;
+        }
+    }
+    // the fewest presses are only searched among the actions the player can press
+    pub fn set_pressable(&mut self, pressable: impl Fn(Action) -> bool) {
+        self.pressable = FINESSE_ACTIONS.map(pressable);
+    }
+    // how the piece that locked last was placed
+    pub fn last_finesse(&self) -> Option<&Finesse> {
+        self.last_finesse.as_ref()
+    }
+    // empties the board, for drills where every piece is placed on its own
+    pub fn clear_stack(&mut self) {
+        for row in self.tiles.iter_mut() {
+            row.fill(None);
+        }
+        self.update_landing();
+    }
+    // practice only, the upcoming pieces, see `PieceBag::set_queue`
+    pub fn set_queue(&mut self, kinds: &[usize], repeat: bool) {
+        self.piece_bag.set_queue(kinds, repeat);
+    }
+    // practice only, the piece in play is swapped for another kind back where pieces spawn
+    pub fn replace_current_piece(&mut self, kind: usize) {
+        if !self.piece_in_play() || kind >= self.settings.pieces.len() {
+            return;
+        }
+        let piece = self.at_spawn(Piece::new(&self.settings.pieces, kind));
+        if !self.valid_piece(&piece) {
+            return;
+        }
+        self.current_piece = piece;
+        self.spawned_piece = piece;
+        self.piece_presses = 0;
+        self.soft_dropped = false;
+        self.update_landing();
+    }
+    // practice only, the visible rows are replaced and the hidden ones above them emptied
+    pub fn set_stack(&mut self, rows: &[Vec<Tile>]) {
+        for (y, row) in self.tiles.iter_mut().enumerate() {
+            match y.checked_sub(BUFFER_HEIGHT).and_then(|y| rows.get(y)) {
+                Some(given) if given.len() == row.len() => row.copy_from_slice(given),
+                _ => row.fill(None),
+            }
+        }
+        self.update_landing();
+    }
+    // practice only
+    pub fn set_held_piece(&mut self, kind: Option<usize>) {
+        self.held_piece = kind
+            .filter(|kind| *kind < self.settings.pieces.len())
+            .map(|kind| self.at_spawn(Piece::new(&self.settings.pieces, kind)));
+        self.can_hold = true;
+    }
+    // practice only
+    pub fn set_gravity(&mut self, on: bool) {
+        if on && !self.gravity {
+            self.last_tick_ms = self.clock_ms; // no catching up on the ticks it was off for
+        }
+        self.gravity = on;
+    }
+    // goes up with every piece that locks into the stack
+    pub fn pieces_locked(&self) -> u32 {
+        self.pieces_locked
+    }
+    // goes up with every piece that comes into play, to tell one piece from the next
+    pub fn pieces_dealt(&self) -> u32 {
+        self.pieces_dealt
+    }
+    // idx 0 is the next piece
+    pub fn upcoming_piece(&self, idx: usize) -> Piece {
+        self.piece_bag.get(idx)
+    }
+    pub fn is_lost(&self) -> bool {
+        self.lost
+    }
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    // nothing more can happen once the game is over
+    pub fn is_over(&self) -> bool {
+        self.lost || self.finished
+    }
+    pub fn clock_ms(&self) -> u64 {
+        self.clock_ms
+    }
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+    pub fn settings(&self) -> GameSettings {
+        self.settings.clone()
+    }
+    pub fn piece_set(&self) -> &PieceSet {
+        &self.settings.pieces
+    }
+    pub fn mode(&self) -> Mode {
+        self.settings.mode
+    }
+    pub fn starting_level(&self) -> u8 {
+        self.settings.starting_level
+    }
+    pub fn seed(&self) -> u64 {
+        self.settings.seed
+    }
+    pub fn cleared_line_counts(&self) -> ClearedLineCounts {
+        self.cleared_line_counts
+    }
+    // lines received that haven't come up yet
+    pub fn pending_garbage(&self) -> u32 {
+        self.garbage.pending()
+    }
+    // attack this game has sent since the last call, for whoever is on the other side
+    pub fn take_outgoing_garbage(&mut self) -> u32 {
+        std::mem::take(&mut self.outgoing_garbage)
+    }
+
+    pub fn handle(&mut self, command: Command) {
+        match command {
+            Command::Act(action) => self.apply(action),
+            Command::Garbage(lines) => self.receive_garbage(lines),
+        }
+    }
+    // queued, it comes up from below the next time a piece locks without clearing anything. more
+    // than the board is tall would top out all the same
+    pub fn receive_garbage(&mut self, lines: u32) {
+        if !self.is_over() {
+            self.garbage.push(lines.min(self.height() as u32));
+        }
+    }
+
+    pub fn apply(&mut self, action: Action) {
+        if self.is_over() {
+            return;
+        }
+        if !self.piece_in_play() {
+            self.buffer_for_spawn(action);
+            return;
+        }
+        match action {
+            Action::SoftDrop => {
+                self.soft_dropped = true;
+                if !self.try_current_piece_movement(Piece::move_down) {
+                    self.consume_piece();
+                }
+            }
+            Action::HardDrop => {
+                while self.try_current_piece_movement(Piece::move_down) {}
+                self.consume_piece();
+            }
+            Action::SonicDrop => {
+                self.soft_dropped = true;
+                self.current_piece = self.moved(self.current_piece, action);
+            }
+            Action::Hold => {
+                self.try_hold_piece();
+                self.check_block_out();
+            }
+            Action::Restart | Action::Undo | Action::Redo | Action::PickPiece => {}
+            _ => {
+                self.piece_presses += 1;
+                self.current_piece = self.moved(self.current_piece, action);
+            }
+        }
+        self.update_landing();
+        // a clear or spawn with no delay happens right away, so it is settled before the next
+        // input, the same as when a replay is simulated
+        self.advance_to(self.clock_ms);
+    }
+
+    // runs gravity up to the given time, returns true if the piece ticked down at least once
+    pub fn advance_to(&mut self, mut ms: u64) -> bool {
+        if self.settings.mode == Mode::Ultra && ms >= ULTRA_TIME_MS {
+            ms = ULTRA_TIME_MS;
+        }
+        let mut ticked = false;
+        while !self.is_over() {
+            match self.phase {
+                Phase::Clearing { until_ms } if until_ms <= ms => {
+                    self.clock_ms = until_ms;
+                    self.finish_clearing();
+                }
+                Phase::Spawning { at_ms } if at_ms <= ms => {
+                    self.clock_ms = at_ms;
+                    self.spawn_next_piece();
+                }
+                Phase::Falling | Phase::Locking
+                    if self.gravity && self.last_tick_ms + self.tick_time_ms <= ms =>
+                {
+                    self.last_tick_ms += self.tick_time_ms;
+                    self.clock_ms = self.last_tick_ms;
+                    // only consume piece and check loss if it can't move
+                    if !self.try_current_piece_movement(Piece::move_down) {
+                        self.consume_piece();
+                    }
+                    self.update_landing();
+                }
+                _ => break,
+            }
+            ticked = true;
+        }
+        // the clock stops where the game ended
+        if !self.is_over() && ms > self.clock_ms {
+            self.clock_ms = ms;
+        }
+        if self.settings.mode == Mode::Ultra && !self.lost && self.clock_ms >= ULTRA_TIME_MS {
+            self.finished = true; // time's up
+        }
+        ticked
+    }
+
+    // when the game next moves on by itself, a gravity tick or the end of a delay
+    pub fn next_deadline_ms(&self) -> Option<u64> {
+        match self.phase {
+            _ if self.is_over() => None,
+            Phase::Clearing { until_ms } => Some(until_ms),
+            Phase::Spawning { at_ms } => Some(at_ms),
+            Phase::Falling | Phase::Locking => match self.gravity {
+                true => Some(self.last_tick_ms + self.tick_time_ms),
+                false => None,
+            },
+        }
+    }
+
+    // where the current piece would land
+    pub fn ghost_piece(&self) -> Option<Piece> {
+        let mut ghost_piece = *self.current_piece()?;
+        while self.try_piece_movement(&mut ghost_piece, Piece::move_down) {}
+        Some(ghost_piece)
+    }
+
+    // gets the current rate of tetrses out of total kinds of line clears as a percentage
+    pub fn tetrs_rate(&self) -> u32 {
+        let tot = self.cleared_line_counts.singles
+            + self.cleared_line_counts.doubles
+            + self.cleared_line_counts.triples
+            + self.cleared_line_counts.tetrses;
+        if tot == 0 {
+            return 0;
+        }
+        100 * self.cleared_line_counts.tetrses / tot
+    }
+
+    // sets self.lost and also returns true if lost
+    fn consume_piece(&mut self) -> bool {
+        let piece = self.current_piece;
+        self.judge_finesse();
+        self.pieces_locked += 1;
+        let mut visible = false;
+        for (x, y) in piece.cells() {
+            visible |= y >= BUFFER_HEIGHT as i8;
+            self.tiles[y as usize][x as usize] = Some(piece.block());
+        }
+        // lock out, the whole piece locked in the hidden buffer
+        if !visible {
+            self.lost = true;
+            return true;
+        }
+        // let us hold again since we just consumed a piece
+        self.can_hold = true;
+        self.score += 1; // give pity point
+        // full lines are cleared after the line clear delay, the next piece comes after the entry
+        // delay
+        let timing = self.settings.timing;
+        let clearing = !self.full_rows().is_empty();
+        // garbage only comes up under a piece that didn't clear anything
+        if !clearing {
+            self.rise_garbage();
+            if self.lost {
+                return true;
+            }
+        }
+        self.phase = match clearing {
+            false => Phase::Spawning {
+                at_ms: self.clock_ms + timing.entry_delay_ms,
+            },
+            true => Phase::Clearing {
+                until_ms: self.clock_ms + timing.line_clear_delay_ms,
+            },
+        };
+        false
+    }
+    fn finish_clearing(&mut self) {
+        let cleared = self.clear_any_full_lines();
+        // clears cancel incoming garbage first, only what is left over is sent
+        let attack = garbage::attack_for_clear(cleared);
+        self.outgoing_garbage += attack - self.garbage.cancel(attack);
+        // update level and tick time accordingly
+        let starting_level = self.settings.starting_level;
+        self.level =
+            starting_level + min(MAX_LEVEL as u32, self.lines / (10 * starting_level as u32)) as u8;
+        self.update_tick_time();
+        if self.settings.mode == Mode::Sprint && self.lines >= SPRINT_LINES {
+            self.finished = true;
+        }
+        self.phase = Phase::Spawning {
+            at_ms: self.clock_ms + self.settings.timing.entry_delay_ms,
+        };
+    }
+    // compares the presses that placed the current piece with the fewest that would have, before
+    // it locks into the stack it's judged against
+    fn judge_finesse(&mut self) {
+        if !self.judges_finesse {
+            return;
+        }
+        let fewest = match std::mem::take(&mut self.soft_dropped) {
+            true => None,
+            false => self.fewest_presses(self.spawned_piece, &self.current_piece),
+        };
+        let finesse = Finesse {
+            landing: self.current_piece,
+            presses: std::mem::take(&mut self.piece_presses),
+            fewest,
+        };
+        self.finesse_faults += finesse.faults();
+        self.last_finesse = Some(finesse);
+    }
+    // the shortest presses from a piece as it spawned to hard dropping onto the landing's cells,
+    // found breadth first over the places the piece can be moved to
+    pub fn fewest_presses(&self, from: Piece, landing: &Piece) -> Option<Vec<Action>> {
+        let cells = |piece: &Piece| {
+            let mut cells: Vec<(i8, i8)> = piece.cells().collect();
+            cells.sort();
+            cells
+        };
+        let target = cells(landing);
+        let dropped = |piece: Piece| cells(&self.moved(piece, Action::SonicDrop));
+        let mut seen = HashSet::from([cells(&from)]);
+        let mut queue = VecDeque::from([(from, Vec::new())]);
+        while let Some((piece, presses)) = queue.pop_front() {
+            if dropped(piece) == target {
+                return Some(presses);
+            }
+            for (action, _) in FINESSE_ACTIONS
+                .into_iter()
+                .zip(self.pressable)
+                .filter(|(_, pressable)| *pressable)
+            {
+                let next = self.moved(piece, action);
+                if seen.insert(cells(&next)) {
+                    let mut next_presses = presses.clone();
+                    next_presses.push(action);
+                    queue.push_back((next, next_presses));
+                }
+            }
+        }
+        None
+    }
+    // where an action that only moves the piece takes it, it stays put when it can't go
+    fn moved(&self, mut piece: Piece, action: Action) -> Piece {
+        match action {
+            Action::MoveLeft => {
+                self.try_piece_movement(&mut piece, Piece::move_left);
+            }
+            Action::MoveRight => {
+                self.try_piece_movement(&mut piece, Piece::move_right);
+            }
+            Action::RotateLeft => {
+                self.try_piece_movement(&mut piece, Piece::rotate_left);
+            }
+            Action::RotateRight => {
+                self.try_piece_movement(&mut piece, Piece::rotate_right);
+            }
+            Action::Rotate180 => {
+                for (dx, dy) in ROTATE_180_KICKS {
+                    let kicked = self.try_piece_movement(&mut piece, |p| {
+                        p.rotate_180();
+                        p.move_by(dx, dy);
+                    });
+                    if kicked {
+                        break;
+                    }
+                }
+            }
+            Action::SonicDrop => while self.try_piece_movement(&mut piece, Piece::move_down) {},
+            Action::InstantLeft => while self.try_piece_movement(&mut piece, Piece::move_left) {},
+            Action::InstantRight => while self.try_piece_movement(&mut piece, Piece::move_right) {},
+            _ => {}
+        }
+        piece
+    }
+    fn piece_in_play(&self) -> bool {
+        matches!(self.phase, Phase::Falling | Phase::Locking)
+    }
+    // keeps Falling and Locking up to date with whether the piece is resting on something
+    fn update_landing(&mut self) {
+        if !self.piece_in_play() {
+            return;
+        }
+        let mut below = self.current_piece;
+        self.phase = match self.try_piece_movement(&mut below, Piece::move_down) {
+            true => Phase::Falling,
+            false => Phase::Locking,
+        };
+    }
+    // rotations add up and a hold is remembered, anything else has nothing to act on yet and
+    // isn't counted as a press either
+    fn buffer_for_spawn(&mut self, action: Action) {
+        let turns = match action {
+            Action::RotateRight => 1,
+            Action::Rotate180 => 2,
+            Action::RotateLeft => 3,
+            Action::Hold => {
+                self.buffered_hold = true;
+                return;
+            }
+            _ => return,
+        };
+        self.buffered_turns = (self.buffered_turns + turns) % 4;
+        self.piece_presses += 1;
+    }
+    fn spawn_next_piece(&mut self) {
+        self.phase = Phase::Falling;
+        self.current_piece = self.next_from_bag();
+        self.spawned_piece = self.current_piece;
+        // the buffered rotations are for whichever piece comes out of the hold
+        let presses = std::mem::take(&mut self.piece_presses);
+        if std::mem::take(&mut self.buffered_hold) {
+            self.try_hold_piece();
+        }
+        self.piece_presses = presses;
+        // an initial rotation that doesn't fit is dropped rather than topping the player out
+        let turns = std::mem::take(&mut self.buffered_turns);
+        self.try_current_piece_movement(|p| {
+            for _ in 0..turns {
+                p.rotate_right();
+            }
+        });
+        self.check_block_out();
+        // gravity starts over for the new piece
+        self.last_tick_ms = self.clock_ms;
+        self.update_landing();
+    }
+    fn next_from_bag(&mut self) -> Piece {
+        self.pieces_dealt += 1;
+        let piece = self.piece_bag.pop();
+        self.at_spawn(piece)
+    }
+    fn at_spawn(&self, piece: Piece) -> Piece {
+        let shape_width = self.settings.pieces.shape(piece.kind()).width();
+        piece.at(
+            board::piece_start_x(self.settings.width, shape_width),
+            PIECE_START_Y,
+        )
+    }
+    // block out, a piece that comes into play overlapping the stack ends the game
+    fn check_block_out(&mut self) {
+        if !self.valid_piece(&self.current_piece) {
+            self.lost = true;
+        }
+    }
+    // helper
+    fn update_tick_time(&mut self) {
+        const CURVE: u64 = 15; // so it's not too hard
+        self.tick_time_ms = match self.level {
+            0 => 800,
+            1 => 717,
+            2 => 633,
+            3 => 550,
+            4 => 467,
+            5 => 383,
+            6 => 300,
+            7 => 217,
+            8 => 133,
+            9 => 100,
+            10..=12 => 83 + CURVE,
+            13..=15 => 67 + CURVE,
+            16..=18 => 50 + CURVE,
+            19..=28 => 33 + CURVE,
+            _ => 17 + CURVE,
+        };
+    }
+    fn full_rows(&self) -> Vec<usize> {
+        (0..self.tiles.len())
+            .filter(|i| self.tiles[*i].iter().all(|t| t.is_some()))
+            .collect()
+    }
+    // clears any full lines on the board, returns how many
+    fn clear_any_full_lines(&mut self) -> u32 {
+        let mut num_cleared = 0;
+        let mut i = self.tiles.len() as isize - 1;
+        while i >= 0 {
+            if self.tiles[i as usize].iter().all(|t| t.is_some()) {
+                num_cleared += 1;
+                self.clear_line_and_shift_down(i as usize);
+                i += 1; // recheck the same row after shifting down
+            }
+            i -= 1;
+        }
+        self.award_points_from_lines_cleared(num_cleared);
+        self.lines += num_cleared as u32;
+        match num_cleared {
+            1 => self.cleared_line_counts.singles += 1,
+            2 => self.cleared_line_counts.doubles += 1,
+            3 => self.cleared_line_counts.triples += 1,
+            4 => self.cleared_line_counts.tetrses += 1,
+            _ => {} // nothing, not possible
+        }
+        num_cleared as u32
+    }
+    // pending garbage pushes the stack up, anything pushed off the top of the buffer tops out
+    fn rise_garbage(&mut self) {
+        let width = self.settings.width;
+        let holes = self.garbage.take_rows(
+            MAX_GARBAGE_PER_LOCK,
+            width,
+            self.settings.garbage_style,
+            &mut self.garbage_rng,
+        );
+        for hole in holes {
+            if self.tiles[0].iter().any(|t| t.is_some()) {
+                self.lost = true;
+            }
+            self.tiles.remove(0);
+            let mut row = vec![Some(Block::Garbage); width];
+            row[hole] = None;
+            self.tiles.push(row);
+        }
+    }
+    // helper for clear_any_full_lines
+    fn clear_line_and_shift_down(&mut self, row: usize) {
+        self.tiles.remove(row);
+        self.tiles.insert(0, vec![None; self.settings.width]);
+    }
+    // awards points based on lines clear
+    fn award_points_from_lines_cleared(&mut self, num_cleared: u8) {
+        let points: u32 = match num_cleared {
+            1 => 100,
+            2 => 300,
+            3 => 500,
+            4 => 800,
+            _ => 0, // not possible
+        };
+        self.score += points * self.level as u32;
+    }
+    fn try_current_piece_movement<F>(&mut self, f: F) -> bool
+    where
+        F: FnMut(&mut Piece),
+    {
+        let mut temp = self.current_piece;
+        let moved = self.try_piece_movement(&mut temp, f);
+        self.current_piece = temp;
+        moved
+    }
+    fn try_piece_movement<F>(&self, piece: &mut Piece, mut f: F) -> bool
+    where
+        F: FnMut(&mut Piece),
+    {
+        let mut temp = *piece;
+        // try movement by transforming temp
+        f(&mut temp);
+
+        if self.valid_piece(&temp) {
+            *piece = temp;
+            true
+        } else {
+            //don't transform current piece
+            false
+        }
+    }
+    fn valid_piece(&self, piece: &Piece) -> bool {
+        // this order matters, checking intersection can get an out of bounds error if
+        // we don't check bounds first
+        !piece.is_out_of_bounds(self.settings.width, self.tiles.len())
+            && !self.check_if_piece_intersects_any_blocks(piece)
+    }
+    fn check_if_piece_intersects_any_blocks(&self, piece: &Piece) -> bool {
+        piece.cells().any(|(x, y)| {
+            // out of bounds guard to be extra safe
+            if x < 0 || y < 0 || x >= self.settings.width as i8 || y >= self.tiles.len() as i8 {
+                return false;
+            }
+            self.tiles[y as usize][x as usize].is_some()
+        })
+    }
+    fn try_hold_piece(&mut self) {
+        if !self.can_hold {
+            return;
+        }
+        let orig_held_piece = self.held_piece;
+        // construct new piece to get orginal, non-rotated layout
+        // also go remember to set starting coords for it with `at`
+        self.held_piece =
+            Some(self.at_spawn(Piece::new(&self.settings.pieces, self.current_piece.kind())));
+        match orig_held_piece {
+            None => {
+                self.current_piece = self.next_from_bag();
+            }
+            Some(p) => {
+                self.current_piece = p;
+            }
+        }
+        // finesse starts over with the piece that came out
+        self.spawned_piece = self.current_piece;
+        self.piece_presses = 0;
+        self.soft_dropped = false;
+        self.can_hold = false; // just held, this has to get reset when we consume the next piece
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T: usize = 6;
+
+    // a marathon game dealing only T pieces
+    fn t_game() -> Game {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 1,
+            timing: Mode::Marathon.timing(),
+            width: 10,
+            height: 20,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        game.set_queue(&[T], true);
+        game.replace_current_piece(T);
+        game
+    }
+
+    fn sorted_cells(piece: &Piece) -> Vec<(i8, i8)> {
+        let mut cells: Vec<(i8, i8)> = piece.cells().collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn the_next_piece_waits_out_the_entry_delay() {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 1,
+            timing: Timing {
+                entry_delay_ms: 250,
+                line_clear_delay_ms: 0,
+            },
+            width: 10,
+            height: 20,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        game.apply(Action::HardDrop);
+        let locked_at = game.clock_ms();
+        assert_eq!(
+            game.phase(),
+            Phase::Spawning {
+                at_ms: locked_at + 250
+            }
+        );
+        game.advance_to(locked_at + 249);
+        assert!(game.current_piece().is_none());
+        game.advance_to(locked_at + 250);
+        assert_eq!(game.phase(), Phase::Falling);
+    }
+
+    #[test]
+    fn without_an_entry_delay_the_next_piece_is_already_there() {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 1,
+            timing: Timing {
+                entry_delay_ms: 0,
+                line_clear_delay_ms: 0,
+            },
+            width: 10,
+            height: 20,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        game.apply(Action::HardDrop);
+        assert_eq!(game.phase(), Phase::Falling);
+        assert!(game.current_piece().is_some());
+    }
+
+    #[test]
+    fn a_piece_spawning_into_the_stack_blocks_out() {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 1,
+            timing: Mode::Marathon.timing(),
+            width: 10,
+            height: 20,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        // the spawn columns fill up while the piece in play is out of the way
+        game.apply(Action::InstantLeft);
+        for row in game.tiles[BUFFER_HEIGHT..BUFFER_HEIGHT + 2].iter_mut() {
+            for tile in row[4..8].iter_mut() {
+                *tile = Some(Block::Red);
+            }
+        }
+        game.apply(Action::HardDrop);
+        assert!(!game.is_over());
+        game.advance_to(game.clock_ms() + Mode::Marathon.timing().entry_delay_ms);
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn a_piece_locking_in_the_hidden_buffer_locks_out() {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 1,
+            timing: Mode::Marathon.timing(),
+            width: 10,
+            height: 20,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        // a stack up into the buffer with the last column open, so nothing clears
+        for row in game.tiles[10..].iter_mut() {
+            for tile in row[..9].iter_mut() {
+                *tile = Some(Block::Red);
+            }
+        }
+        game.current_piece = game.current_piece.at(0, 0);
+        game.apply(Action::HardDrop);
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn extra_presses_are_finesse_faults() {
+        let mut game = t_game();
+        game.apply(Action::MoveLeft);
+        game.apply(Action::MoveRight);
+        game.apply(Action::HardDrop);
+        let finesse = game.last_finesse().unwrap();
+        assert_eq!(finesse.presses, 2);
+        assert_eq!(finesse.fewest, Some(Vec::new()));
+        assert_eq!(game.finesse_faults(), 2);
+    }
+
+    #[test]
+    fn only_buffered_presses_count_during_the_entry_delay() {
+        let mut game = t_game();
+        game.apply(Action::HardDrop);
+        assert!(matches!(game.phase(), Phase::Spawning { .. }));
+        game.apply(Action::MoveLeft);
+        game.apply(Action::InstantRight);
+        game.apply(Action::RotateRight);
+        game.advance_to(game.clock_ms() + Mode::Marathon.timing().entry_delay_ms);
+        game.apply(Action::HardDrop);
+        let finesse = game.last_finesse().unwrap();
+        assert_eq!(finesse.presses, 1);
+        assert_eq!(finesse.fewest, Some(vec![Action::RotateRight]));
+        assert_eq!(game.finesse_faults(), 0);
+    }
+
+    #[test]
+    fn buffered_rotations_carry_over_a_buffered_hold() {
+        let mut game = t_game();
+        game.apply(Action::HardDrop);
+        game.apply(Action::Hold);
+        game.apply(Action::RotateLeft);
+        game.advance_to(game.clock_ms() + Mode::Marathon.timing().entry_delay_ms);
+        assert!(game.held_piece().is_some());
+        game.apply(Action::HardDrop);
+        assert_eq!(game.last_finesse().unwrap().presses, 1);
+        assert_eq!(game.finesse_faults(), 0);
+    }
+
+    #[test]
+    fn rotate_180_kicks_up_off_the_floor() {
+        let mut game = t_game();
+        game.apply(Action::SonicDrop);
+        let before = sorted_cells(game.current_piece().unwrap());
+        let (x, y) = (before[0].0, before[0].1);
+        game.apply(Action::Rotate180);
+        // flat side up on the floor becomes flat side down, one row up
+        let mut flipped = vec![
+            (x, y - 1),
+            (x + 1, y - 1),
+            (x + 1, y),
+            (x + 2, y - 1),
+        ];
+        flipped.sort();
+        assert_eq!(sorted_cells(game.current_piece().unwrap()), flipped);
+    }
+
+    #[test]
+    fn rotate_180_turns_in_place_when_it_fits() {
+        let mut game = t_game();
+        let before = sorted_cells(game.current_piece().unwrap());
+        let (x, y) = (before[0].0, before[0].1);
+        game.apply(Action::Rotate180);
+        let mut flipped = vec![
+            (x, y),
+            (x + 1, y),
+            (x + 1, y + 1),
+            (x + 2, y),
+        ];
+        flipped.sort();
+        assert_eq!(sorted_cells(game.current_piece().unwrap()), flipped);
+    }
+
+    #[test]
+    fn rotate_180_kicks_off_a_wall() {
+        let mut game = t_game();
+        game.apply(Action::RotateRight);
+        game.apply(Action::InstantLeft);
+        // upright against the left wall with the nub to the right
+        let before = sorted_cells(game.current_piece().unwrap());
+        let y = before[0].1;
+        assert_eq!(
+            before,
+            vec![
+                (0, y),
+                (0, y + 1),
+                (0, y + 2),
+                (1, y + 1)
+            ]
+        );
+        game.apply(Action::Rotate180);
+        let after = sorted_cells(game.current_piece().unwrap());
+        assert_eq!(
+            after,
+            vec![
+                (0, y + 1),
+                (1, y),
+                (1, y + 1),
+                (1, y + 2)
+            ]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+// bots that are programs of their own, started with a command line and driven over their stdin
+// and stdout one line at a time. it's in the spirit of the tetris bot protocol (TBP) but plain text
+// like the network protocol, and the bot answers with the keys a player would press rather than
+// where the piece should end up, so it can't do anything a person couldn't:
+//
+//   bot <version> <name>               the bot's first line, the protocol version (currently 1)
+//                                      and a name for it, which may have spaces
+//   rules <width> <height> <hidden> <pieces>
+//                                      the board is width columns by height visible rows, with
+//                                      hidden rows above them, and the set has pieces kinds
+//   piece <kind> <rows>                one after rules for every kind, how it looks as it spawns
+//                                      with rows split by `/`, `#` for a block and `.` for nothing
+//   ready                              the bot is set up
+//   suggest <current> <held> <queue> <garbage> <rows>
+//                                      the bot is to play the current piece. pieces are kinds, held
+//                                      is `-` when nothing is held, queue is the next kinds split by
+//                                      `,`, garbage is the lines waiting to come up and rows is the
+//                                      whole board from the top of the hidden rows, split by `/`,
+//                                      `#` for a block and `.` for nothing
+//   play <action> <action>...          the bot's answer, the actions to press in order named as in
+//                                      the config file (`hold`, `rotate_right`, `instant_left`,
+//                                      `move_right`, ...), ending with `hard_drop` so the piece locks
+//   quit                               the game is over, the bot should exit
+//
+// only one suggest is out at a time. when a piece locked by gravity before the answer came the
+// answer is dropped and the next piece is asked about. a bot that answers anything else, takes
+// longer than START_TIMEOUT to get ready or ANSWER_TIMEOUT when it's waited for, or exits, gives
+// up the game
+use crate::board::BUFFER_HEIGHT;
+use crate::engine::Action;
+use crate::engine::Game;
+use crate::engine::GameSettings;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use std::time::Duration;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+// for each line of the handshake, the screen is frozen while it happens
+pub const START_TIMEOUT: Duration = Duration::from_secs(2);
+// for every answer in games that wait for the bot
+pub const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+// more than any placement takes, an answer this long is a bot gone wrong
+const MAX_ACTIONS: usize = 100;
+
+pub struct ExternalBot {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    incoming: Mutex<Receiver<String>>, // a board has to be shareable between threads
+    asked_for: Option<u32>,            // the piece the suggest that's out is about, by pieces dealt
+    stale: usize,                      // answers still to come for games that were restarted
+    waits: bool,                       // the game stops for the bot's answers instead of playing on
+    error: Option<String>,
+}
+
+impl ExternalBot {
+    // runs the command (split on whitespace, no shell) and goes through the rules with it
+    pub fn start(command: &str, settings: &GameSettings) -> Result<Self, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("the bot command is empty")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("could not start bot `{}`: {}", command, e))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err(format!("could not talk to bot `{}`", command));
+        };
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut bot = ExternalBot {
+            name: String::new(),
+            child,
+            stdin,
+            incoming: Mutex::new(rx),
+            asked_for: None,
+            stale: 0,
+            waits: false,
+            error: None,
+        };
+        bot.name = bot
+            .greet(settings)
+            .map_err(|e| format!("bot `{}` {}", command, e))?;
+        Ok(bot)
+    }
+    fn greet(&mut self, settings: &GameSettings) -> Result<String, String> {
+        let hello = self.recv(START_TIMEOUT)?;
+        let name = match hello.strip_prefix("bot ").map(|r| r.split_once(' ')) {
+            Some(Some((version, name))) if version == PROTOCOL_VERSION.to_string() => name.trim(),
+            Some(Some((version, _))) => {
+                return Err(format!(
+                    "speaks protocol version {}, this is version {}",
+                    version, PROTOCOL_VERSION
+                ));
+            }
+            _ => return Err(format!("said `{}` instead of introducing itself", hello)),
+        };
+        let pieces = &settings.pieces;
+        let mut rules = vec![format!(
+            "rules {} {} {} {}",
+            settings.width,
+            settings.height,
+            BUFFER_HEIGHT,
+            pieces.len()
+        )];
+        for kind in 0..pieces.len() {
+            rules.push(format!(
+                "piece {} {}",
+                kind,
+                drawing(pieces.shape(kind).cells())
+            ));
+        }
+        for line in rules {
+            self.send(&line)
+                .map_err(|e| format!("stopped listening: {}", e))?;
+        }
+        match self.recv(START_TIMEOUT)?.as_str() {
+            "ready" => Ok(name.to_string()),
+            other => Err(format!("said `{}` instead of ready", other)),
+        }
+    }
+    // the game waits for every answer, for playing without a screen
+    pub fn waiting(mut self) -> Self {
+        self.waits = true;
+        self
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    // why the bot gave up, if it did
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+    // a new game starts, whatever was asked about the last one is thrown away when it comes
+    pub fn reset(&mut self) {
+        if self.asked_for.take().is_some() {
+            self.stale += 1;
+        }
+    }
+    // the actions for the piece in play, None while the bot is still thinking or after it gave up
+    pub fn suggest(&mut self, game: &Game) -> Option<Vec<Action>> {
+        if self.error.is_some() {
+            return None;
+        }
+        if self.asked_for.is_none() {
+            if let Err(e) = self.send(&suggestion_request(game)) {
+                self.error = Some(format!("stopped listening: {}", e));
+                return None;
+            }
+            self.asked_for = Some(game.pieces_dealt());
+        }
+        let answer = loop {
+            let answer = match self.waits {
+                true => self.recv(ANSWER_TIMEOUT),
+                false => match self.incoming.lock().unwrap().try_recv() {
+                    Ok(line) => Ok(line),
+                    Err(TryRecvError::Empty) => return None,
+                    Err(TryRecvError::Disconnected) => Err("exited".to_string()),
+                },
+            };
+            match answer.is_ok() && self.stale > 0 {
+                true => self.stale -= 1,
+                false => break answer,
+            }
+        };
+        let asked_for = self.asked_for.take();
+        match answer.and_then(|line| parse_play(&line)) {
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+            // too late, that piece is gone
+            Ok(_) if asked_for != Some(game.pieces_dealt()) => None,
+            Ok(actions) => Some(actions),
+        }
+    }
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+    fn recv(&self, timeout: Duration) -> Result<String, String> {
+        match self.incoming.lock().unwrap().recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(format!(
+                "didn't answer within {} seconds",
+                timeout.as_secs()
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err("exited".to_string()),
+        }
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // a moment to exit by itself before it's made to
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// `#` and `.` rows split by `/`, from the top left of the cells
+fn drawing(cells: &[(i8, i8)]) -> String {
+    let width = cells.iter().map(|c| c.0 + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|c| c.1 + 1).max().unwrap_or(0);
+    let rows: Vec<String> = (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| match cells.contains(&(x, y)) {
+                    true => '#',
+                    false => '.',
+                })
+                .collect()
+        })
+        .collect();
+    rows.join("/")
+}
+
+fn suggestion_request(game: &Game) -> String {
+    let current = game.current_piece().map(|p| p.kind()).unwrap_or_default();
+    let held = match game.held_piece() {
+        Some(piece) => piece.kind().to_string(),
+        None => "-".to_string(),
+    };
+    // the bag is only ever dealt this far ahead
+    let queue: Vec<String> = (0..5)
+        .map(|i| game.upcoming_piece(i).kind().to_string())
+        .collect();
+    let rows: Vec<String> = game
+        .tiles()
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|t| match t {
+                    Some(_) => '#',
+                    None => '.',
+                })
+                .collect()
+        })
+        .collect();
+    format!(
+        "suggest {} {} {} {} {}",
+        current,
+        held,
+        queue.join(","),
+        game.pending_garbage(),
+        rows.join("/")
+    )
+}
+
+fn parse_play(line: &str) -> Result<Vec<Action>, String> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("play") {
+        return Err(format!("said `{}` instead of what to play", line));
+    }
+    let actions: Vec<Action> = words
+        .map(|word| match Action::from_name(word) {
+            Some(Action::Restart | Action::Undo | Action::Redo | Action::PickPiece) | None => {
+                Err(format!("wanted to press `{}`", word))
+            }
+            Some(action) => Ok(action),
+        })
+        .collect::<Result<_, _>>()?;
+    // the hard drop locks the piece, so it comes last and only once
+    match actions.iter().position(|a| *a == Action::HardDrop) {
+        _ if actions.len() > MAX_ACTIONS => Err(format!("answered with {} actions", actions.len())),
+        Some(at) if at + 1 == actions.len() => Ok(actions),
+        Some(_) => Err("pressed more after hard_drop".to_string()),
+        None => Err("didn't end with hard_drop".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_end_with_one_hard_drop() {
+        assert_eq!(
+            parse_play("play hold rotate_right instant_left hard_drop"),
+            Ok(vec![
+                Action::Hold,
+                Action::RotateRight,
+                Action::InstantLeft,
+                Action::HardDrop,
+            ])
+        );
+        assert_eq!(parse_play("play hard_drop"), Ok(vec![Action::HardDrop]));
+        assert!(parse_play("play").is_err());
+        assert!(parse_play("play move_left").is_err());
+        assert!(parse_play("play hard_drop move_left").is_err());
+        assert!(parse_play("play hard_drop hard_drop").is_err());
+        assert!(parse_play("play undo hard_drop").is_err());
+        assert!(parse_play("drop hard_drop").is_err());
+        let long = format!("play {}hard_drop", "move_left ".repeat(MAX_ACTIONS));
+        assert!(parse_play(&long).is_err());
+    }
+
+    #[test]
+    fn drawings_are_rows_of_the_cells() {
+        assert_eq!(drawing(&[(1, 0), (0, 1), (1, 1), (2, 1)]), ".#./###");
+        assert_eq!(drawing(&[(0, 1), (1, 1), (2, 1), (3, 1)]), "..../####");
+    }
+}
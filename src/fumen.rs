@@ -0,0 +1,891 @@
+// fumen (https://fumen.zui.jp), the board diagrams tetris players share setups and solutions as,
+// read into positions to practice from and written from boards and replays. only the current v115
+// format is understood:
+//
+//   v115@<data>                        `?` may be put anywhere in the data to break it up, and
+//                                      anything before the `v115@` (like the site's url) is skipped
+//
+// the data is numbers in base 64 (A-Z a-z 0-9 + /, lowest digit first), page after page:
+//
+//   field                              the 10 by 23 field and a garbage row under it, from the top
+//                                      left, as runs of (cell - cell on the last page + 8) * 240 +
+//                                      run length - 1 in 2 digits each. a page with the same field
+//                                      as the last one is one run and is followed by a digit for
+//                                      how many pages after it are the same again, those have no
+//                                      field of their own
+//   action                             3 digits, from the highest: the piece isn't locked, the page
+//                                      has a comment, guideline colors, the field is mirrored, the
+//                                      garbage row comes up, then the piece's cell (from the top
+//                                      left, 240 of them), its rotation and its kind
+//   comment                            only when the page's comment changes, the length in 2 digits
+//                                      and 4 chars of the escaped text in every 5 digits after
+//
+// cells are 0 empty, 1-7 the I, L, O, Z, T, J and S, and 8 gray. a locked piece is put on the
+// field for the next page, full rows are cleared and then the garbage row comes up and the field
+// is mirrored if the page says so
+use crate::board;
+use crate::engine::Action;
+use crate::engine::Command;
+use crate::engine::Game;
+use crate::engine::Phase;
+use crate::piece::Piece;
+use crate::piece_set::PieceSet;
+use crate::position::Position;
+use crate::replay::Replay;
+use crate::tile::Block;
+use crate::tile::Tile;
+
+pub const WIDTH: usize = 10;
+pub const FIELD_HEIGHT: usize = 23; // above the garbage row
+const CELLS: usize = WIDTH * (FIELD_HEIGHT + 1);
+const GRAY: u8 = 8;
+// the kinds as cells are numbered from 1, they're looked up by name in a piece set
+const KINDS: [&str; 7] = [
+    "I", "L", "O", "Z", "T", "J", "S",
+];
+const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_CHARS: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_BASE: u32 = COMMENT_CHARS.len() as u32 + 1;
+const MAX_COMMENT: usize = 4095;
+// a field unchanged from the last page, as a single run
+const SAME_FIELD: u32 = 8 * CELLS as u32 + CELLS as u32 - 1;
+// tetris-fumen writes the data in lines, the first one shorter for the prefix
+const FIRST_LINE: usize = 42;
+const LINE: usize = 47;
+// quiz pages say what's held and dealt in their comment, "#Q=[L](T)IOSZ"
+const QUIZ: &str = "#Q=";
+
+// numbered as in the data
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    Reverse,
+    Right,
+    Spawn,
+    Left,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::Reverse,
+        Rotation::Right,
+        Rotation::Spawn,
+        Rotation::Left,
+    ];
+    // a cell of a piece at spawn turned this way around its center, y up
+    fn turn(&self, (x, y): (i8, i8)) -> (i8, i8) {
+        match self {
+            Rotation::Spawn => (x, y),
+            Rotation::Right => (y, -x),
+            Rotation::Reverse => (-x, -y),
+            Rotation::Left => (-y, x),
+        }
+    }
+}
+
+// a piece on the field, x and y are of its center the way the srs rotates it, y counting up from
+// the bottom row of the field
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Operation {
+    pub kind: u8, // 1-7
+    pub rotation: Rotation,
+    pub x: i8,
+    pub y: i8,
+}
+
+impl Operation {
+    pub fn cells(&self) -> Vec<(i8, i8)> {
+        let spawn: [(i8, i8); 4] = match self.kind {
+            1 => [
+                (0, 0),
+                (-1, 0),
+                (1, 0),
+                (2, 0),
+            ],
+            2 => [
+                (0, 0),
+                (-1, 0),
+                (1, 0),
+                (1, 1),
+            ],
+            3 => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            4 => [
+                (0, 0),
+                (1, 0),
+                (0, 1),
+                (-1, 1),
+            ],
+            5 => [
+                (0, 0),
+                (-1, 0),
+                (1, 0),
+                (0, 1),
+            ],
+            6 => [
+                (0, 0),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+            ],
+            _ => [
+                (0, 0),
+                (-1, 0),
+                (0, 1),
+                (1, 1),
+            ],
+        };
+        spawn
+            .iter()
+            .map(|cell| self.rotation.turn(*cell))
+            .map(|(x, y)| (self.x + x, self.y + y))
+            .collect()
+    }
+    // the piece covering exactly these cells, y up
+    fn covering(kind: u8, cells: &[(i8, i8)]) -> Option<Operation> {
+        let mut wanted = cells.to_vec();
+        wanted.sort();
+        Rotation::ALL.iter().find_map(|rotation| {
+            // every shape has a block on its center
+            cells.iter().find_map(|(x, y)| {
+                let operation = Operation {
+                    kind,
+                    rotation: *rotation,
+                    x: *x,
+                    y: *y,
+                };
+                let mut covered = operation.cells();
+                covered.sort();
+                match covered == wanted {
+                    true => Some(operation),
+                    false => None,
+                }
+            })
+        })
+    }
+    // the data keeps the centers of fumen's older rotation system for some of the pieces
+    fn stored_cell(&self) -> u32 {
+        let (dx, dy) = match (self.kind, self.rotation) {
+            (3, Rotation::Left) => (-1, 1),
+            (3, Rotation::Reverse) => (-1, 0),
+            (3, Rotation::Spawn) => (0, 1),
+            (1, Rotation::Reverse) => (-1, 0),
+            (1, Rotation::Left) => (0, 1),
+            (7, Rotation::Spawn) => (0, 1),
+            (7, Rotation::Right) => (1, 0),
+            (4, Rotation::Spawn) => (0, 1),
+            (4, Rotation::Left) => (-1, 0),
+            _ => (0, 0),
+        };
+        let (x, y) = (self.x as i32 + dx, self.y as i32 + dy);
+        ((FIELD_HEIGHT as i32 - y - 1) * WIDTH as i32 + x).clamp(0, CELLS as i32 - 1) as u32
+    }
+    fn from_stored(kind: u8, rotation: Rotation, cell: u32) -> Self {
+        let x = (cell as usize % WIDTH) as i8;
+        let y = FIELD_HEIGHT as i8 - (cell as usize / WIDTH) as i8 - 1;
+        let (dx, dy) = match (kind, rotation) {
+            (3, Rotation::Left) => (1, -1),
+            (3, Rotation::Reverse) => (1, 0),
+            (3, Rotation::Spawn) => (0, -1),
+            (1, Rotation::Reverse) => (1, 0),
+            (1, Rotation::Left) => (0, -1),
+            (7, Rotation::Spawn) => (0, -1),
+            (7, Rotation::Right) => (-1, 0),
+            (4, Rotation::Spawn) => (0, -1),
+            (4, Rotation::Left) => (1, 0),
+            _ => (0, 0),
+        };
+        Operation {
+            kind,
+            rotation,
+            x: x + dx,
+            y: y + dy,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    pub field: Vec<u8>, // the cells from the top left, the last row is the garbage row
+    pub operation: Option<Operation>,
+    pub comment: String,
+    pub lock: bool,     // the piece is put on the field for the next page
+    pub rise: bool,     // the garbage row comes up after the piece locks
+    pub mirror: bool,   // the field is flipped after the piece locks
+    pub colorize: bool, // guideline colors, only for showing the page
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Page {
+            field: vec![0; CELLS],
+            operation: None,
+            comment: String::new(),
+            lock: true,
+            rise: false,
+            mirror: false,
+            colorize: true,
+        }
+    }
+}
+
+impl Page {
+    // the field the next page starts from
+    pub fn next_field(&self) -> Vec<u8> {
+        let mut field = self.field.clone();
+        if !self.lock {
+            return field;
+        }
+        if let Some(operation) = self.operation {
+            for cell in operation.cells() {
+                if let Some(i) = index(cell) {
+                    field[i] = operation.kind;
+                }
+            }
+        }
+        let (rows, garbage) = field.split_at(WIDTH * FIELD_HEIGHT);
+        let mut rows: Vec<Vec<u8>> = rows
+            .chunks(WIDTH)
+            .filter(|row| row.contains(&0))
+            .map(|row| row.to_vec())
+            .collect();
+        let mut garbage = garbage.to_vec();
+        while rows.len() < FIELD_HEIGHT {
+            rows.insert(0, vec![0; WIDTH]);
+        }
+        if self.rise {
+            rows.remove(0);
+            rows.push(garbage);
+            garbage = vec![0; WIDTH];
+        }
+        if self.mirror {
+            for row in rows.iter_mut() {
+                row.reverse();
+            }
+        }
+        rows.push(garbage);
+        rows.concat()
+    }
+    // what a quiz page says is held and dealt, the piece in play first
+    fn quiz(&self) -> Option<(String, String)> {
+        let rest = self.comment.strip_prefix(QUIZ)?;
+        let (hold, rest) = rest.strip_prefix('[')?.split_once(']')?;
+        let (current, next) = rest.strip_prefix('(')?.split_once(')')?;
+        Some((hold.to_string(), format!("{}{}", current, next)))
+    }
+}
+
+// the index of a cell in a page's field, y counting up from the bottom row of the field and -1 for
+// the garbage row
+fn index((x, y): (i8, i8)) -> Option<usize> {
+    let row = FIELD_HEIGHT as i32 - 1 - y as i32;
+    match (0..WIDTH as i8).contains(&x) && (0..=FIELD_HEIGHT as i32).contains(&row) {
+        true => Some(row as usize * WIDTH + x as usize),
+        false => None,
+    }
+}
+
+pub fn decode(text: &str) -> Result<Vec<Page>, String> {
+    let (version, data) = text
+        .trim()
+        .rsplit_once("115@")
+        .ok_or("that isn't a fumen, they start with v115@")?;
+    if !version.ends_with(['v', 'm', 'd']) {
+        return Err("only v115 fumens can be read".to_string());
+    }
+    let mut digits = Digits::parse(data)?;
+    let mut pages: Vec<Page> = Vec::new();
+    let mut field = vec![0; CELLS];
+    let mut repeats = 0;
+    let mut comment = String::new();
+    while !digits.is_empty() {
+        let n = pages.len() + 1;
+        if repeats > 0 {
+            repeats -= 1;
+        } else {
+            let mut at = 0;
+            while at < CELLS {
+                let run = digits.take(2)?;
+                let (diff, len) = (
+                    (run / CELLS as u32) as i32,
+                    (run % CELLS as u32) as usize + 1,
+                );
+                if at + len > CELLS {
+                    return Err(format!("the field of page {} runs over", n));
+                }
+                for cell in field[at..at + len].iter_mut() {
+                    let value = *cell as i32 + diff - 8;
+                    if !(0..=GRAY as i32).contains(&value) {
+                        return Err(format!("page {} has a cell that's no piece", n));
+                    }
+                    *cell = value as u8;
+                }
+                at += len;
+                if run == SAME_FIELD {
+                    repeats = digits.take(1)?;
+                }
+            }
+        }
+        let mut action = digits.take(3)?;
+        let mut take = |n: u32| {
+            let value = action % n;
+            action /= n;
+            value
+        };
+        let kind = take(8) as u8;
+        let rotation = Rotation::ALL[take(4) as usize];
+        let cell = take(CELLS as u32);
+        let (rise, mirror, colorize, commented, unlocked) =
+            (take(2), take(2), take(2), take(2), take(2));
+        if commented == 1 {
+            let len = digits.take(2)? as usize;
+            let mut escaped = String::new();
+            for _ in 0..len.div_ceil(4) {
+                let mut chars = digits.take(5)?;
+                for _ in 0..4 {
+                    let c = COMMENT_CHARS.get((chars % COMMENT_BASE) as usize);
+                    let c = c.ok_or_else(|| format!("page {} has a broken comment", n))?;
+                    escaped.push(*c as char);
+                    chars /= COMMENT_BASE;
+                }
+            }
+            escaped.truncate(len);
+            comment = unescape(&escaped);
+        }
+        let page = Page {
+            field: field.clone(),
+            operation: match kind {
+                0 => None,
+                _ => Some(Operation::from_stored(kind, rotation, cell)),
+            },
+            comment: comment.clone(),
+            lock: unlocked == 0,
+            rise: rise == 1,
+            mirror: mirror == 1,
+            colorize: colorize == 1,
+        };
+        field = page.next_field();
+        pages.push(page);
+    }
+    match pages.is_empty() {
+        true => Err("the fumen has no pages".to_string()),
+        false => Ok(pages),
+    }
+}
+
+pub fn encode(pages: &[Page]) -> String {
+    let mut digits = Digits::default();
+    let mut field = vec![0; CELLS];
+    let mut repeats: Option<usize> = None; // where the count of same fields is, while they last
+    let mut comment = "";
+    for page in pages {
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for (last, cell) in field.iter().zip(&page.field) {
+            let diff = *cell as u32 + 8 - *last as u32;
+            match runs.last_mut() {
+                Some((d, len)) if *d == diff && *len < CELLS as u32 => *len += 1,
+                _ => runs.push((diff, 1)),
+            }
+        }
+        let same = runs == [(8, CELLS as u32)];
+        match repeats {
+            Some(at) if same && digits.values[at] < DIGITS.len() as u32 - 1 => {
+                digits.values[at] += 1
+            }
+            _ => {
+                for (diff, len) in runs {
+                    digits.put(diff * CELLS as u32 + len - 1, 2);
+                }
+                repeats = match same {
+                    true => {
+                        digits.put(0, 1);
+                        Some(digits.values.len() - 1)
+                    }
+                    false => None,
+                };
+            }
+        }
+        let (kind, rotation, cell) = match page.operation {
+            Some(operation) => (operation.kind, operation.rotation, operation.stored_cell()),
+            None => (0, Rotation::Reverse, 0),
+        };
+        let commented = page.comment != comment;
+        let flags = [
+            !page.lock,
+            commented,
+            page.colorize,
+            page.mirror,
+            page.rise,
+        ];
+        let mut action = flags.iter().fold(0, |value, flag| value * 2 + *flag as u32);
+        action = action * CELLS as u32 + cell;
+        action = action * 4
+            + Rotation::ALL
+                .iter()
+                .position(|r| *r == rotation)
+                .unwrap_or(0) as u32;
+        digits.put(action * 8 + kind as u32, 3);
+        if commented {
+            let mut escaped = escape(&page.comment);
+            escaped.truncate(MAX_COMMENT);
+            digits.put(escaped.len() as u32, 2);
+            for chunk in escaped.as_bytes().chunks(4) {
+                let value = chunk.iter().rev().fold(0, |value, c| {
+                    let c = COMMENT_CHARS.iter().position(|t| t == c).unwrap_or(0) as u32;
+                    value * COMMENT_BASE + c
+                });
+                digits.put(value, 5);
+            }
+        }
+        comment = &page.comment;
+        field = page.next_field();
+    }
+    let data = digits.to_string();
+    let (first, mut rest) = data.split_at(data.len().min(FIRST_LINE));
+    let mut lines = vec![first];
+    while !rest.is_empty() {
+        let (line, after) = rest.split_at(rest.len().min(LINE));
+        lines.push(line);
+        rest = after;
+    }
+    format!("v115@{}", lines.join("?"))
+}
+
+#[derive(Default)]
+struct Digits {
+    values: Vec<u32>,
+    at: usize,
+}
+
+impl Digits {
+    fn parse(data: &str) -> Result<Self, String> {
+        let values = data
+            .chars()
+            .filter(|c| *c != '?' && !c.is_whitespace())
+            .map(|c| {
+                DIGITS
+                    .iter()
+                    .position(|d| *d as char == c)
+                    .map(|d| d as u32)
+                    .ok_or_else(|| format!("`{}` isn't part of a fumen", c))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Digits { values, at: 0 })
+    }
+    fn is_empty(&self) -> bool {
+        self.at >= self.values.len()
+    }
+    fn take(&mut self, n: usize) -> Result<u32, String> {
+        let digits = self
+            .values
+            .get(self.at..self.at + n)
+            .ok_or("the fumen ends halfway through a page")?;
+        self.at += n;
+        Ok(digits.iter().rev().fold(0, |value, d| value * 64 + d))
+    }
+    fn put(&mut self, mut value: u32, n: usize) {
+        for _ in 0..n {
+            self.values.push(value % 64);
+            value /= 64;
+        }
+    }
+}
+
+impl std::fmt::Display for Digits {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for d in &self.values {
+            write!(f, "{}", DIGITS[*d as usize] as char)?;
+        }
+        Ok(())
+    }
+}
+
+// comments are kept the way javascript's escape() writes them
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c.is_ascii_alphanumeric() || "@*_+-./".contains(c) {
+            true => escaped.push(c),
+            false if (c as u32) < 256 => escaped.push_str(&format!("%{:02X}", c as u32)),
+            false => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    escaped.push_str(&format!("%u{:04X}", unit));
+                }
+            }
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> String {
+    let mut units: Vec<u16> = Vec::new();
+    let mut rest = escaped;
+    while let Some(c) = rest.chars().next() {
+        let hex = |s: &str, len: usize| s.get(..len).and_then(|h| u16::from_str_radix(h, 16).ok());
+        let (unit, len) = match (rest.strip_prefix("%u"), rest.strip_prefix('%')) {
+            (Some(after), _) if hex(after, 4).is_some() => (hex(after, 4), 6),
+            (_, Some(after)) if hex(after, 2).is_some() => (hex(after, 2), 3),
+            _ => (None, c.len_utf8()),
+        };
+        match unit {
+            Some(unit) => units.push(unit),
+            None => units.extend(c.encode_utf16(&mut [0; 2]).iter()),
+        }
+        rest = &rest[len..];
+    }
+    String::from_utf16_lossy(&units)
+}
+
+// the cell for a block of a piece set, by the piece's name
+fn cell_of(pieces: &PieceSet, tile: &Tile) -> u8 {
+    let Some(block) = tile else {
+        return 0;
+    };
+    (0..pieces.len())
+        .find(|kind| pieces.shape(*kind).block == *block)
+        .and_then(|kind| KINDS.iter().position(|k| *k == pieces.shape(kind).name))
+        .map(|k| k as u8 + 1)
+        .unwrap_or(GRAY)
+}
+
+fn name_of(pieces: &PieceSet, kind: usize) -> Option<u8> {
+    let name = &pieces.shape(kind).name;
+    KINDS.iter().position(|k| k == name).map(|k| k as u8 + 1)
+}
+
+// the rows from the top of a board, its bottom row at the bottom of the field
+fn field_of(pieces: &PieceSet, rows: &[Vec<Tile>]) -> Result<Vec<u8>, String> {
+    if rows.first().map(|row| row.len()) != Some(WIDTH) {
+        return Err(format!("fumen boards are {} wide", WIDTH));
+    }
+    let mut field = vec![0; CELLS];
+    for (up, row) in rows.iter().rev().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            match index((x as i8, up as i8)) {
+                Some(i) if up < FIELD_HEIGHT => field[i] = cell_of(pieces, tile),
+                _ if tile.is_some() => {
+                    return Err(format!("fumen boards are {} rows tall", FIELD_HEIGHT));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(field)
+}
+
+// what's held and dealt written as a quiz, none when a piece isn't one fumen knows
+fn quiz_of(pieces: &PieceSet, hold: Option<usize>, queue: &[usize]) -> String {
+    let name = |kind: usize| name_of(pieces, kind).map(|k| KINDS[k as usize - 1]);
+    let hold = match hold.map(name) {
+        Some(Some(name)) => name,
+        Some(None) => return String::new(),
+        None => "",
+    };
+    let Some(queue) = queue
+        .iter()
+        .map(|kind| name(*kind))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return String::new();
+    };
+    match queue.split_first() {
+        Some((current, next)) => format!("{}[{}]({}){}", QUIZ, hold, current, next.concat()),
+        None => String::new(),
+    }
+}
+
+// the piece as it is on a board `height` rows tall counting the hidden ones
+fn operation_of(pieces: &PieceSet, piece: &Piece, height: usize) -> Option<Operation> {
+    let kind = name_of(pieces, piece.kind())?;
+    let cells: Vec<(i8, i8)> = piece
+        .cells()
+        .map(|(x, y)| (x, height as i8 - 1 - y))
+        .collect();
+    Operation::covering(kind, &cells)
+}
+
+// the board of a game with the piece where it is, and the held and next pieces as a quiz
+pub fn page_of(game: &Game) -> Result<Page, String> {
+    page_with(game, game.current_piece().copied())
+}
+
+fn page_with(game: &Game, piece: Option<Piece>) -> Result<Page, String> {
+    let pieces = game.piece_set();
+    let mut queue: Vec<usize> = piece.iter().map(|p| p.kind()).collect();
+    queue.extend((0..5).map(|i| game.upcoming_piece(i).kind()));
+    Ok(Page {
+        field: field_of(pieces, game.tiles())?,
+        operation: piece.and_then(|p| operation_of(pieces, &p, game.tiles().len())),
+        comment: quiz_of(pieces, game.held_piece().map(|p| p.kind()), &queue),
+        ..Page::default()
+    })
+}
+
+// a position's stack, with its queue and hold as a quiz
+pub fn page_of_position(position: &Position) -> Result<Page, String> {
+    let pieces = &position.pieces;
+    Ok(Page {
+        field: field_of(pieces, &position.tiles()?)?,
+        comment: quiz_of(pieces, position.held_kind()?, &position.queue_kinds()?),
+        ..Page::default()
+    })
+}
+
+// a page for every piece of a replay up to `pieces` of them, each locking where it did
+pub fn replay_pages(replay: &Replay, pieces: Option<u32>) -> Result<Vec<Page>, String> {
+    let mut game = Game::new(replay.settings());
+    let mut pages = Vec::new();
+    let mut inputs = replay.inputs.iter().peekable();
+    let wanted = |pages: &Vec<Page>| pieces.is_none_or(|n| pages.len() < n as usize);
+    while !game.is_over() && wanted(&pages) {
+        // one thing happens per step, the next input or whatever the game does by itself first,
+        // so no two pieces lock in one step
+        let deadline = game.next_deadline_ms();
+        let input = inputs
+            .next_if(|input| deadline.is_none_or(|deadline| input.ms < deadline))
+            .copied();
+        // a piece locks when a drop presses it down or gravity ticks while it rests on the stack,
+        // its landing is taken just before
+        let locks = match input.map(|input| input.command) {
+            Some(Command::Act(Action::HardDrop | Action::SoftDrop)) => true,
+            Some(_) => false,
+            None => game.phase() == Phase::Locking,
+        };
+        let landing = match locks {
+            true => Some(page_with(&game, game.ghost_piece())?),
+            false => None,
+        };
+        let locked = game.pieces_locked();
+        match (input, deadline) {
+            (Some(input), _) => {
+                game.advance_to(input.ms);
+                game.handle(input.command);
+            }
+            (None, Some(deadline)) => {
+                game.advance_to(deadline);
+            }
+            (None, None) => break,
+        }
+        if game.pieces_locked() > locked {
+            pages.push(landing.ok_or("a piece locked where none was expected to")?);
+        }
+    }
+    match pages.is_empty() {
+        true => Err("the replay has no pieces locked".to_string()),
+        false => Ok(pages),
+    }
+}
+
+// the pages as positions to practice from, for the tetrominoes on a standard board (taller when
+// the stack needs it). the piece of a page is the one in play and the pieces of the pages after it
+// come next, unless the page is a quiz saying what's held and dealt
+pub fn positions(pages: &[Page]) -> Result<Vec<Position>, String> {
+    let pieces = PieceSet::tetrominoes();
+    let height = pages
+        .iter()
+        .filter_map(|page| {
+            let top = page.field[..WIDTH * FIELD_HEIGHT]
+                .iter()
+                .position(|c| *c != 0)?;
+            Some(FIELD_HEIGHT - top / WIDTH)
+        })
+        .fold(board::BOARD_HEIGHT, usize::max);
+    let block = |cell: u8| match cell {
+        0 => None,
+        1..=7 => (0..pieces.len())
+            .map(|kind| pieces.shape(kind))
+            .find(|shape| shape.name == KINDS[cell as usize - 1])
+            .map(|shape| shape.block),
+        _ => Some(Block::Garbage),
+    };
+    pages
+        .iter()
+        .enumerate()
+        .map(|(n, page)| {
+            let rows: Vec<Vec<Tile>> = page.field[WIDTH * (FIELD_HEIGHT - height)..]
+                .chunks(WIDTH)
+                .take(height)
+                .map(|row| row.iter().map(|cell| block(*cell)).collect())
+                .collect();
+            let (hold, queue) = page.quiz().unwrap_or_else(|| {
+                let queue = pages[n..]
+                    .iter()
+                    .map_while(|page| page.operation)
+                    .map(|operation| KINDS[operation.kind as usize - 1])
+                    .collect();
+                (String::new(), queue)
+            });
+            let position = Position::from_tiles(&pieces, &rows, &queue, &hold);
+            position.queue_kinds()?;
+            position.held_kind()?;
+            Ok(position)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::GameSettings;
+    use crate::engine::Mode;
+    use crate::garbage::GarbageStyle;
+    use crate::replay::Input;
+    use std::sync::Arc;
+
+    const T: u8 = 5;
+    // an empty field
+    const EMPTY: &str = "v115@vhAAgH";
+    // a T on the floor, and the page after it with the T locked
+    const T_LOCKED: &str = "v115@vhBVQJAAA";
+    // a T, with I held and S and Z coming after it
+    const QUIZ: &str = "v115@vhA1OYXAFLDmClcJSAVDVSAVG88AYe88A5sDCA";
+    // a T that isn't locked over a gray cell in the garbage row, then a page that brings the
+    // garbage row up and mirrors the field, then the field that leaves
+    const FLAGS: &str = "v115@qhA8De1OnvhBAoUTAye0rDFbcRASx78AwDWXEyX6CA?AAA";
+
+    fn t_on_floor(x: i8) -> Operation {
+        Operation {
+            kind: T,
+            rotation: Rotation::Spawn,
+            x,
+            y: 0,
+        }
+    }
+
+    #[test]
+    fn known_fumens_write_back_the_same() {
+        for fumen in [EMPTY, T_LOCKED, QUIZ, FLAGS] {
+            assert_eq!(encode(&decode(fumen).unwrap()), fumen);
+        }
+        // the site's url and line breaks are skipped
+        let shared = format!("https://fumen.zui.jp/?{}", FLAGS.replace('?', "?\n"));
+        assert_eq!(encode(&decode(&shared).unwrap()), FLAGS);
+    }
+
+    #[test]
+    fn known_fumens_read_as_drawn() {
+        assert_eq!(decode(EMPTY).unwrap(), vec![Page::default()]);
+
+        let pages = decode(T_LOCKED).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].operation, Some(t_on_floor(4)));
+        assert_eq!(pages[1].operation, None);
+        assert_eq!(pages[1].field, pages[0].next_field());
+        let filled: Vec<usize> = (0..CELLS).filter(|i| pages[1].field[*i] == T).collect();
+        assert_eq!(filled, vec![214, 223, 224, 225]);
+
+        let page = &decode(QUIZ).unwrap()[0];
+        assert_eq!(page.comment, "#Q=[I](T)SZ");
+        assert_eq!(page.operation, Some(t_on_floor(1)));
+
+        let pages = decode(FLAGS).unwrap();
+        assert!(!pages[0].lock);
+        assert_eq!(pages[1].field, pages[0].field);
+        assert!(pages[1].rise && pages[1].mirror);
+        assert_eq!(pages[1].comment, "rise & mirror");
+        assert_eq!(pages[2].comment, "rise & mirror");
+        // up a row from the garbage row and over to the other side
+        let gray: Vec<usize> = (0..CELLS).filter(|i| pages[2].field[*i] == GRAY).collect();
+        assert_eq!(gray, vec![224]);
+    }
+
+    #[test]
+    fn pages_read_back_the_same() {
+        let mut pages = vec![
+            Page {
+                operation: Some(t_on_floor(4)),
+                comment: "テトリス 100%".to_string(),
+                ..Page::default()
+            },
+            Page {
+                operation: Some(Operation {
+                    kind: 1,
+                    rotation: Rotation::Left,
+                    x: 0,
+                    y: 1,
+                }),
+                colorize: false,
+                ..Page::default()
+            },
+            Page {
+                operation: Some(Operation {
+                    kind: 3,
+                    rotation: Rotation::Spawn,
+                    x: 8,
+                    y: 0,
+                }),
+                rise: true,
+                colorize: false,
+                ..Page::default()
+            },
+            Page {
+                colorize: false,
+                ..Page::default()
+            },
+        ];
+        pages[0].field[230..239].fill(GRAY);
+        pages[1].field = pages[0].next_field();
+        pages[2].field = pages[1].next_field();
+        // edited by hand rather than left by the page before
+        pages[3].field = pages[2].next_field();
+        pages[3].field[0] = 2;
+        assert_eq!(decode(&encode(&pages)).unwrap(), pages);
+    }
+
+    #[test]
+    fn positions_read_back_the_same() {
+        let pieces = PieceSet::tetrominoes();
+        let mut rows = vec![vec![None; WIDTH]; board::BOARD_HEIGHT];
+        rows[19][..9].fill(Some(Block::Garbage));
+        rows[18][0] = Some(pieces.shape(0).block);
+        rows[18][1] = Some(pieces.shape(6).block);
+        let position = Position::from_tiles(&pieces, &rows, "TSZ", "I");
+        let page = page_of_position(&position).unwrap();
+        assert_eq!(page.comment, "#Q=[I](T)SZ");
+        let read = positions(&decode(&encode(&[page])).unwrap()).unwrap();
+        assert_eq!(read, vec![position]);
+    }
+
+    #[test]
+    fn replays_lock_a_page_at_a_time() {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 3,
+            timing: Mode::Marathon.timing(),
+            width: WIDTH,
+            height: board::BOARD_HEIGHT,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        // hard drops to the left and right in turn, with time for gravity to lock some pieces too
+        let moves = [
+            Action::InstantLeft,
+            Action::InstantRight,
+        ];
+        let mut inputs = Vec::new();
+        let mut ms = 0;
+        while !game.is_over() && inputs.len() < 60 {
+            ms += 700;
+            game.advance_to(ms);
+            let action = match inputs.len() % 3 {
+                2 => Action::HardDrop,
+                n => moves[n],
+            };
+            if !game.is_over() {
+                game.apply(action);
+                inputs.push(Input {
+                    ms,
+                    command: Command::Act(action),
+                });
+            }
+        }
+        let locked = game.pieces_locked();
+        let replay = Replay::from_game(&game, inputs);
+        let pages = replay_pages(&replay, None).unwrap();
+        assert_eq!(pages.len(), locked as usize);
+        for pair in pages.windows(2) {
+            assert_eq!(pair[0].next_field(), pair[1].field);
+        }
+        assert_eq!(replay_pages(&replay, Some(3)).unwrap(), pages[..3]);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::board::MAX_BOARD_HEIGHT;
+use crate::{text_art::BLOCK_CHAR, tile::Block};
+use cursive::{
+    Printer, View,
+    theme::{BaseColor, Color},
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// most garbage rows that come up under a single locked piece, the rest waits for the next one
+pub const MAX_GARBAGE_PER_LOCK: u32 = 8;
+// the most lines a single attack from someone else can be, taller than any board
+pub const MAX_ATTACK_LINES: u32 = MAX_BOARD_HEIGHT as u32;
+
+// where the holes of incoming rows go
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GarbageStyle {
+    #[default]
+    Clean, // every row of an attack has its hole in the same column
+    Messy, // every row gets a hole of its own
+}
+
+impl GarbageStyle {
+    pub const ALL: [GarbageStyle; 2] = [
+        GarbageStyle::Clean,
+        GarbageStyle::Messy,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            GarbageStyle::Clean => "clean",
+            GarbageStyle::Messy => "messy",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<GarbageStyle> {
+        GarbageStyle::ALL.into_iter().find(|s| s.name() == name)
+    }
+}
+
+// lines sent to the other side for clearing this many at once
+pub fn attack_for_clear(lines: u32) -> u32 {
+    match lines {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        n => n, // a tetrs or better sends everything
+    }
+}
+
+// the lines of an attack as another player or a replay wrote them, None for more than any board
+// could take
+pub fn parse_attack(text: &str) -> Option<u32> {
+    text.parse().ok().filter(|lines| *lines <= MAX_ATTACK_LINES)
+}
+
+// one attack, its hole is picked when its first row comes up
+#[derive(Clone, Copy)]
+struct Batch {
+    lines: u32,
+    hole: Option<usize>,
+}
+
+// attack that has been received but hasn't come up onto the board yet, oldest first
+#[derive(Clone, Default)]
+pub struct GarbageQueue {
+    batches: VecDeque<Batch>,
+}
+
+impl GarbageQueue {
+    pub fn push(&mut self, lines: u32) {
+        if lines > 0 {
+            self.batches.push_back(Batch { lines, hole: None });
+        }
+    }
+    pub fn pending(&self) -> u32 {
+        self.batches
+            .iter()
+            .fold(0, |sum: u32, b| sum.saturating_add(b.lines))
+    }
+    // takes up to lines off the oldest attacks, returns how many were cancelled
+    pub fn cancel(&mut self, mut lines: u32) -> u32 {
+        let mut cancelled = 0;
+        while let Some(batch) = self.batches.front_mut() {
+            if lines == 0 {
+                break;
+            }
+            let n = lines.min(batch.lines);
+            batch.lines -= n;
+            lines -= n;
+            cancelled += n;
+            if batch.lines == 0 {
+                self.batches.pop_front();
+            }
+        }
+        cancelled
+    }
+    // the hole column of each row to add, bottom row last
+    pub fn take_rows(
+        &mut self,
+        max: u32,
+        width: usize,
+        style: GarbageStyle,
+        rng: &mut impl Rng,
+    ) -> Vec<usize> {
+        let mut holes = Vec::new();
+        while let Some(batch) = self.batches.front_mut() {
+            if holes.len() as u32 == max {
+                break;
+            }
+            let hole = match (style, batch.hole) {
+                (GarbageStyle::Clean, Some(hole)) => hole,
+                _ => rng.random_range(0..width),
+            };
+            batch.hole = Some(hole);
+            holes.push(hole);
+            batch.lines -= 1;
+            if batch.lines == 0 {
+                self.batches.pop_front();
+            }
+        }
+        holes
+    }
+}
+
+// a column beside the board that fills up from the bottom with the garbage waiting to come up
+pub struct GarbageMeter {
+    pending: u32,
+    rows: usize, // visible rows of the board it stands beside
+    large: bool,
+}
+
+impl GarbageMeter {
+    pub fn new(rows: usize) -> Self {
+        GarbageMeter {
+            pending: 0,
+            rows,
+            large: false,
+        }
+    }
+    pub fn set_pending(&mut self, pending: u32) {
+        self.pending = pending;
+    }
+    pub fn set_rows(&mut self, rows: usize) {
+        self.rows = rows;
+    }
+    fn get_scale(&self) -> usize {
+        match self.large {
+            true => 2,
+            false => 1,
+        }
+    }
+    pub fn set_scale(&mut self, make_large: bool) {
+        self.large = make_large;
+    }
+}
+
+impl View for GarbageMeter {
+    fn required_size(&mut self, _constraint: cursive::XY<usize>) -> cursive::XY<usize> {
+        (self.get_scale(), self.rows * self.get_scale()).into()
+    }
+    fn draw(&self, printer: &Printer) {
+        let filled = (self.pending as usize).min(self.rows);
+        for row in 0..self.rows {
+            // more than a board's worth coming is drawn in a brighter red
+            let color = match (row >= self.rows - filled, self.pending as usize > self.rows) {
+                (false, _) => Color::Dark(BaseColor::Black),
+                (true, false) => Block::Red.get_color(),
+                (true, true) => Color::Light(BaseColor::Red),
+            };
+            for dy in 0..self.get_scale() {
+                for dx in 0..self.get_scale() {
+                    printer.with_style(color, |p| {
+                        p.print((dx, row * self.get_scale() + dy), BLOCK_CHAR)
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Game;
+    use crate::engine::GameSettings;
+    use crate::engine::Mode;
+    use crate::piece_set::PieceSet;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::Arc;
+
+    #[test]
+    fn cancel_takes_from_the_oldest_attacks() {
+        let mut queue = GarbageQueue::default();
+        queue.push(2);
+        queue.push(0); // nothing to queue
+        queue.push(3);
+        assert_eq!(queue.pending(), 5);
+        assert_eq!(queue.cancel(3), 3);
+        assert_eq!(queue.pending(), 2);
+        assert_eq!(queue.cancel(10), 2);
+        assert_eq!(queue.pending(), 0);
+        assert_eq!(queue.cancel(1), 0);
+    }
+
+    #[test]
+    fn take_rows_keeps_a_clean_attack_in_one_column() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut queue = GarbageQueue::default();
+        queue.push(3);
+        queue.push(2);
+        let first = queue.take_rows(2, 10, GarbageStyle::Clean, &mut rng);
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0], first[1]);
+        // the rest of the first attack keeps its hole
+        let rest = queue.take_rows(MAX_GARBAGE_PER_LOCK, 10, GarbageStyle::Clean, &mut rng);
+        assert_eq!(rest.len(), 3);
+        assert_eq!(rest[0], first[0]);
+        assert_eq!(rest[1], rest[2]);
+        assert!(rest.iter().all(|hole| *hole < 10));
+        assert_eq!(queue.pending(), 0);
+    }
+
+    #[test]
+    fn pending_saturates() {
+        let mut queue = GarbageQueue::default();
+        queue.push(u32::MAX);
+        queue.push(u32::MAX);
+        assert_eq!(queue.pending(), u32::MAX);
+    }
+
+    #[test]
+    fn attacks_taller_than_any_board_are_rejected() {
+        assert_eq!(parse_attack("4"), Some(4));
+        assert_eq!(
+            parse_attack(&MAX_ATTACK_LINES.to_string()),
+            Some(MAX_ATTACK_LINES)
+        );
+        assert_eq!(parse_attack("4294967295"), None);
+        assert_eq!(parse_attack("-1"), None);
+    }
+
+    #[test]
+    fn a_game_takes_no_more_than_its_height() {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 1,
+            timing: Mode::Marathon.timing(),
+            width: 10,
+            height: 20,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        game.receive_garbage(MAX_ATTACK_LINES);
+        game.receive_garbage(MAX_ATTACK_LINES);
+        assert_eq!(game.pending_garbage(), 40);
+    }
+}
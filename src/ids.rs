@@ -1,4 +1,4 @@
-// ids for cursive callbacks on views
+// ids for cursive callbacks on views, see `for_player` for the views of a versus game
 pub const ACTION: &str = "action";
 pub const HIGH_SCORE: &str = "highscore";
 pub const SCORE: &str = "score";
@@ -6,10 +6,39 @@ pub const LEVEL: &str = "level";
 pub const LINES: &str = "lines";
 pub const STARTING_LEVEL: &str = "slevel";
 pub const STARTING_LEVEL_PREVIEW: &str = "slevelp";
+pub const MODE_PREVIEW: &str = "modep";
 pub const BOARD: &str = "board";
 pub const GHOST_PIECE_ON_OFF: &str = "ghostpiece";
 pub const AUDIO_ON_OFF: &str = "audioset";
+pub const BOT_DIFFICULTY_PREVIEW: &str = "botdifficultyp";
+pub const HINTS_PREVIEW: &str = "hintsp";
 pub const HELD_PIECE: &str = "heldpiece";
+pub const GARBAGE_METER: &str = "garbagemeter";
+// the other side of a networked game
+pub const OPPONENT: &str = "opponent";
+pub const OPPONENT_METER: &str = "opponentmeter";
+pub const OPPONENT_STATS: &str = "opponentstats";
+// who is winning a game being watched
+pub const SPECTATOR_STATUS: &str = "spectatorstatus";
+// practice setup
+pub const PRACTICE_QUEUE: &str = "practicequeue";
+pub const PRACTICE_REPEAT: &str = "practicerepeat";
+pub const PRACTICE_GRAVITY: &str = "practicegravity";
+pub const PRACTICE_ERROR: &str = "practiceerror";
+// board editor
+pub const EDITOR: &str = "editor";
+pub const EDITOR_PALETTE: &str = "editorpalette";
+pub const EDITOR_QUEUE: &str = "editorqueue";
+pub const EDITOR_HOLD: &str = "editorhold";
+pub const EDITOR_NAME: &str = "editorname";
+pub const EDITOR_STATUS: &str = "editorstatus";
+pub const FUMEN: &str = "fumen";
+pub const FUMEN_ERROR: &str = "fumenerror";
+// battle royale
+pub const ROYALE_LOBBY: &str = "royalelobby";
+pub const ROYALE_STATUS: &str = "royalestatus";
+pub const TARGETING_PREVIEW: &str = "targetingpreview";
+pub const MINI_BOARD: &str = "miniboard";
 // next pieces
 pub const NEXT_PIECE: &str = "npiece";
 pub const PIECE_IN_2: &str = "2piece";
@@ -19,6 +48,8 @@ pub const PIECE_IN_4: &str = "4piece";
 pub const HIDE_IN_2: &str = "h2";
 pub const HIDE_IN_3: &str = "h3";
 pub const HIDE_IN_4: &str = "h4";
+pub const HINTS: &str = "hints";
+pub const HIDE_HINTS: &str = "hidehints";
 
 // stats
 pub const ELAPSED: &str = "elapsed";
@@ -28,3 +59,13 @@ pub const DOUBLES: &str = "doubles";
 pub const TRIPLES: &str = "triples";
 pub const TETRSES: &str = "tetrses";
 pub const TETRS_RATE: &str = "tetrsrate";
+pub const FINESSE: &str = "finesse";
+
+// each side of a versus game has its own copy of the views above, player 0 is the only player of
+// a normal game and keeps the plain ids
+pub fn for_player(id: &str, player: usize) -> String {
+    match player {
+        0 => id.to_string(),
+        _ => format!("{}{}", id, player),
+    }
+}
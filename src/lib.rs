@@ -2,9 +2,23 @@
 pub mod log;
 mod audio;
 pub mod board;
+pub mod bot;
+pub mod config;
+pub mod controls;
+pub mod editor;
+pub mod engine;
+pub mod external_bot;
+pub mod fumen;
+pub mod garbage;
 mod ids;
+pub mod net;
 pub mod piece;
+pub mod piece_set;
+pub mod position;
+pub mod replay;
+pub mod royale;
 pub mod save;
+pub mod scores;
 pub mod tetrs;
 mod text_art;
-mod tile;
+pub mod tile;
@@ -1,5 +1,599 @@
+use clap::{Parser, Subcommand};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+use tetrs_tui::board;
+use tetrs_tui::bot;
+use tetrs_tui::bot::BotSpec;
+use tetrs_tui::config;
+use tetrs_tui::engine::Game;
+use tetrs_tui::engine::GameSettings;
+use tetrs_tui::engine::Mode;
+use tetrs_tui::fumen;
+use tetrs_tui::net;
+use tetrs_tui::piece_set::PieceSet;
+use tetrs_tui::replay::Replay;
+use tetrs_tui::royale;
+use tetrs_tui::scores;
 use tetrs_tui::tetrs;
 
-fn main() {
-    tetrs::run();
+// doc comments on these are what `tetrs --help` prints
+/// A terminal-based Tetris clone.
+#[derive(Parser)]
+#[command(name = "tetrs", version)]
+struct Cli {
+    /// Game mode to play: marathon, sprint or ultra
+    #[arg(long, value_parser = parse_mode)]
+    mode: Option<Mode>,
+    /// Starting level
+    #[arg(long, value_parser = clap::value_parser!(u8).range(
+        config::MIN_STARTING_LEVEL as i64..=config::MAX_STARTING_LEVEL as i64
+    ))]
+    level: Option<u8>,
+    /// Board width in columns
+    #[arg(long, value_parser = clap::value_parser!(u64).range(
+        board::MIN_BOARD_WIDTH as u64..=board::MAX_BOARD_WIDTH as u64
+    ))]
+    width: Option<u64>,
+    /// Board height in visible rows
+    #[arg(long, value_parser = clap::value_parser!(u64).range(
+        board::MIN_BOARD_HEIGHT as u64..=board::MAX_BOARD_HEIGHT as u64
+    ))]
+    height: Option<u64>,
+    /// Piece set: tetrominoes, pentominoes, trominoes or a piece set file
+    #[arg(long, value_name = "SET")]
+    pieces: Option<String>,
+    /// Seed for the piece sequence, every game of the session uses it
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Start with the music off
+    #[arg(long)]
+    no_music: bool,
+    /// Start with the ghost piece off
+    #[arg(long)]
+    no_ghost: bool,
+    /// Use this config file instead of the default one
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Watch a replay instead of playing
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+    /// Bot for Watch Bot and Versus Bot: easy, medium, hard, expert or the command line of a bot
+    /// program
+    #[arg(long, value_name = "BOT")]
+    bot: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List recorded scores, best first
+    Scores {
+        /// Only list this mode
+        #[arg(long, value_parser = parse_mode)]
+        mode: Option<Mode>,
+        /// Re-simulate every replay and mark scores that don't hold up
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Work with replay files
+    Replay {
+        #[command(subcommand)]
+        command: ReplayCommand,
+    },
+    /// Host a networked versus game and wait for someone to join
+    Host {
+        /// Port to listen on
+        #[arg(long, default_value_t = net::DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Join a networked versus game
+    Join {
+        /// Address of the host, like 192.168.1.20 or example.com:4994
+        addr: String,
+    },
+    /// Watch a networked versus game someone is hosting
+    Watch {
+        /// Address of the host, like 192.168.1.20 or example.com:4994
+        addr: String,
+    },
+    /// Run a battle royale server, uses --level, --width, --height and --pieces for its games
+    Server {
+        /// Port to listen on
+        #[arg(long, default_value_t = net::DEFAULT_PORT)]
+        port: u16,
+        /// Most players let in at once
+        #[arg(long, default_value_t = royale::DEFAULT_MAX_PLAYERS as u64, value_parser = clap::value_parser!(u64).range(
+            2..=royale::MAX_PLAYERS as u64
+        ))]
+        max_players: u64,
+    },
+    /// Join a battle royale server
+    Battle {
+        /// Address of the server, like 192.168.1.20 or example.com:4994
+        addr: String,
+        /// Name shown to the other players, defaults to your user name
+        #[arg(long, value_parser = parse_name)]
+        name: Option<String>,
+    },
+    /// Let the bot play games without a screen and time them, uses --mode, --level, --width,
+    /// --height, --pieces and --seed (counting up from it for every game)
+    Bench {
+        /// Games to play
+        #[arg(long, default_value_t = 10)]
+        games: u64,
+        /// Stop a game after this many pieces
+        #[arg(long, default_value_t = 500)]
+        max_pieces: usize,
+        /// Pieces the bot looks ahead, the current one included
+        #[arg(long, default_value_t = bot::DEFAULT_LOOKAHEAD as u64, value_parser = clap::value_parser!(u64).range(1..=5))]
+        lookahead: u64,
+    },
+    /// Play bots against each other without a screen, every pair of them --games times on the same
+    /// pieces, uses --level, --width, --height, --pieces and --seed
+    Tournament {
+        /// A bot: easy, medium, hard, expert or the command line of a bot program, give at least
+        /// two
+        #[arg(long = "bot", value_name = "BOT", required = true)]
+        bots: Vec<String>,
+        /// Games every pair of bots plays
+        #[arg(long, default_value_t = 2)]
+        games: u64,
+        /// Call a game a draw once both bots placed this many pieces
+        #[arg(long, default_value_t = 1000)]
+        max_pieces: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReplayCommand {
+    /// Re-simulate a replay and check that its score, lines and time are real
+    Verify { file: PathBuf },
+    /// Print a replay as a fumen, a page for every piece
+    Fumen {
+        file: PathBuf,
+        /// Only the page of this piece, counting from 1
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        piece: Option<u32>,
+    },
+}
+
+fn parse_mode(name: &str) -> Result<Mode, String> {
+    Mode::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = Mode::ALL.iter().map(|m| m.name()).collect();
+        format!("expected one of: {}", names.join(", "))
+    })
+}
+
+// the default port when none is given
+fn with_default_port(addr: String) -> String {
+    match addr.contains(':') {
+        true => addr,
+        false => format!("{}:{}", addr, net::DEFAULT_PORT),
+    }
+}
+
+fn parse_name(name: &str) -> Result<String, String> {
+    match royale::valid_name(name) {
+        true => Ok(name.to_string()),
+        false => Err("names are up to 16 letters, digits, `-` and `_`".to_string()),
+    }
+}
+
+fn main() -> ExitCode {
+    let mut cli = Cli::parse();
+    let network = match cli.command.take() {
+        Some(Command::Scores { mode, verify }) => return list_scores(mode, verify),
+        Some(Command::Replay {
+            command: ReplayCommand::Verify { file },
+        }) => return verify_replay(&file),
+        Some(Command::Replay {
+            command: ReplayCommand::Fumen { file, piece },
+        }) => return replay_fumen(&file, piece),
+        Some(Command::Host { port }) => Some(tetrs::Network::Host { port }),
+        Some(Command::Join { addr }) => Some(tetrs::Network::Join {
+            addr: with_default_port(addr),
+        }),
+        Some(Command::Watch { addr }) => Some(tetrs::Network::Watch {
+            addr: with_default_port(addr),
+        }),
+        Some(Command::Server { port, max_players }) => {
+            let settings = net::MatchSettings {
+                seed: 0, // every game picks its own
+                starting_level: cli.level.unwrap_or(config::MIN_STARTING_LEVEL),
+                width: cli.width.map(|w| w as usize).unwrap_or(board::BOARD_WIDTH),
+                height: cli
+                    .height
+                    .map(|h| h as usize)
+                    .unwrap_or(board::BOARD_HEIGHT),
+                pieces: cli.pieces.unwrap_or_else(|| PieceSet::default().name),
+                garbage_style: Default::default(),
+            };
+            return run_server(port, max_players as usize, settings);
+        }
+        Some(Command::Battle { addr, name }) => Some(tetrs::Network::Battle {
+            addr: with_default_port(addr),
+            name: name.unwrap_or_else(|| {
+                std::env::var("USER")
+                    .ok()
+                    .filter(|n| royale::valid_name(n))
+                    .unwrap_or_else(|| "player".to_string())
+            }),
+        }),
+        Some(Command::Bench {
+            games,
+            max_pieces,
+            lookahead,
+        }) => {
+            return match game_settings(&cli, cli.mode.unwrap_or_default()) {
+                Ok(settings) => bench(settings, games, max_pieces, lookahead as usize),
+                Err(e) => {
+                    eprintln!("tetrs: {}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Tournament {
+            bots,
+            games,
+            max_pieces,
+        }) => {
+            let bots = bots.iter().map(|b| BotSpec::parse(b)).collect();
+            // like a versus game, over when someone tops out
+            return match game_settings(&cli, Mode::Marathon) {
+                Ok(settings) => tournament(settings, bots, games, max_pieces),
+                Err(e) => {
+                    eprintln!("tetrs: {}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        None => None,
+    };
+    let replay = match cli.replay.as_deref().map(Replay::load) {
+        Some(Err(e)) => {
+            eprintln!("tetrs: could not load replay: {}", e);
+            return ExitCode::FAILURE;
+        }
+        Some(Ok(replay)) => Some(replay),
+        None => None,
+    };
+    tetrs::run(tetrs::LaunchOptions {
+        mode: cli.mode,
+        starting_level: cli.level,
+        seed: cli.seed,
+        width: cli.width.map(|w| w as usize),
+        height: cli.height.map(|h| h as usize),
+        pieces: cli.pieces,
+        no_music: cli.no_music,
+        no_ghost: cli.no_ghost,
+        config_path: cli.config,
+        replay,
+        bot: cli.bot.as_deref().map(BotSpec::parse),
+        network,
+    });
+    ExitCode::SUCCESS
+}
+
+fn list_scores(mode: Option<Mode>, verify: bool) -> ExitCode {
+    let loaded = match scores::load() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("tetrs: could not read scores: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(corruption) = loaded.corruption {
+        eprintln!("tetrs: your score file {}", corruption);
+    }
+    let records = loaded.value.unwrap_or_default();
+    let modes: Vec<Mode> = match mode {
+        Some(mode) => vec![mode],
+        None => Mode::ALL.to_vec(),
+    };
+    let mut all_verified = true;
+    for mode in modes {
+        let board = scores::leaderboard(&records, mode);
+        if board.is_empty() {
+            continue;
+        }
+        println!("{}", mode.title());
+        for (rank, record) in board.iter().enumerate() {
+            let status = match verify {
+                false => String::new(),
+                true => match scores::verify_record(record) {
+                    Ok(_) => "  verified".to_string(),
+                    Err(e) => {
+                        all_verified = false;
+                        format!("  NOT VERIFIED: {}", e)
+                    }
+                },
+            };
+            println!(
+                "{:>4}. {}  {}{}",
+                rank + 1,
+                format_date(record.played_at),
+                record.summary,
+                status
+            );
+        }
+    }
+    match all_verified {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+fn verify_replay(path: &Path) -> ExitCode {
+    match scores::verify_replay_file(path) {
+        Ok(summary) => {
+            println!("verified: {}", summary);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("verification failed: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn replay_fumen(path: &Path, piece: Option<u32>) -> ExitCode {
+    let pages = Replay::load(path).and_then(|replay| fumen::replay_pages(&replay, piece));
+    match pages {
+        // the replay can end before the piece asked for
+        Ok(pages) if piece.is_some_and(|n| pages.len() < n as usize) => {
+            eprintln!("tetrs: the replay has only {} pieces", pages.len());
+            ExitCode::FAILURE
+        }
+        Ok(mut pages) => {
+            if piece.is_some() {
+                pages.drain(..pages.len() - 1);
+            }
+            println!("{}", fumen::encode(&pages));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("tetrs: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// unix seconds to yyyy-mm-dd (utc), see http://howardhinnant.github.io/date_algorithms.html
+fn format_date(unix_secs: u64) -> String {
+    let days = (unix_secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn run_server(port: u16, max_players: usize, settings: net::MatchSettings) -> ExitCode {
+    if let Err(e) = settings.validate() {
+        eprintln!("tetrs: {}", e);
+        return ExitCode::FAILURE;
+    }
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("tetrs: could not listen on port {}: {}", port, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!(
+        "tetrs: battle royale server for up to {} players on port {}",
+        max_players, port
+    );
+    royale::serve(listener, settings, max_players)
+}
+
+// a game without a screen from the command line options, the seed is random unless given
+fn game_settings(cli: &Cli, mode: Mode) -> Result<GameSettings, String> {
+    let pieces = PieceSet::resolve(cli.pieces.as_deref().unwrap_or("tetrominoes"))?;
+    let settings = GameSettings {
+        mode,
+        starting_level: cli.level.unwrap_or(config::MIN_STARTING_LEVEL),
+        seed: cli.seed.unwrap_or_else(rand::random),
+        timing: mode.timing(),
+        width: cli.width.map(|w| w as usize).unwrap_or(board::BOARD_WIDTH),
+        height: cli
+            .height
+            .map(|h| h as usize)
+            .unwrap_or(board::BOARD_HEIGHT),
+        pieces: Arc::new(pieces),
+        garbage_style: Default::default(),
+    };
+    if settings.width < settings.pieces.min_board_width() {
+        return Err(format!(
+            "the {} piece set needs a board at least {} wide",
+            settings.pieces.name,
+            settings.pieces.min_board_width()
+        ));
+    }
+    Ok(settings)
+}
+
+// the bot plays on the logical clock so games take as long as the search does, which is what's timed
+fn bench(settings: GameSettings, games: u64, max_pieces: usize, lookahead: usize) -> ExitCode {
+    let (mut total_pieces, mut total_lines, mut total_secs) = (0, 0, 0.0);
+    for n in 0..games {
+        let seed = settings.seed.wrapping_add(n);
+        let mut game = Game::new(GameSettings {
+            seed,
+            ..settings.clone()
+        });
+        let mut player = bot::Bot::new(lookahead, bot::DEFAULT_ACTION_MS);
+        let started = Instant::now();
+        let pieces = bot::play_out(&mut game, &mut player, max_pieces);
+        let secs = started.elapsed().as_secs_f64();
+        let ending = match game.is_over() && !game.is_finished() {
+            true => "  topped out",
+            false => "",
+        };
+        println!(
+            "game {:>3}  seed {}  {} pieces  {} lines  {} points  {:.2}s  {:.1} pieces/s{}",
+            n + 1,
+            seed,
+            pieces,
+            game.lines(),
+            game.score(),
+            secs,
+            pieces as f64 / secs.max(f64::EPSILON),
+            ending
+        );
+        total_pieces += pieces;
+        total_lines += game.lines();
+        total_secs += secs;
+    }
+    println!(
+        "total  {} pieces  {} lines  {:.2}s  {:.1} pieces/s",
+        total_pieces,
+        total_lines,
+        total_secs,
+        total_pieces as f64 / f64::max(total_secs, f64::EPSILON)
+    );
+    ExitCode::SUCCESS
+}
+
+// every bot plays every other one games times, swapping sides every other game, and a win counts
+// as much as two draws in the standings
+fn tournament(
+    settings: GameSettings,
+    bots: Vec<BotSpec>,
+    games: u64,
+    max_pieces: usize,
+) -> ExitCode {
+    if bots.len() < 2 {
+        eprintln!("tetrs: a tournament needs at least two bots");
+        return ExitCode::FAILURE;
+    }
+    let mut records = vec![[0; 3]; bots.len()]; // wins, losses, draws
+    let mut played = 0;
+    for a in 0..bots.len() {
+        for b in a + 1..bots.len() {
+            for n in 0..games {
+                let seed = settings.seed.wrapping_add(n);
+                let settings = GameSettings {
+                    seed,
+                    ..settings.clone()
+                };
+                let sides = match n % 2 {
+                    0 => [a, b],
+                    _ => [b, a],
+                };
+                // the same difficulty on both sides still makes its own mistakes
+                let started =
+                    sides.map(|i| bots[i].start(&settings, seed.wrapping_add(i as u64), true));
+                let mut players = match started {
+                    [Ok(left), Ok(right)] => [left, right],
+                    [Err(e), _] | [_, Err(e)] => {
+                        eprintln!("tetrs: {}", e);
+                        return ExitCode::FAILURE;
+                    }
+                };
+                let [left, right] = &mut players;
+                let winner = bot::play_versus(&settings, [left, right], max_pieces);
+                played += 1;
+                let result = match winner {
+                    Some(side) => {
+                        records[sides[side]][0] += 1;
+                        records[sides[1 - side]][1] += 1;
+                        format!("{} wins", bots[sides[side]].name())
+                    }
+                    None => {
+                        records[a][2] += 1;
+                        records[b][2] += 1;
+                        "draw".to_string()
+                    }
+                };
+                let gave_up: String = players
+                    .iter()
+                    .filter_map(|p| {
+                        p.error()
+                            .map(|e| format!("  ({} gave up: {})", p.name(), e))
+                    })
+                    .collect();
+                println!(
+                    "game {:>3}  seed {}  {} vs {}  {}{}",
+                    played,
+                    seed,
+                    bots[sides[0]].name(),
+                    bots[sides[1]].name(),
+                    result,
+                    gave_up
+                );
+            }
+        }
+    }
+    let mut standings: Vec<usize> = (0..bots.len()).collect();
+    standings.sort_by_key(|i| std::cmp::Reverse(records[*i][0] * 2 + records[*i][2]));
+    println!("standings");
+    for (rank, i) in standings.into_iter().enumerate() {
+        let [wins, losses, draws] = records[i];
+        println!(
+            "{:>4}. {}  {} wins  {} losses  {} draws",
+            rank + 1,
+            bots[i].name(),
+            wins,
+            losses,
+            draws
+        );
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn the_cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn options_out_of_range_are_refused() {
+        let cli = Cli::try_parse_from([
+            "tetrs", "--mode", "sprint", "--level", "9",
+        ])
+        .unwrap();
+        assert_eq!(cli.mode, Some(Mode::Sprint));
+        assert_eq!(cli.level, Some(9));
+        assert!(Cli::try_parse_from(["tetrs", "--level", "0"]).is_err());
+        assert!(Cli::try_parse_from(["tetrs", "--level", "10"]).is_err());
+        assert!(Cli::try_parse_from(["tetrs", "--mode", "zen"]).is_err());
+    }
+
+    #[test]
+    fn scores_take_a_mode_and_verify() {
+        let cli = Cli::try_parse_from([
+            "tetrs", "scores", "--mode", "ultra", "--verify",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Scores {
+                mode: Some(Mode::Ultra),
+                verify: true
+            })
+        ));
+    }
+
+    #[test]
+    fn dates_are_utc_days() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_704_067_199), "2023-12-31");
+    }
 }
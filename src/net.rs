@@ -0,0 +1,1190 @@
+// networked versus, two players over one tcp connection and anyone else watching
+//
+// the protocol is plain text, one message per line with fields separated by single spaces. both
+// sides first send `tetrs <version>`, then whoever connected says `join` to play or `watch` to
+// spectate. between the two players:
+//
+//   start <seed> <level> <width> <height> <pieces> <garbage style>
+//                               host to guest, a game begins (again after a rematch)
+//   garbage <lines>             attack sent to the other side
+//   board <stats> <palette> <rows>
+//                               what the sender's board looks like now
+//   over                        the sender topped out
+//   rematch                     the sender wants to play again, the host starts the next game
+//                               once both have asked
+//   bye                         the sender is leaving, the host also says it to anyone who joins
+//                               after the game is taken
+//
+// and from the host to spectators, who see the host as player 1 and the guest as player 2:
+//
+//   start ...                   as above, both boards start out empty
+//   frame <player> <stats> <palette> <rows>
+//                               a whole board, for spectators that come in partway
+//   delta <player> <stats> <palette> <changes>
+//                               what changed on a board since the last frame or delta
+//   result <player>             who won, 0 when nobody did
+//   bye                         the host is leaving
+//
+// <stats> is `<score> <lines> <level> <pending garbage> <held> <next>` with pieces as their index
+// in the piece set, <held> `-` when there is none and <next> separated by commas. <pieces> is a
+// built in piece set. <palette> lists the colors on the rows sent separated by commas, as names
+// like `red`, `#rrggbb` or `garbage`, and is `-` when there are none. <rows> are the visible rows
+// top to bottom separated by `/`, each tile `.` when empty or the palette index of its color as
+// 0-9, a-z then A-Z. <changes> are `<row>=<tiles>` separated by `/` with rows counted from the
+// top, or `-`. lines that don't parse are skipped so newer versions can add messages. the battle
+// royale server speaks the same protocol with a few messages of its own, see the royale module.
+use crate::board;
+use crate::board::BUFFER_HEIGHT;
+use crate::config;
+use crate::engine::Game;
+use crate::engine::GameSettings;
+use crate::engine::Mode;
+use crate::garbage;
+use crate::garbage::GarbageStyle;
+use crate::piece_set;
+use crate::piece_set::PieceSet;
+use crate::replay::Input;
+use crate::royale;
+use crate::royale::LobbyEntry;
+use crate::royale::Targeting;
+use crate::text_art::BLOCK_CHAR;
+use crate::tile::Block;
+use crate::tile::Tile;
+use cursive::Printer;
+use cursive::View;
+use cursive::theme::BaseColor;
+use cursive::theme::Color;
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::Shutdown;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use std::time::Duration;
+
+pub const PROTOCOL_VERSION: u32 = 3;
+pub const DEFAULT_PORT: u16 = 4994;
+// how long the other side gets to answer while connecting
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// how much of the next queue a snapshot carries
+const NEXT_PIECES: usize = 4;
+const PALETTE_CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+// what both sides play with, the host picks it
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchSettings {
+    pub seed: u64,
+    pub starting_level: u8,
+    pub width: usize,
+    pub height: usize,
+    pub pieces: String, // a built in set
+    pub garbage_style: GarbageStyle,
+}
+
+impl MatchSettings {
+    // networked games are always marathon with the standard delays so both sides play the same
+    pub fn game_settings(&self) -> GameSettings {
+        GameSettings {
+            mode: Mode::Marathon,
+            starting_level: self.starting_level,
+            seed: self.seed,
+            timing: Mode::Marathon.timing(),
+            width: self.width,
+            height: self.height,
+            pieces: Arc::new(PieceSet::by_name(&self.pieces).unwrap_or_default()),
+            garbage_style: self.garbage_style,
+        }
+    }
+    // catch settings from the other side that this side can't play
+    pub fn validate(&self) -> Result<(), String> {
+        if !(config::MIN_STARTING_LEVEL..=config::MAX_STARTING_LEVEL).contains(&self.starting_level)
+        {
+            return Err(format!("invalid starting level {}", self.starting_level));
+        }
+        config::validate_board_size(self.width, self.height)?;
+        let pieces = PieceSet::by_name(&self.pieces).ok_or_else(|| {
+            format!(
+                "`{}` isn't a built in piece set, networked games can only use those",
+                self.pieces
+            )
+        })?;
+        if self.width < pieces.min_board_width() {
+            return Err(format!(
+                "the {} piece set doesn't fit a board {} wide",
+                pieces.name, self.width
+            ));
+        }
+        Ok(())
+    }
+}
+
+// what a board shows besides its tiles, pieces are their index in the piece set
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub score: u32,
+    pub lines: u32,
+    pub level: u8,
+    pub pending: u32, // garbage waiting to come up
+    pub held: Option<usize>,
+    pub next: Vec<usize>,
+}
+
+impl Stats {
+    pub fn of(game: &Game) -> Self {
+        Stats {
+            score: game.score(),
+            lines: game.lines(),
+            level: game.level(),
+            pending: game.pending_garbage(),
+            held: game.held_piece().map(|p| p.kind()),
+            next: (0..NEXT_PIECES)
+                .map(|i| game.upcoming_piece(i).kind())
+                .collect(),
+        }
+    }
+    fn encode(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            self.score,
+            self.lines,
+            self.level,
+            self.pending,
+            match self.held {
+                Some(kind) => kind.to_string(),
+                None => "-".to_string(),
+            },
+            match self.next.is_empty() {
+                true => "-".to_string(),
+                false => self
+                    .next
+                    .iter()
+                    .map(|k| k.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            }
+        )
+    }
+    fn decode(fields: &[&str]) -> Option<Stats> {
+        let [
+            score,
+            lines,
+            level,
+            pending,
+            held,
+            next,
+        ] = fields
+        else {
+            return None;
+        };
+        Some(Stats {
+            score: score.parse().ok()?,
+            lines: lines.parse().ok()?,
+            level: level.parse().ok()?,
+            pending: pending.parse().ok()?,
+            held: match *held {
+                "-" => None,
+                held => Some(held.parse().ok()?),
+            },
+            next: match *next {
+                "-" => Vec::new(),
+                next => next
+                    .split(',')
+                    .map(|k| k.parse().ok())
+                    .collect::<Option<_>>()?,
+            },
+        })
+    }
+}
+
+// the visible part of a board as the other side sees it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub stats: Stats,
+    pub rows: Vec<Vec<Tile>>,
+}
+
+impl Snapshot {
+    pub fn of(game: &Game) -> Self {
+        let mut rows: Vec<Vec<Tile>> = game.tiles()[BUFFER_HEIGHT..].to_vec();
+        if let Some(piece) = game.current_piece() {
+            for (x, y) in piece.cells() {
+                let row = y as isize - BUFFER_HEIGHT as isize;
+                if row >= 0 && x >= 0 {
+                    rows[row as usize][x as usize] = Some(piece.block());
+                }
+            }
+        }
+        Snapshot {
+            stats: Stats::of(game),
+            rows,
+        }
+    }
+    // a board before its game started
+    pub fn empty(width: usize, height: usize) -> Self {
+        Snapshot {
+            stats: Stats::default(),
+            rows: vec![vec![None; width]; height],
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.rows.first().map(|r| r.len()).unwrap_or(0)
+    }
+    // what changed since an older snapshot of the same board
+    pub fn delta_from(&self, old: &Snapshot) -> Delta {
+        Delta {
+            stats: self.stats.clone(),
+            rows: self
+                .rows
+                .iter()
+                .enumerate()
+                .filter(|(i, row)| old.rows.get(*i) != Some(*row))
+                .map(|(i, row)| (i, row.clone()))
+                .collect(),
+        }
+    }
+    // rows that don't fit the board are left out
+    pub fn apply(&mut self, delta: &Delta) {
+        let width = self.width();
+        self.stats = delta.stats.clone();
+        for (i, row) in delta.rows.iter() {
+            let Some(old) = self.rows.get_mut(*i) else {
+                continue;
+            };
+            if row.len() == width {
+                *old = row.clone();
+            }
+        }
+    }
+    fn encode(&self) -> String {
+        let (palette, rows) = encode_rows(self.rows.iter());
+        format!("{} {} {}", self.stats.encode(), palette, rows.join("/"))
+    }
+    fn decode(fields: &[&str]) -> Option<Snapshot> {
+        let [stats @ .., palette, rows] = fields else {
+            return None;
+        };
+        let palette = decode_palette(palette)?;
+        let rows: Vec<Vec<Tile>> = rows
+            .split('/')
+            .map(|row| decode_row(row, &palette))
+            .collect::<Option<_>>()?;
+        let width = rows.first().map(|r| r.len()).unwrap_or(0);
+        if rows.len() > board::MAX_BOARD_HEIGHT
+            || width > board::MAX_BOARD_WIDTH
+            || rows.iter().any(|r| r.len() != width)
+        {
+            return None;
+        }
+        Some(Snapshot {
+            stats: Stats::decode(stats)?,
+            rows,
+        })
+    }
+}
+
+// the rows of a board that changed, by their index from the top
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delta {
+    pub stats: Stats,
+    pub rows: Vec<(usize, Vec<Tile>)>,
+}
+
+impl Delta {
+    fn encode(&self) -> String {
+        let (palette, rows) = encode_rows(self.rows.iter().map(|(_, row)| row));
+        let rows: Vec<String> = self
+            .rows
+            .iter()
+            .zip(rows)
+            .map(|((i, _), row)| format!("{}={}", i, row))
+            .collect();
+        format!(
+            "{} {} {}",
+            self.stats.encode(),
+            palette,
+            match rows.is_empty() {
+                true => "-".to_string(),
+                false => rows.join("/"),
+            }
+        )
+    }
+    fn decode(fields: &[&str]) -> Option<Delta> {
+        let [stats @ .., palette, rows] = fields else {
+            return None;
+        };
+        let palette = decode_palette(palette)?;
+        let rows = match *rows {
+            "-" => Vec::new(),
+            rows => rows
+                .split('/')
+                .map(|row| {
+                    let (i, row) = row.split_once('=')?;
+                    let i: usize = i.parse().ok()?;
+                    let row = decode_row(row, &palette)?;
+                    match i < board::MAX_BOARD_HEIGHT && row.len() <= board::MAX_BOARD_WIDTH {
+                        true => Some((i, row)),
+                        false => None,
+                    }
+                })
+                .collect::<Option<_>>()?,
+        };
+        Some(Delta {
+            stats: Stats::decode(stats)?,
+            rows,
+        })
+    }
+}
+
+// the palette of the colors on the rows and each row as characters of it
+fn encode_rows<'a>(rows: impl Iterator<Item = &'a Vec<Tile>>) -> (String, Vec<String>) {
+    let mut palette: Vec<Block> = Vec::new();
+    let mut encoded_rows = Vec::new();
+    for row in rows {
+        let mut encoded = String::new();
+        for tile in row {
+            let Some(block) = tile else {
+                encoded.push('.');
+                continue;
+            };
+            let idx = match palette.iter().position(|b| b == block) {
+                Some(idx) => idx,
+                None => {
+                    palette.push(*block);
+                    palette.len() - 1
+                }
+            };
+            // more colors than there are characters for is drawn as the last one
+            encoded.push(PALETTE_CHARS[idx.min(PALETTE_CHARS.len() - 1)] as char);
+        }
+        encoded_rows.push(encoded);
+    }
+    let palette: Vec<String> = palette
+        .into_iter()
+        .map(|block| match block {
+            Block::Garbage => "garbage".to_string(),
+            block => piece_set::color_name(block),
+        })
+        .collect();
+    let palette = match palette.is_empty() {
+        true => "-".to_string(),
+        false => palette.join(","),
+    };
+    (palette, encoded_rows)
+}
+
+fn decode_palette(palette: &str) -> Option<Vec<Block>> {
+    match palette {
+        "-" => Some(Vec::new()),
+        palette => palette
+            .split(',')
+            .map(|name| match name {
+                "garbage" => Some(Block::Garbage),
+                name => piece_set::parse_color(name),
+            })
+            .collect(),
+    }
+}
+
+fn decode_row(row: &str, palette: &[Block]) -> Option<Vec<Tile>> {
+    row.bytes()
+        .map(|c| match c {
+            b'.' => Some(None),
+            c => {
+                let idx = PALETTE_CHARS.iter().position(|p| *p == c)?;
+                palette.get(idx).map(|b| Some(*b))
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Hello(u32),
+    Join,
+    Watch,
+    Start(MatchSettings),
+    Garbage(u32),
+    Board(Snapshot),
+    Over,
+    Rematch,
+    Frame(usize, Snapshot),
+    Delta(usize, Delta),
+    Result(usize),
+    Bye,
+    // battle royale, see the royale module
+    Enter(String),
+    Lobby(usize, Vec<LobbyEntry>),
+    Ready(bool),
+    Target(Targeting),
+    Input(Input),
+    Clock(u64),
+    Ko {
+        player: usize,
+        place: usize,
+        by: usize,
+    },
+    Aim {
+        target: usize,
+        attackers: Vec<usize>,
+    },
+}
+
+impl Message {
+    // None for anything that isn't a message of this version
+    pub fn parse(line: &str) -> Option<Message> {
+        let fields: Vec<&str> = line.trim_end().split(' ').collect();
+        match fields.as_slice() {
+            ["tetrs", version] => Some(Message::Hello(version.parse().ok()?)),
+            ["join"] => Some(Message::Join),
+            ["watch"] => Some(Message::Watch),
+            [
+                "start",
+                seed,
+                level,
+                width,
+                height,
+                pieces,
+                style,
+            ] => Some(Message::Start(MatchSettings {
+                seed: seed.parse().ok()?,
+                starting_level: level.parse().ok()?,
+                width: width.parse().ok()?,
+                height: height.parse().ok()?,
+                pieces: pieces.to_string(),
+                garbage_style: GarbageStyle::from_name(style)?,
+            })),
+            ["garbage", lines] => Some(Message::Garbage(garbage::parse_attack(lines)?)),
+            ["board", rest @ ..] => Some(Message::Board(Snapshot::decode(rest)?)),
+            ["over"] => Some(Message::Over),
+            ["rematch"] => Some(Message::Rematch),
+            ["frame", player, rest @ ..] => Some(Message::Frame(
+                player.parse().ok()?,
+                Snapshot::decode(rest)?,
+            )),
+            ["delta", player, rest @ ..] => {
+                Some(Message::Delta(player.parse().ok()?, Delta::decode(rest)?))
+            }
+            ["result", winner] => Some(Message::Result(winner.parse().ok()?)),
+            ["enter", name] if royale::valid_name(name) => Some(Message::Enter(name.to_string())),
+            ["lobby", you, entries] => Some(Message::Lobby(
+                you.parse().ok()?,
+                match *entries {
+                    "-" => Vec::new(),
+                    entries => entries
+                        .split(',')
+                        .map(LobbyEntry::parse)
+                        .collect::<Option<_>>()?,
+                },
+            )),
+            ["ready", "0"] => Some(Message::Ready(false)),
+            ["ready", "1"] => Some(Message::Ready(true)),
+            ["target", strategy] => Some(Message::Target(Targeting::from_name(strategy)?)),
+            ["input", rest @ ..] => Some(Message::Input(Input::try_from(rest.join(" ")).ok()?)),
+            ["clock", ms] => Some(Message::Clock(ms.parse().ok()?)),
+            ["ko", player, place, by] => Some(Message::Ko {
+                player: player.parse().ok()?,
+                place: place.parse().ok()?,
+                by: by.parse().ok()?,
+            }),
+            ["aim", target, attackers] => Some(Message::Aim {
+                target: target.parse().ok()?,
+                attackers: match *attackers {
+                    "-" => Vec::new(),
+                    attackers => attackers
+                        .split(',')
+                        .map(|id| id.parse().ok())
+                        .collect::<Option<_>>()?,
+                },
+            }),
+            ["bye"] => Some(Message::Bye),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Hello(version) => write!(f, "tetrs {}", version),
+            Message::Join => write!(f, "join"),
+            Message::Watch => write!(f, "watch"),
+            Message::Start(m) => write!(
+                f,
+                "start {} {} {} {} {} {}",
+                m.seed,
+                m.starting_level,
+                m.width,
+                m.height,
+                m.pieces,
+                m.garbage_style.name()
+            ),
+            Message::Garbage(lines) => write!(f, "garbage {}", lines),
+            Message::Board(snapshot) => write!(f, "board {}", snapshot.encode()),
+            Message::Over => write!(f, "over"),
+            Message::Rematch => write!(f, "rematch"),
+            Message::Frame(player, snapshot) => {
+                write!(f, "frame {} {}", player, snapshot.encode())
+            }
+            Message::Delta(player, delta) => write!(f, "delta {} {}", player, delta.encode()),
+            Message::Result(winner) => write!(f, "result {}", winner),
+            Message::Enter(name) => write!(f, "enter {}", name),
+            Message::Lobby(you, entries) => write!(
+                f,
+                "lobby {} {}",
+                you,
+                match entries.is_empty() {
+                    true => "-".to_string(),
+                    false => entries
+                        .iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                }
+            ),
+            Message::Ready(ready) => write!(f, "ready {}", *ready as u8),
+            Message::Target(strategy) => write!(f, "target {}", strategy.name()),
+            Message::Input(input) => write!(f, "input {}", String::from(*input)),
+            Message::Clock(ms) => write!(f, "clock {}", ms),
+            Message::Ko { player, place, by } => write!(f, "ko {} {} {}", player, place, by),
+            Message::Aim { target, attackers } => write!(
+                f,
+                "aim {} {}",
+                target,
+                match attackers.is_empty() {
+                    true => "-".to_string(),
+                    false => attackers
+                        .iter()
+                        .map(|id| id.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                }
+            ),
+            Message::Bye => write!(f, "bye"),
+        }
+    }
+}
+
+// lines are read on a thread of their own so checking for messages never blocks the game
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Receiver<Message>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                let Some(message) = Message::parse(&line) else {
+                    continue;
+                };
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Connection {
+            stream,
+            incoming: rx,
+        })
+    }
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        writeln!(self.stream, "{}", message)
+    }
+    // None when nothing new has arrived, a closed connection reads as the other side saying bye
+    pub fn try_recv(&self) -> Option<Message> {
+        match self.incoming.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Message::Bye),
+        }
+    }
+    pub(crate) fn recv_timeout(&self, timeout: Duration) -> io::Result<Message> {
+        match self.incoming.recv_timeout(timeout) {
+            Ok(message) => Ok(message),
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the other side didn't answer",
+            )),
+            Err(RecvTimeoutError::Disconnected) => Ok(Message::Bye),
+        }
+    }
+    // both sides say which version they speak, anything else is refused
+    pub(crate) fn handshake(&mut self) -> io::Result<()> {
+        self.send(&Message::Hello(PROTOCOL_VERSION))?;
+        match self.recv_timeout(HANDSHAKE_TIMEOUT)? {
+            Message::Hello(PROTOCOL_VERSION) => Ok(()),
+            Message::Hello(version) => Err(io::Error::other(format!(
+                "the other side speaks protocol version {}, this is version {}",
+                version, PROTOCOL_VERSION
+            ))),
+            _ => Err(io::Error::other("the other side isn't a tetrs game")),
+        }
+    }
+}
+
+// the reading thread holds a clone of the stream, so it has to be shut down for the other side to
+// see the connection close
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+// one side of a networked game, keeps the local game and the other side in step. the host also
+// keeps everyone watching up to date
+pub struct Session {
+    conn: Connection,
+    is_host: bool,
+    settings: MatchSettings,
+    opponent: Option<Snapshot>,
+    opponent_over: bool,
+    closed: bool,
+    sent_over: bool,
+    last_board: Option<Snapshot>, // the last snapshot sent, to only send changes
+    rematch_asked: bool,
+    rematch_offered: bool, // by the other side
+    next_game: Option<MatchSettings>,
+    new_spectators: Option<Receiver<Connection>>, // only the host has any
+    spectators: Vec<Connection>,
+    watched: [Option<Snapshot>; 2], // both boards as spectators last saw them
+    result: Option<usize>,          // the winner once spectators were told, 0 for nobody
+}
+
+impl Session {
+    // waits for someone to join and starts a game with them, the seed of settings is only used for
+    // the first game. whoever connects to watch is let in for as long as the session lasts
+    pub fn host(listener: TcpListener, settings: MatchSettings) -> io::Result<Session> {
+        let (players_tx, players) = mpsc::channel();
+        let (spectators_tx, spectators) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let players_tx = players_tx.clone();
+                let spectators_tx = spectators_tx.clone();
+                // a client that takes its time doesn't keep anyone else waiting
+                thread::spawn(move || greet(stream, &players_tx, &spectators_tx));
+            }
+        });
+        let mut conn: Connection = players
+            .recv()
+            .map_err(|_| io::Error::other("stopped listening for players"))?;
+        conn.send(&Message::Start(settings.clone()))?;
+        let mut session = Session::new(conn, true, settings);
+        session.new_spectators = Some(spectators);
+        Ok(session)
+    }
+    pub fn join(addr: impl ToSocketAddrs) -> io::Result<Session> {
+        let mut conn = Connection::new(TcpStream::connect(addr)?)?;
+        conn.handshake()?;
+        conn.send(&Message::Join)?;
+        match conn.recv_timeout(HANDSHAKE_TIMEOUT)? {
+            Message::Start(settings) => {
+                settings.validate().map_err(io::Error::other)?;
+                Ok(Session::new(conn, false, settings))
+            }
+            Message::Bye => Err(io::Error::other(
+                "the host is already playing someone, use `tetrs watch` to spectate",
+            )),
+            _ => Err(io::Error::other("the host didn't start a game")),
+        }
+    }
+    fn new(conn: Connection, is_host: bool, settings: MatchSettings) -> Self {
+        Session {
+            conn,
+            is_host,
+            settings,
+            opponent: None,
+            opponent_over: false,
+            closed: false,
+            sent_over: false,
+            last_board: None,
+            rematch_asked: false,
+            rematch_offered: false,
+            next_game: None,
+            new_spectators: None,
+            spectators: Vec::new(),
+            watched: [None, None],
+            result: None,
+        }
+    }
+
+    pub fn settings(&self) -> &MatchSettings {
+        &self.settings
+    }
+    // the other side's board as of its last snapshot
+    pub fn opponent(&self) -> Option<&Snapshot> {
+        self.opponent.as_ref()
+    }
+    pub fn opponent_over(&self) -> bool {
+        self.opponent_over
+    }
+    // the other side left or the connection dropped
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // sends what changed on this side after the local game moved on and returns the attacks that
+    // came in from the other side
+    pub fn exchange(&mut self, game: &Game, attack: u32) -> Vec<u32> {
+        if attack > 0 {
+            self.send(&Message::Garbage(attack));
+        }
+        let board = Snapshot::of(game);
+        if self.last_board.as_ref() != Some(&board) {
+            self.send(&Message::Board(board.clone()));
+        }
+        if game.is_over() && !self.sent_over {
+            self.sent_over = true;
+            self.send(&Message::Over);
+        }
+        let attacks = self.poll();
+        if self.is_host {
+            self.update_spectators(board.clone(), game.is_over());
+        }
+        self.last_board = Some(board);
+        attacks
+    }
+    // handles everything the other side sent since the last call, returns the attacks among it
+    pub fn poll(&mut self) -> Vec<u32> {
+        let mut attacks = Vec::new();
+        while let Some(message) = self.conn.try_recv() {
+            match message {
+                Message::Garbage(lines) => attacks.push(lines),
+                Message::Board(snapshot) => self.opponent = Some(snapshot),
+                Message::Over => self.opponent_over = true,
+                Message::Rematch => self.rematch_offered = true,
+                Message::Start(settings) if !self.is_host && settings.validate().is_ok() => {
+                    self.next_game = Some(settings);
+                }
+                Message::Bye => {
+                    self.closed = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        if self.is_host && self.rematch_asked && self.rematch_offered && self.next_game.is_none() {
+            let settings = MatchSettings {
+                seed: rand::random(),
+                ..self.settings.clone()
+            };
+            self.send(&Message::Start(settings.clone()));
+            self.broadcast(&Message::Start(settings.clone()));
+            self.watched = [None, None];
+            self.result = None;
+            self.next_game = Some(settings);
+        }
+        attacks
+    }
+    pub fn ask_rematch(&mut self) {
+        self.rematch_asked = true;
+        self.send(&Message::Rematch);
+    }
+    // the settings of the next game once both asked for a rematch, everything about the last game
+    // is forgotten
+    pub fn take_next_game(&mut self) -> Option<MatchSettings> {
+        let settings = self.next_game.take()?;
+        self.settings = settings.clone();
+        self.opponent = None;
+        self.opponent_over = false;
+        self.sent_over = false;
+        self.last_board = None;
+        self.rematch_asked = false;
+        self.rematch_offered = false;
+        Some(settings)
+    }
+    pub fn leave(&mut self) {
+        self.send(&Message::Bye);
+        self.broadcast(&Message::Bye);
+        self.closed = true;
+    }
+    fn send(&mut self, message: &Message) {
+        if self.closed {
+            return;
+        }
+        if self.conn.send(message).is_err() {
+            self.closed = true;
+        }
+    }
+
+    // spectators see the host as player 1 and the guest as player 2. whoever just came in gets
+    // both boards whole, everyone after that only what changed
+    fn update_spectators(&mut self, own: Snapshot, lost: bool) {
+        if let Some(incoming) = &self.new_spectators {
+            while let Ok(mut conn) = incoming.try_recv() {
+                let mut ok = conn.send(&Message::Start(self.settings.clone())).is_ok();
+                for (i, board) in self.watched.iter().enumerate() {
+                    if let Some(board) = board {
+                        ok &= conn.send(&Message::Frame(i + 1, board.clone())).is_ok();
+                    }
+                }
+                if let Some(winner) = self.result {
+                    ok &= conn.send(&Message::Result(winner)).is_ok();
+                }
+                if ok {
+                    self.spectators.push(conn);
+                }
+            }
+        }
+        let boards = [
+            Some(own),
+            self.opponent.clone(),
+        ];
+        for (i, board) in boards.into_iter().enumerate() {
+            let Some(board) = board else {
+                continue;
+            };
+            let message = match &self.watched[i] {
+                Some(old) if *old == board => continue,
+                Some(old) => Message::Delta(i + 1, board.delta_from(old)),
+                None => Message::Frame(i + 1, board.clone()),
+            };
+            self.broadcast(&message);
+            self.watched[i] = Some(board);
+        }
+        if self.result.is_none() {
+            let winner = match (lost, self.opponent_over, self.closed) {
+                (true, _, _) => Some(2),
+                (false, true, _) => Some(1),
+                (false, false, true) => Some(0),
+                (false, false, false) => None,
+            };
+            if let Some(winner) = winner {
+                self.result = Some(winner);
+                self.broadcast(&Message::Result(winner));
+            }
+        }
+    }
+    // spectators that can't be reached anymore are dropped
+    fn broadcast(&mut self, message: &Message) {
+        self.spectators
+            .retain_mut(|conn| conn.send(message).is_ok());
+    }
+}
+
+// sorts out whether a new connection came to play or to watch. once there's a player anyone else
+// asking to play is turned away
+fn greet(stream: TcpStream, players: &Sender<Connection>, spectators: &Sender<Connection>) {
+    let Ok(mut conn) = Connection::new(stream) else {
+        return;
+    };
+    if conn.handshake().is_err() {
+        return;
+    }
+    match conn.recv_timeout(HANDSHAKE_TIMEOUT) {
+        Ok(Message::Join) => {
+            if let Err(mpsc::SendError(mut conn)) = players.send(conn) {
+                let _ = conn.send(&Message::Bye);
+            }
+        }
+        Ok(Message::Watch) => {
+            let _ = spectators.send(conn);
+        }
+        _ => {}
+    }
+}
+
+// watches a game someone is hosting without taking part in it
+pub struct Spectator {
+    conn: Connection,
+    settings: MatchSettings,
+    boards: [Snapshot; 2], // the host's then the guest's
+    winner: Option<usize>,
+    closed: bool,
+}
+
+impl Spectator {
+    pub fn watch(addr: impl ToSocketAddrs) -> io::Result<Spectator> {
+        let mut conn = Connection::new(TcpStream::connect(addr)?)?;
+        conn.handshake()?;
+        conn.send(&Message::Watch)?;
+        // the host only gets to new spectators between frames of its game, which starts once
+        // someone joins to play
+        let settings = loop {
+            match conn.incoming.recv() {
+                Ok(Message::Start(settings)) => break settings,
+                Ok(Message::Bye) | Err(_) => {
+                    return Err(io::Error::other("the host stopped before a game started"));
+                }
+                Ok(_) => {}
+            }
+        };
+        settings.validate().map_err(io::Error::other)?;
+        Ok(Spectator {
+            conn,
+            boards: Spectator::empty_boards(&settings),
+            settings,
+            winner: None,
+            closed: false,
+        })
+    }
+    fn empty_boards(settings: &MatchSettings) -> [Snapshot; 2] {
+        [
+            Snapshot::empty(settings.width, settings.height),
+            Snapshot::empty(settings.width, settings.height),
+        ]
+    }
+
+    pub fn settings(&self) -> &MatchSettings {
+        &self.settings
+    }
+    // player 1 is the host, 2 the guest
+    pub fn board(&self, player: usize) -> &Snapshot {
+        &self.boards[player - 1]
+    }
+    // the player who won once the game is decided, 0 when nobody did
+    pub fn winner(&self) -> Option<usize> {
+        self.winner
+    }
+    // the host left or the connection dropped
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // handles everything the host sent since the last call
+    pub fn poll(&mut self) {
+        while let Some(message) = self.conn.try_recv() {
+            match message {
+                Message::Start(settings) if settings.validate().is_ok() => {
+                    self.boards = Spectator::empty_boards(&settings);
+                    self.settings = settings;
+                    self.winner = None;
+                }
+                Message::Frame(player @ 1..=2, snapshot) => self.boards[player - 1] = snapshot,
+                Message::Delta(player @ 1..=2, delta) => self.boards[player - 1].apply(&delta),
+                Message::Result(winner) => self.winner = Some(winner),
+                Message::Bye => {
+                    self.closed = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// the other side's board, always drawn small to leave the room to the local one
+pub struct OpponentView {
+    snapshot: Option<Snapshot>,
+    width: usize,
+    height: usize,
+}
+
+impl OpponentView {
+    pub fn new(width: usize, height: usize) -> Self {
+        OpponentView {
+            snapshot: None,
+            width,
+            height,
+        }
+    }
+    pub fn set_snapshot(&mut self, snapshot: Option<Snapshot>) {
+        self.snapshot = snapshot;
+    }
+}
+
+impl View for OpponentView {
+    fn required_size(&mut self, _constraint: cursive::XY<usize>) -> cursive::XY<usize> {
+        (self.width * 2, self.height).into()
+    }
+    fn draw(&self, printer: &Printer) {
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let tile = self
+                    .snapshot
+                    .as_ref()
+                    .and_then(|s| s.rows.get(row))
+                    .and_then(|r| r.get(col))
+                    .copied()
+                    .flatten();
+                let color = match tile {
+                    Some(block) => block.get_color(),
+                    None => Color::Dark(BaseColor::Black),
+                };
+                printer.with_style(color, |p| {
+                    p.print((col * 2, row), BLOCK_CHAR);
+                    p.print((col * 2 + 1, row), BLOCK_CHAR);
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Action;
+    use std::time::Instant;
+
+    // polls until the condition holds, the other side's messages arrive on their own thread
+    fn eventually(what: &str, mut done: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn garbage_taller_than_any_board_is_refused() {
+        assert!(matches!(
+            Message::parse("garbage 3"),
+            Some(Message::Garbage(3))
+        ));
+        assert!(Message::parse("garbage 4294967295").is_none());
+    }
+
+    #[test]
+    fn a_late_frame_then_deltas_keep_up() {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 3,
+            timing: Mode::Marathon.timing(),
+            width: 10,
+            height: 20,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        game.apply(Action::HardDrop);
+        game.advance_to(game.clock_ms() + 1000);
+        // someone watching from here on gets the whole board first, over the wire
+        let frame = Snapshot::of(&game);
+        let Some(Message::Frame(1, mut seen)) =
+            Message::parse(&Message::Frame(1, frame.clone()).to_string())
+        else {
+            panic!("the frame didn't read back");
+        };
+        assert_eq!(seen, frame);
+
+        game.apply(Action::InstantLeft);
+        game.apply(Action::HardDrop);
+        let now = Snapshot::of(&game);
+        let delta = now.delta_from(&frame);
+        assert!(!delta.rows.is_empty() && delta.rows.len() < now.rows.len());
+        let Some(Message::Delta(1, delta)) = Message::parse(&Message::Delta(1, delta).to_string())
+        else {
+            panic!("the delta didn't read back");
+        };
+        seen.apply(&delta);
+        assert_eq!(seen, now);
+    }
+
+    #[test]
+    fn rows_of_the_wrong_width_are_refused() {
+        let mut seen = Snapshot::empty(10, 20);
+        let delta = Delta {
+            stats: Stats::default(),
+            rows: vec![
+                (0, vec![Some(Block::Red); 9]),
+                (1, vec![Some(Block::Red); 10]),
+            ],
+        };
+        seen.apply(&delta);
+        assert_eq!(seen.rows[0], vec![None; 10]);
+        assert_eq!(seen.rows[1], vec![Some(Block::Red); 10]);
+        // a whole frame has to be square
+        let mut ragged = Snapshot::empty(10, 20);
+        ragged.rows[5].pop();
+        assert!(Message::parse(&Message::Frame(1, ragged).to_string()).is_none());
+    }
+
+    #[test]
+    fn a_game_over_localhost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let settings = MatchSettings {
+            seed: 5,
+            starting_level: 1,
+            width: board::BOARD_WIDTH,
+            height: board::BOARD_HEIGHT,
+            pieces: "tetrominoes".to_string(),
+            garbage_style: GarbageStyle::Clean,
+        };
+        let hosting = {
+            let settings = settings.clone();
+            thread::spawn(move || Session::host(listener, settings).unwrap())
+        };
+        let joining = thread::spawn(move || Session::join(addr).unwrap());
+        let (mut host, mut guest) = (hosting.join().unwrap(), joining.join().unwrap());
+        assert_eq!(guest.settings(), &settings);
+        let mut host_game = Game::new(host.settings().game_settings());
+        let mut guest_game = Game::new(guest.settings().game_settings());
+
+        // an attack comes in as garbage on the other side
+        host_game.apply(Action::HardDrop);
+        assert!(host.exchange(&host_game, 3).is_empty());
+        let mut attacks = Vec::new();
+        eventually("the attack", || {
+            attacks.extend(guest.exchange(&guest_game, 0));
+            !attacks.is_empty()
+        });
+        assert_eq!(attacks, vec![3]);
+        for lines in attacks {
+            guest_game.receive_garbage(lines);
+        }
+        assert_eq!(guest_game.pending_garbage(), 3);
+
+        // each side sees the other's board as it is
+        eventually("the host's board", || {
+            guest.poll();
+            guest.opponent() == Some(&Snapshot::of(&host_game))
+        });
+        guest.exchange(&guest_game, 0);
+        eventually("the guest's board", || {
+            host.poll();
+            host.opponent() == Some(&Snapshot::of(&guest_game))
+        });
+
+        // topping out is seen by the other side
+        while !guest_game.is_over() {
+            guest_game.advance_to(guest_game.clock_ms() + 1000);
+            guest_game.apply(Action::HardDrop);
+        }
+        guest.exchange(&guest_game, 0);
+        eventually("the guest's game over", || {
+            host.poll();
+            host.opponent_over()
+        });
+        assert!(!guest.opponent_over());
+
+        // the next game starts once both ask, with the same new seed on both sides
+        host.ask_rematch();
+        assert!(host.take_next_game().is_none());
+        guest.ask_rematch();
+        let mut next = None;
+        eventually("the rematch", || {
+            host.poll();
+            guest.poll();
+            next = guest.take_next_game();
+            next.is_some()
+        });
+        assert_eq!(host.take_next_game(), next);
+        assert!(!host.opponent_over() && host.opponent().is_none());
+        assert_eq!(host.settings(), guest.settings());
+
+        guest.leave();
+        eventually("the guest leaving", || {
+            host.poll();
+            host.is_closed()
+        });
+    }
+}
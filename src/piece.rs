@@ -1,5 +1,5 @@
 use crate::{
-    board,
+    piece_set::{MAX_CELLS, PieceSet},
     text_art::BLOCK_CHAR,
     tile::{Block, Tile},
 };
@@ -7,167 +7,58 @@ use cursive::{
     Printer, View,
     theme::{BaseColor, Color},
 };
-use rand::Rng;
-
-#[derive(Clone, Copy)]
-pub enum PieceType {
-    I,
-    O,
-    J,
-    L,
-    S,
-    Z,
-    T,
-}
-
-const LAYOUT_LEN: usize = 4;
-pub type PieceLayout = [[Tile; LAYOUT_LEN]; LAYOUT_LEN];
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+use std::sync::Arc;
 
 pub enum Rotation {
     Left,
     Right,
+    Half,
 }
 
-impl PieceType {
-    fn get_colored_block(&self) -> Block {
-        match self {
-            PieceType::I => Block::Cyan,
-            PieceType::O => Block::Yellow,
-            PieceType::J => Block::Blue,
-            PieceType::L => Block::Orange,
-            PieceType::S => Block::Green,
-            PieceType::Z => Block::Red,
-            PieceType::T => Block::Magenta,
-        }
-    }
-    // for checking if rotation is possible
-    fn get_rot_diameter(&self) -> usize {
-        match self {
-            PieceType::I => 4,
-            PieceType::O => 4,
-            _ => 3,
-        }
-    }
-    fn get_layout(&self) -> PieceLayout {
-        type Lay = [[u8; LAYOUT_LEN]; LAYOUT_LEN];
-
-        static I_LAYOUT: Lay = [
-            [0, 0, 0, 0],
-            [1, 1, 1, 1],
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-        ];
-        static O_LAYOUT: Lay = [
-            [0, 0, 0, 0],
-            [0, 1, 1, 0],
-            [0, 1, 1, 0],
-            [0, 0, 0, 0],
-        ];
-        static J_LAYOUT: Lay = [
-            [1, 0, 0, 0],
-            [1, 1, 1, 0],
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-        ];
-        static L_LAYOUT: Lay = [
-            [0, 0, 1, 0],
-            [1, 1, 1, 0],
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-        ];
-        static S_LAYOUT: Lay = [
-            [0, 1, 1, 0],
-            [1, 1, 0, 0],
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-        ];
-        static Z_LAYOUT: Lay = [
-            [1, 1, 0, 0],
-            [0, 1, 1, 0],
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-        ];
-        static T_LAYOUT: Lay = [
-            [0, 1, 0, 0],
-            [1, 1, 1, 0],
-            [0, 0, 0, 0],
-            [0, 0, 0, 0],
-        ];
-
-        let bitmap_layout: Lay = match self {
-            PieceType::I => I_LAYOUT,
-            PieceType::O => O_LAYOUT,
-            PieceType::J => J_LAYOUT,
-            PieceType::L => L_LAYOUT,
-            PieceType::S => S_LAYOUT,
-            PieceType::Z => Z_LAYOUT,
-            PieceType::T => T_LAYOUT,
-        };
-
-        self.to_tilemap(bitmap_layout)
-    }
-    fn to_tilemap(&self, bitmap: [[u8; LAYOUT_LEN]; LAYOUT_LEN]) -> PieceLayout {
-        let mut layout: PieceLayout = [[None; LAYOUT_LEN]; LAYOUT_LEN];
-        for i in 0..LAYOUT_LEN {
-            for j in 0..LAYOUT_LEN {
-                if bitmap[i][j] == 1 {
-                    layout[i][j] = Some(self.get_colored_block());
-                }
-            }
-        }
-        layout
-    }
-}
 #[derive(Clone, Copy)]
 pub struct Piece {
-    piece_type: PieceType,
-    layout: PieceLayout,
-    // of top left, signed so piece itself can go to edge even when top left of 4x4 layout is at
-    // some 0 coord
+    kind: usize, // which shape of the game's piece set
+    block: Block,
+    cells: [(i8, i8); MAX_CELLS], // (x, y) from coord, only the first len are blocks
+    len: usize,
+    center2: (i8, i8), // rotation center from coord, doubled so it can sit between cells
+    // of top left of the shape's drawing, signed so the piece itself can go to the edge even when
+    // the drawing has empty space on that side
     coord: (i8, i8),
 }
 
 impl Piece {
-    pub fn piece_type(&self) -> PieceType {
-        self.piece_type
+    pub fn new(set: &PieceSet, kind: usize) -> Self {
+        let shape = set.shape(kind);
+        let mut cells = [(0, 0); MAX_CELLS];
+        cells[..shape.cells().len()].copy_from_slice(shape.cells());
+        Self {
+            kind,
+            block: shape.block,
+            cells,
+            len: shape.cells().len(),
+            center2: shape.center2(),
+            coord: (0, 0),
+        }
+    }
+    pub fn kind(&self) -> usize {
+        self.kind
     }
-    pub fn layout(&self) -> &PieceLayout {
-        &self.layout
+    pub fn block(&self) -> Block {
+        self.block
     }
-    pub fn layout_mut(&mut self) -> &mut PieceLayout {
-        &mut self.layout
+    // where the piece's blocks are on the board, as (x, y)
+    pub fn cells(&self) -> impl Iterator<Item = (i8, i8)> + '_ {
+        self.cells[..self.len]
+            .iter()
+            .map(|(x, y)| (x + self.coord.0, y + self.coord.1))
     }
     pub fn coord(&self) -> (i8, i8) {
         self.coord
     }
-    pub fn random_new() -> Self {
-        let mut rng = rand::rng();
-        let piece_type = match rng.random_range(0..=6) {
-            0 => PieceType::I,
-            1 => PieceType::O,
-            2 => PieceType::J,
-            3 => PieceType::L,
-            4 => PieceType::S,
-            5 => PieceType::Z,
-            6 => PieceType::T,
-            _ => PieceType::I, // shouldn't happen
-        };
-        let layout = piece_type.get_layout();
-        Self {
-            piece_type: piece_type,
-            layout: layout,
-            coord: (0, 0),
-        }
-    }
-    pub fn new(piece_type: PieceType) -> Self {
-        let layout = piece_type.get_layout();
-        Self {
-            piece_type: piece_type,
-            layout: layout,
-            coord: (0, 0),
-        }
-    }
-
     pub fn at(mut self, x: i8, y: i8) -> Self {
         self.coord = (x, y);
         self
@@ -199,141 +90,53 @@ impl Piece {
             Rotation::Right => {
                 self.rotate_right();
             }
+            Rotation::Half => {
+                self.rotate_180();
+            }
         }
     }
+    // quarter turns around the center, y grows downwards so (x, y) -> (-y, x) is clockwise
     pub fn rotate_left(&mut self) {
-        let mut temp: PieceLayout = [[None; 4]; 4];
-        match self.piece_type.get_rot_diameter() {
-            4 => {
-                temp[0][0] = self.layout[0][3];
-                temp[0][1] = self.layout[1][3];
-                temp[0][2] = self.layout[2][3];
-                temp[0][3] = self.layout[3][3];
-
-                temp[1][0] = self.layout[0][2];
-                temp[1][1] = self.layout[1][2];
-                temp[1][2] = self.layout[2][2];
-                temp[1][3] = self.layout[3][2];
-
-                temp[2][0] = self.layout[0][1];
-                temp[2][1] = self.layout[1][1];
-                temp[2][2] = self.layout[2][1];
-                temp[2][3] = self.layout[3][1];
-
-                temp[3][0] = self.layout[0][0];
-                temp[3][1] = self.layout[1][0];
-                temp[3][2] = self.layout[2][0];
-                temp[3][3] = self.layout[3][0];
-            }
-            3 => {
-                temp[0][0] = self.layout[0][2];
-                temp[0][1] = self.layout[1][2];
-                temp[0][2] = self.layout[2][2];
-
-                temp[1][0] = self.layout[0][1];
-                temp[1][1] = self.layout[1][1];
-                temp[1][2] = self.layout[2][1];
-
-                temp[2][0] = self.layout[0][0];
-                temp[2][1] = self.layout[1][0];
-                temp[2][2] = self.layout[2][0];
-            }
-            _ => {
-                //impossible, but we'll know it fails because the piece will be empty
-            }
+        let (cx2, cy2) = self.center2;
+        for (x, y) in self.cells[..self.len].iter_mut() {
+            (*x, *y) = (*y + (cx2 - cy2) / 2, (cx2 + cy2) / 2 - *x);
         }
-        self.layout = temp;
     }
     pub fn rotate_right(&mut self) {
-        let mut temp: PieceLayout = [[None; 4]; 4];
-
-        match self.piece_type.get_rot_diameter() {
-            4 => {
-                temp[0][0] = self.layout[3][0];
-                temp[0][1] = self.layout[2][0];
-                temp[0][2] = self.layout[1][0];
-                temp[0][3] = self.layout[0][0];
-
-                temp[1][0] = self.layout[3][1];
-                temp[1][1] = self.layout[2][1];
-                temp[1][2] = self.layout[1][1];
-                temp[1][3] = self.layout[0][1];
-
-                temp[2][0] = self.layout[3][2];
-                temp[2][1] = self.layout[2][2];
-                temp[2][2] = self.layout[1][2];
-                temp[2][3] = self.layout[0][2];
-
-                temp[3][0] = self.layout[3][3];
-                temp[3][1] = self.layout[2][3];
-                temp[3][2] = self.layout[1][3];
-                temp[3][3] = self.layout[0][3];
-            }
-            3 => {
-                temp[0][2] = self.layout[0][0];
-                temp[1][2] = self.layout[0][1];
-                temp[2][2] = self.layout[0][2];
-
-                temp[0][1] = self.layout[1][0];
-                temp[1][1] = self.layout[1][1];
-                temp[2][1] = self.layout[1][2];
-
-                temp[0][0] = self.layout[2][0];
-                temp[1][0] = self.layout[2][1];
-                temp[2][0] = self.layout[2][2];
-            }
-            _ => {
-                //impossible, but we'll know it fails because the piece will be empty
-            }
+        let (cx2, cy2) = self.center2;
+        for (x, y) in self.cells[..self.len].iter_mut() {
+            (*x, *y) = ((cx2 + cy2) / 2 - *y, *x + (cy2 - cx2) / 2);
         }
-        self.layout = temp;
-    }
-    // checks if piece is out of bounds for movement purposes, but pieces above the board are not
-    // considered out of bounds
-    pub fn is_out_of_bounds(&self) -> bool {
-        for i in 0..self.layout.len() {
-            for j in 0..self.layout[i].len() {
-                let tile = self.layout[i][j];
-                if tile.is_none() {
-                    continue; // we do not care, no block
-                }
-                let x = j as i8 + self.coord.0;
-                let y = i as i8 + self.coord.1;
-                if x < 0 || x >= board::BOARD_WIDTH as i8 || y >= board::BOARD_HEIGHT as i8 {
-                    return true;
-                }
-            }
-        }
-        false
+    }
+    pub fn rotate_180(&mut self) {
+        self.rotate_right();
+        self.rotate_right();
+    }
+    // checks if piece is out of bounds of a field of the given size for movement purposes, the top
+    // of the field (the hidden buffer) is a ceiling like the floor and walls
+    pub fn is_out_of_bounds(&self, width: usize, height: usize) -> bool {
+        self.cells()
+            .any(|(x, y)| x < 0 || x >= width as i8 || y < 0 || y >= height as i8)
     }
 }
 
 pub struct PieceView {
     piece: Option<Piece>,
     large: bool,
+    size: (usize, usize), // in tiles, big enough for every piece of the set
 }
 
 impl PieceView {
-    pub fn new() -> Self {
+    pub fn new(size: (usize, usize)) -> Self {
         PieceView {
             piece: None,
             large: true,
+            size,
         }
     }
 
     pub fn set_piece(&mut self, piece: Piece) {
         self.piece = Some(piece);
-        // shift up O (square) so he can fit into 2x4
-        if let Some(ref mut p) = self.piece {
-            match p.piece_type() {
-                PieceType::O => {
-                    for i in 0..PIECEVIEW_HEIGHT - 1 {
-                        p.layout_mut()[i] = p.layout_mut()[i + 1];
-                    }
-                }
-                _ => {}
-            }
-        }
     }
     pub fn set_piece_optional(&mut self, opt_piece: Option<Piece>) {
         match opt_piece {
@@ -354,12 +157,13 @@ impl PieceView {
         let i = self.get_scale() * row;
         // constant 2 to account for characters inheritantly being narrow
         let j = self.get_scale() * col * 2;
-        const SMALL_SHIFT: usize = 2; // adjust for center alignment when small
+        // adjust for center alignment when small
+        let small_shift = self.size.0 / 2;
         match self.large {
             false => {
                 // 2 chars wide, 1 char tall
                 for dx in 0..2 {
-                    Self::draw_tile_char(printer, tile, (j + dx + SMALL_SHIFT, i));
+                    Self::draw_tile_char(printer, tile, (j + dx + small_shift, i));
                 }
             }
             true => {
@@ -384,56 +188,105 @@ impl PieceView {
     }
 }
 
-const PIECEVIEW_WIDTH: usize = 4;
-const PIECEVIEW_HEIGHT: usize = 2;
-
 impl View for PieceView {
     fn required_size(&mut self, _constraint: cursive::XY<usize>) -> cursive::XY<usize> {
         // scale should be externally managed by the owning view by passing in a bool for large as
         // true/false
-        let dimen_x = PIECEVIEW_WIDTH * 2 * self.get_scale();
-        let dimen_y = PIECEVIEW_HEIGHT * self.get_scale();
+        let dimen_x = self.size.0 * 2 * self.get_scale();
+        let dimen_y = self.size.1 * self.get_scale();
         (dimen_x, dimen_y).into()
     }
     fn draw(&self, printer: &Printer) {
         // rendering logic for static board
-        for i in 0..PIECEVIEW_HEIGHT {
-            for j in 0..PIECEVIEW_WIDTH {
-                match self.piece {
-                    None => {
-                        self.draw_tile(printer, None, i, j);
-                    }
-                    Some(piece) => {
-                        let tile = piece.layout()[i][j];
-                        self.draw_tile(printer, tile, i, j);
-                    }
-                }
+        for i in 0..self.size.1 {
+            for j in 0..self.size.0 {
+                self.draw_tile(printer, None, i, j);
+            }
+        }
+        let Some(piece) = self.piece else {
+            return;
+        };
+        // centered in the view
+        let cells: Vec<(i8, i8)> = piece.cells().collect();
+        let min_x = cells.iter().map(|c| c.0).min().unwrap_or(0);
+        let max_x = cells.iter().map(|c| c.0).max().unwrap_or(0);
+        let min_y = cells.iter().map(|c| c.1).min().unwrap_or(0);
+        let max_y = cells.iter().map(|c| c.1).max().unwrap_or(0);
+        let shift_x = (self.size.0 as i8 - (max_x - min_x + 1)) / 2 - min_x;
+        let shift_y = (self.size.1 as i8 - (max_y - min_y + 1)) / 2 - min_y;
+        for (x, y) in cells {
+            let (col, row) = (x + shift_x, y + shift_y);
+            if col < 0 || row < 0 {
+                continue;
             }
+            self.draw_tile(printer, Some(piece.block()), row as usize, col as usize);
         }
     }
 }
 
 const PIECE_BAG_SIZE: usize = 7;
+// seeded so a game can be replayed piece for piece, the game moves pieces to where they spawn
+#[derive(Clone)]
 pub struct PieceBag {
+    piece_set: Arc<PieceSet>,
     pieces: [Piece; PIECE_BAG_SIZE],
     curr: usize,
+    rng: ChaCha8Rng,
+    // practice can choose the pieces, these kinds are dealt before going back to random
+    queued: VecDeque<usize>,
+    // or these over and over instead of random
+    pattern: Vec<usize>,
+    pattern_at: usize,
 }
 
 impl PieceBag {
-    pub fn new() -> Self {
+    pub fn new(seed: u64, piece_set: Arc<PieceSet>) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
         PieceBag {
-            pieces: [(); PIECE_BAG_SIZE]
-                .map(|_| Piece::random_new().at(board::PIECE_START_X, board::PIECE_START_Y)),
+            pieces: [(); PIECE_BAG_SIZE].map(|_| PieceBag::random_piece(&piece_set, &mut rng)),
+            piece_set,
             curr: 0,
+            rng,
+            queued: VecDeque::new(),
+            pattern: Vec::new(),
+            pattern_at: 0,
+        }
+    }
+    fn random_piece(piece_set: &PieceSet, rng: &mut impl Rng) -> Piece {
+        Piece::new(piece_set, rng.random_range(0..piece_set.len()))
+    }
+    fn next_piece(&mut self) -> Piece {
+        if let Some(kind) = self.queued.pop_front() {
+            return Piece::new(&self.piece_set, kind);
+        }
+        if self.pattern.is_empty() {
+            return PieceBag::random_piece(&self.piece_set, &mut self.rng);
         }
+        let kind = self.pattern[self.pattern_at];
+        self.pattern_at = (self.pattern_at + 1) % self.pattern.len();
+        Piece::new(&self.piece_set, kind)
     }
     #[inline]
     pub fn pop(&mut self) -> Piece {
         let piece = self.pieces[self.curr];
-        self.pieces[self.curr] = Piece::random_new().at(board::PIECE_START_X, board::PIECE_START_Y);
+        self.pieces[self.curr] = self.next_piece();
         self.curr = (self.curr + 1) % PIECE_BAG_SIZE;
         piece
     }
+    // the next pieces become these kinds, then it's random again unless they're to repeat. an
+    // empty queue goes straight back to random
+    pub fn set_queue(&mut self, kinds: &[usize], repeat: bool) {
+        self.queued.clear();
+        self.pattern.clear();
+        self.pattern_at = 0;
+        match repeat {
+            true => self.pattern.extend_from_slice(kinds),
+            false => self.queued.extend(kinds),
+        }
+        for i in 0..PIECE_BAG_SIZE {
+            self.pieces[(self.curr + i) % PIECE_BAG_SIZE] = self.next_piece();
+        }
+    }
 
     #[inline]
     pub fn get(&self, idx: usize) -> Piece {
@@ -441,4 +294,7 @@ impl PieceBag {
         let true_pos = (self.curr + idx) % PIECE_BAG_SIZE;
         self.pieces[true_pos]
     }
+    pub fn piece_set(&self) -> &PieceSet {
+        &self.piece_set
+    }
 }
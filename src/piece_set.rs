@@ -0,0 +1,496 @@
+use crate::tile::Block;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// the biggest piece a set may have, keeps `Piece` a small Copy type
+pub const MAX_CELLS: usize = 16;
+pub const MAX_SHAPE_LEN: usize = 8; // rows and columns of a shape's drawing
+
+// one kind of piece, as drawn in its spawn orientation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shape {
+    pub name: String,
+    pub block: Block,
+    rows: Vec<String>,    // kept as written so the set saves back the same way
+    cells: Vec<(i8, i8)>, // (x, y) from the top left of the drawing
+    center2: (i8, i8),    // rotation center, doubled so it can sit on a corner between cells
+    center_given: bool,
+}
+
+impl Shape {
+    // rows use `#` for a block and `.` (or a space) for nothing, the rotation center defaults to
+    // the middle of the blocks
+    pub fn new(
+        name: &str,
+        block: Block,
+        rows: &[&str],
+        center: Option<(f32, f32)>,
+    ) -> Result<Shape, String> {
+        let mut cells = Vec::new();
+        if rows.len() > MAX_SHAPE_LEN {
+            return Err(format!(
+                "piece {} is more than {} rows",
+                name, MAX_SHAPE_LEN
+            ));
+        }
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() > MAX_SHAPE_LEN {
+                return Err(format!(
+                    "piece {} is more than {} columns",
+                    name, MAX_SHAPE_LEN
+                ));
+            }
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    '#' => cells.push((x as i8, y as i8)),
+                    '.' | ' ' => {}
+                    _ => return Err(format!("piece {} has `{}` in its rows", name, c)),
+                }
+            }
+        }
+        if cells.is_empty() || cells.len() > MAX_CELLS {
+            return Err(format!(
+                "piece {} must have between 1 and {} blocks",
+                name, MAX_CELLS
+            ));
+        }
+        let center2 = match center {
+            Some((x, y)) => {
+                let (x2, y2) = (x * 2.0, y * 2.0);
+                if x2.fract() != 0.0 || y2.fract() != 0.0 || x2 < 0.0 || y2 < 0.0 {
+                    return Err(format!(
+                        "piece {} center must be whole or half numbers",
+                        name
+                    ));
+                }
+                // past the drawing the doubled center no longer fits an i8, and rotating around
+                // it would throw the cells far off the board anyway
+                let cols = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0);
+                if x2 > 2.0 * (cols as f32 - 1.0) || y2 > 2.0 * (rows.len() as f32 - 1.0) {
+                    return Err(format!("piece {} center must be inside its rows", name));
+                }
+                // a center on a cell's middle on one axis and its edge on the other would turn
+                // cells into half cells
+                if (x2 as i32 + y2 as i32) % 2 != 0 {
+                    return Err(format!(
+                        "piece {} center must be whole on both axes or half on both",
+                        name
+                    ));
+                }
+                (x2 as i8, y2 as i8)
+            }
+            None => {
+                let min_x = cells.iter().map(|c| c.0).min().unwrap_or(0);
+                let max_x = cells.iter().map(|c| c.0).max().unwrap_or(0);
+                let min_y = cells.iter().map(|c| c.1).min().unwrap_or(0);
+                let max_y = cells.iter().map(|c| c.1).max().unwrap_or(0);
+                let (cx2, cy2) = (min_x + max_x, min_y + max_y);
+                // same problem as above, settle on the lower of the two candidate centers
+                match (cx2 + cy2) % 2 {
+                    0 => (cx2, cy2),
+                    _ => (cx2, cy2 + 1),
+                }
+            }
+        };
+        Ok(Shape {
+            name: name.to_string(),
+            block,
+            rows: rows.iter().map(|r| r.to_string()).collect(),
+            cells,
+            center2,
+            center_given: center.is_some(),
+        })
+    }
+    pub fn cells(&self) -> &[(i8, i8)] {
+        &self.cells
+    }
+    pub fn center2(&self) -> (i8, i8) {
+        self.center2
+    }
+    // width of the drawing, for spawning pieces in the middle of the board
+    pub fn width(&self) -> usize {
+        self.rows
+            .iter()
+            .map(|r| r.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+    // size of the blocks' bounding box, as the piece spawns
+    fn extent(&self) -> (usize, usize) {
+        let span =
+            |v: Vec<i8>| (v.iter().max().unwrap_or(&0) - v.iter().min().unwrap_or(&0) + 1) as usize;
+        (
+            span(self.cells.iter().map(|c| c.0).collect()),
+            span(self.cells.iter().map(|c| c.1).collect()),
+        )
+    }
+}
+
+// the pieces a game is played with
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PieceSetFile", into = "PieceSetFile")]
+pub struct PieceSet {
+    pub name: String,
+    shapes: Vec<Shape>,
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        PieceSet::tetrominoes()
+    }
+}
+
+impl PieceSet {
+    pub const BUILT_IN: [&str; 3] = [
+        "tetrominoes",
+        "pentominoes",
+        "trominoes",
+    ];
+
+    pub fn tetrominoes() -> Self {
+        PieceSet::built_in("tetrominoes", &TETROMINOES)
+    }
+    pub fn pentominoes() -> Self {
+        PieceSet::built_in("pentominoes", &PENTOMINOES)
+    }
+    pub fn trominoes() -> Self {
+        PieceSet::built_in("trominoes", &TROMINOES)
+    }
+    fn built_in(name: &str, defs: &[(&str, Block, &[&str])]) -> Self {
+        PieceSet {
+            name: name.to_string(),
+            shapes: defs
+                .iter()
+                .map(|(n, block, rows)| {
+                    Shape::new(n, *block, rows, None).expect("built in pieces are valid")
+                })
+                .collect(),
+        }
+    }
+    // a built in set by name, or a set file
+    pub fn resolve(name_or_path: &str) -> Result<PieceSet, String> {
+        match PieceSet::by_name(name_or_path) {
+            Some(set) => Ok(set),
+            None => PieceSet::load(Path::new(name_or_path)),
+        }
+    }
+    // only the built in sets
+    pub fn by_name(name: &str) -> Option<PieceSet> {
+        match name {
+            "tetrominoes" => Some(PieceSet::tetrominoes()),
+            "pentominoes" => Some(PieceSet::pentominoes()),
+            "trominoes" => Some(PieceSet::trominoes()),
+            _ => None,
+        }
+    }
+    pub fn load(path: &Path) -> Result<PieceSet, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+    pub fn shape(&self, kind: usize) -> &Shape {
+        &self.shapes[kind]
+    }
+    // kinds from their names run together like "TIJLOSZ", spaces and commas are skipped and the
+    // longest name that fits is taken so "F'" isn't read as "F"
+    pub fn parse_queue(&self, text: &str) -> Result<Vec<usize>, String> {
+        let separator = |c: char| c.is_whitespace() || c == ',';
+        let mut kinds = Vec::new();
+        let mut rest = text.trim_start_matches(separator);
+        while !rest.is_empty() {
+            let (kind, name) = self
+                .shapes
+                .iter()
+                .enumerate()
+                .map(|(kind, shape)| (kind, &shape.name))
+                .filter(|(_, name)| {
+                    rest.get(..name.len())
+                        .is_some_and(|start| start.eq_ignore_ascii_case(name))
+                })
+                .max_by_key(|(_, name)| name.len())
+                .ok_or_else(|| format!("{} has no piece at `{}`", self.name, rest))?;
+            // a nameless piece matches anywhere without consuming anything
+            if name.is_empty() {
+                return Err(format!("{} has no piece at `{}`", self.name, rest));
+            }
+            kinds.push(kind);
+            rest = rest[name.len()..].trim_start_matches(separator);
+        }
+        Ok(kinds)
+    }
+    // the narrowest board the set can be played on
+    pub fn min_board_width(&self) -> usize {
+        self.shapes.iter().map(|s| s.width()).max().unwrap_or(0)
+    }
+    // how many tiles across and down a preview needs to show any piece of the set
+    pub fn preview_size(&self) -> (usize, usize) {
+        let extents = self.shapes.iter().map(|s| s.extent());
+        (
+            extents.clone().map(|e| e.0).max().unwrap_or(0),
+            extents.map(|e| e.1).max().unwrap_or(0),
+        )
+    }
+}
+
+// set file form:
+//
+// name = "crosses"
+// [[piece]]
+// name = "X"
+// color = "orange" # or "#ff8800"
+// rows = [".#.", "###", ".#."]
+// center = [1, 1] # optional
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PieceSetFile {
+    name: String,
+    piece: Vec<ShapeDef>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShapeDef {
+    name: String,
+    color: String,
+    rows: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    center: Option<(f32, f32)>,
+}
+
+impl TryFrom<PieceSetFile> for PieceSet {
+    type Error = String;
+    fn try_from(file: PieceSetFile) -> Result<Self, Self::Error> {
+        if file.piece.is_empty() {
+            return Err(format!("piece set {} has no pieces", file.name));
+        }
+        let mut shapes: Vec<Shape> = Vec::new();
+        for def in file.piece.iter() {
+            // queues are typed by name, so every piece needs one and it must be unambiguous
+            if def.name.is_empty() {
+                return Err(format!("piece set {} has a piece with no name", file.name));
+            }
+            if shapes
+                .iter()
+                .any(|s| s.name.eq_ignore_ascii_case(&def.name))
+            {
+                return Err(format!(
+                    "piece set {} has two pieces named {}",
+                    file.name, def.name
+                ));
+            }
+            let block = parse_color(&def.color)
+                .ok_or_else(|| format!("unknown color `{}` for {}", def.color, def.name))?;
+            let rows: Vec<&str> = def.rows.iter().map(|r| r.as_str()).collect();
+            shapes.push(Shape::new(&def.name, block, &rows, def.center)?);
+        }
+        Ok(PieceSet {
+            name: file.name,
+            shapes,
+        })
+    }
+}
+
+impl From<PieceSet> for PieceSetFile {
+    fn from(set: PieceSet) -> Self {
+        PieceSetFile {
+            name: set.name,
+            piece: set
+                .shapes
+                .into_iter()
+                .map(|s| ShapeDef {
+                    center: match s.center_given {
+                        true => Some((s.center2.0 as f32 / 2.0, s.center2.1 as f32 / 2.0)),
+                        false => None,
+                    },
+                    name: s.name,
+                    color: color_name(s.block),
+                    rows: s.rows,
+                })
+                .collect(),
+        }
+    }
+}
+
+const NAMED_COLORS: [(&str, Block); 9] = [
+    ("red", Block::Red),
+    ("green", Block::Green),
+    ("blue", Block::Blue),
+    ("magenta", Block::Magenta),
+    ("yellow", Block::Yellow),
+    ("cyan", Block::Cyan),
+    ("white", Block::White),
+    ("orange", Block::Orange),
+    ("gray", Block::Gray),
+];
+
+// a color name from the list above or "#rrggbb"
+pub fn parse_color(name: &str) -> Option<Block> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Block::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    NAMED_COLORS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, b)| *b)
+}
+pub fn color_name(block: Block) -> String {
+    match block {
+        Block::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        _ => NAMED_COLORS
+            .iter()
+            .find(|(_, b)| *b == block)
+            .map(|(n, _)| n.to_string())
+            .unwrap_or_else(|| "gray".to_string()),
+    }
+}
+
+// in the order the old random piece picker used, the leading empty row of I and O keeps them
+// spawning at the same height as before
+const TETROMINOES: [(&str, Block, &[&str]); 7] = [
+    ("I", Block::Cyan, &["....", "####"]),
+    ("O", Block::Yellow, &["....", ".##.", ".##."]),
+    ("J", Block::Blue, &["#..", "###"]),
+    ("L", Block::Orange, &["..#", "###"]),
+    ("S", Block::Green, &[".##", "##."]),
+    ("Z", Block::Red, &["##.", ".##"]),
+    ("T", Block::Magenta, &[".#.", "###"]),
+];
+
+// the 18 one-sided pentominoes, mirror images are primed
+const PENTOMINOES: [(&str, Block, &[&str]); 18] = [
+    ("F", Block::Rgb(0x9b, 0x59, 0xb6), &[".##", "##.", ".#."]),
+    ("F'", Block::Rgb(0x6c, 0x3a, 0x83), &["##.", ".##", ".#."]),
+    ("I", Block::Cyan, &["#####"]),
+    ("L", Block::Orange, &["...#", "####"]),
+    ("J", Block::Blue, &["#...", "####"]),
+    ("N", Block::Rgb(0x2e, 0x8b, 0x57), &["##..", ".###"]),
+    ("N'", Block::Rgb(0x3c, 0xb3, 0x71), &["..##", "###."]),
+    ("P", Block::Rgb(0xff, 0x69, 0xb4), &[".##", "###"]),
+    ("P'", Block::Rgb(0xc7, 0x15, 0x85), &["##.", "###"]),
+    ("T", Block::Magenta, &["###", ".#.", ".#."]),
+    ("U", Block::Yellow, &["#.#", "###"]),
+    ("V", Block::Rgb(0x46, 0x82, 0xb4), &["#..", "#..", "###"]),
+    ("W", Block::Rgb(0xd2, 0x69, 0x1e), &["#..", "##.", ".##"]),
+    ("X", Block::White, &[".#.", "###", ".#."]),
+    ("Y", Block::Rgb(0xda, 0xa5, 0x20), &["..#.", "####"]),
+    ("Y'", Block::Rgb(0xb8, 0x86, 0x0b), &[".#..", "####"]),
+    ("Z", Block::Red, &["##.", ".#.", ".##"]),
+    ("S", Block::Green, &[".##", ".#.", "##."]),
+];
+
+const TROMINOES: [(&str, Block, &[&str]); 2] = [
+    ("I", Block::Cyan, &["###"]),
+    ("L", Block::Orange, &["#.", "##"]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::Piece;
+
+    #[test]
+    fn built_in_sets_load() {
+        for name in [
+            "tetrominoes",
+            "pentominoes",
+            "trominoes",
+        ] {
+            let set = PieceSet::by_name(name).unwrap();
+            assert!(!set.is_empty());
+        }
+        assert_eq!(PieceSet::default().shape(6).center2(), (2, 2));
+    }
+
+    #[test]
+    fn centers_outside_the_rows_are_rejected() {
+        let rows = [".#.", "###"];
+        assert!(Shape::new("T", Block::Magenta, &rows, Some((1.0, 1.0))).is_ok());
+        assert!(Shape::new("T", Block::Magenta, &rows, Some((2.0, 1.0))).is_ok());
+        assert!(Shape::new("T", Block::Magenta, &rows, Some((3.0, 1.0))).is_err());
+        assert!(Shape::new("T", Block::Magenta, &rows, Some((1.0, 2.0))).is_err());
+        assert!(Shape::new("T", Block::Magenta, &rows, Some((100.0, 100.0))).is_err());
+        assert!(Shape::new("T", Block::Magenta, &rows, Some((-1.0, 0.0))).is_err());
+        assert!(Shape::new("T", Block::Magenta, &rows, Some((0.5, 1.0))).is_err());
+    }
+
+    #[test]
+    fn four_quarter_turns_come_back() {
+        let set = PieceSet::pentominoes();
+        for kind in 0..set.len() {
+            let mut piece = Piece::new(&set, kind);
+            let spawn: Vec<_> = piece.cells().collect();
+            piece.rotate_right();
+            piece.rotate_left();
+            assert_eq!(piece.cells().collect::<Vec<_>>(), spawn);
+            for _ in 0..4 {
+                piece.rotate_right();
+            }
+            assert_eq!(piece.cells().collect::<Vec<_>>(), spawn);
+        }
+    }
+
+    #[test]
+    fn sets_save_back_the_same() {
+        let set = PieceSet::try_from(PieceSetFile {
+            name: "crosses".to_string(),
+            piece: vec![ShapeDef {
+                name: "X".to_string(),
+                color: "#ff8800".to_string(),
+                rows: vec![
+                    ".#.".to_string(),
+                    "###".to_string(),
+                    ".#.".to_string(),
+                ],
+                center: Some((1.0, 1.0)),
+            }],
+        })
+        .unwrap();
+        let file = PieceSetFile::from(set.clone());
+        assert_eq!(file.piece[0].center, Some((1.0, 1.0)));
+        assert_eq!(file.piece[0].color, "#ff8800");
+        assert_eq!(PieceSet::try_from(file).unwrap(), set);
+    }
+
+    #[test]
+    fn nameless_pieces_are_rejected() {
+        let mut file = PieceSetFile::from(PieceSet::default());
+        file.piece[3].name = String::new();
+        assert!(PieceSet::try_from(file).is_err());
+    }
+
+    #[test]
+    fn names_must_differ_by_more_than_case() {
+        let mut file = PieceSetFile::from(PieceSet::default());
+        file.piece[3].name = "t".to_string();
+        assert!(PieceSet::try_from(file).is_err());
+    }
+
+    #[test]
+    fn queues_take_the_longest_name() {
+        let set = PieceSet::pentominoes();
+        assert_eq!(set.parse_queue("f'F, i").unwrap(), vec![1, 0, 2]);
+        assert_eq!(
+            PieceSet::default().parse_queue("tij lo").unwrap(),
+            vec![6, 0, 2, 3, 1]
+        );
+        assert!(PieceSet::default().parse_queue("TQ").is_err());
+    }
+
+    #[test]
+    fn a_nameless_piece_matches_nothing() {
+        let mut set = PieceSet::default();
+        set.shapes[3].name = String::new();
+        assert!(set.parse_queue("TQ").is_err());
+    }
+}
@@ -0,0 +1,254 @@
+// starting positions built in the board editor and played from in practice, saved by name as toml
+// files in the positions dir:
+//
+//   queue = "TIJ"          the piece in play first, then the ones after it
+//   hold = "L"             empty for nothing held
+//   rows = ["..........", "IIII.....#"]
+//
+// rows are the visible rows from the top with a char per cell, `.` for nothing, `#` for garbage and
+// otherwise the piece whose color the cell has, by its name when that's a single char (see
+// `cell_codes`). the piece set the names refer to is saved along with them
+use crate::config;
+use crate::piece_set::PieceSet;
+use crate::save;
+use crate::tile::Block;
+use crate::tile::Tile;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const EMPTY: char = '.';
+const GARBAGE: char = '#';
+// for pieces without a name of their own that fits in a cell
+const SPARE_CODES: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Position {
+    #[serde(default)]
+    pub queue: String,
+    #[serde(default)]
+    pub hold: String,
+    pub rows: Vec<String>,
+    pub pieces: PieceSet,
+}
+
+impl Position {
+    pub fn from_tiles(pieces: &PieceSet, tiles: &[Vec<Tile>], queue: &str, hold: &str) -> Self {
+        let codes = cell_codes(pieces);
+        let code = |tile: &Tile| match tile {
+            None => EMPTY,
+            Some(block) => (0..pieces.len())
+                .find(|kind| pieces.shape(*kind).block == *block)
+                .map(|kind| codes[kind])
+                .unwrap_or(GARBAGE),
+        };
+        Position {
+            queue: queue.trim().to_string(),
+            hold: hold.trim().to_string(),
+            rows: tiles
+                .iter()
+                .map(|row| row.iter().map(code).collect())
+                .collect(),
+            pieces: pieces.clone(),
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.rows.first().map(|r| r.chars().count()).unwrap_or(0)
+    }
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+    pub fn tiles(&self) -> Result<Vec<Vec<Tile>>, String> {
+        let codes = cell_codes(&self.pieces);
+        let tile = |c: char| match c {
+            EMPTY => Ok(None),
+            GARBAGE => Ok(Some(Block::Garbage)),
+            _ => codes
+                .iter()
+                .position(|code| *code == c)
+                .map(|kind| Some(self.pieces.shape(kind).block))
+                .ok_or_else(|| format!("`{}` isn't a cell of {}", c, self.pieces.name)),
+        };
+        self.rows
+            .iter()
+            .map(|row| row.chars().map(tile).collect())
+            .collect()
+    }
+    // the piece in play and the ones after it
+    pub fn queue_kinds(&self) -> Result<Vec<usize>, String> {
+        self.pieces.parse_queue(&self.queue)
+    }
+    pub fn held_kind(&self) -> Result<Option<usize>, String> {
+        match self.pieces.parse_queue(&self.hold)?.as_slice() {
+            [] => Ok(None),
+            [kind] => Ok(Some(*kind)),
+            _ => Err("only one piece can be held".to_string()),
+        }
+    }
+    pub fn parse(content: &str) -> Result<Position, String> {
+        let position: Position = toml::from_str(content).map_err(|e| e.to_string())?;
+        position.validate()?;
+        Ok(position)
+    }
+    fn validate(&self) -> Result<(), String> {
+        config::validate_board_size(self.width(), self.height())?;
+        if self.rows.iter().any(|r| r.chars().count() != self.width()) {
+            return Err("the rows aren't all the same width".to_string());
+        }
+        if self.width() < self.pieces.min_board_width() {
+            return Err(format!(
+                "the {} piece set doesn't fit a board {} wide",
+                self.pieces.name,
+                self.width()
+            ));
+        }
+        self.tiles()?;
+        self.queue_kinds()?;
+        self.held_kind()?;
+        Ok(())
+    }
+    pub fn load(name: &str) -> Result<Position, String> {
+        let path = path(name).map_err(|e| e.to_string())?;
+        let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", name, e))?;
+        Position::parse(&content).map_err(|e| format!("{}: {}", name, e))
+    }
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        self.validate()?;
+        check_name(name)?;
+        let content = toml::to_string(self).map_err(|e| e.to_string())?;
+        let path = path(name).map_err(|e| e.to_string())?;
+        save::write_atomic(&path, &content).map_err(|e| format!("{}: {}", name, e))
+    }
+}
+
+// the names of the saved positions, sorted
+pub fn list() -> io::Result<Vec<String>> {
+    let dir = save::positions_dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            name.strip_suffix(".toml").map(|n| n.to_string())
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn path(name: &str) -> io::Result<PathBuf> {
+    Ok(save::positions_dir()?.join(format!("{}.toml", name)))
+}
+
+// names become file names, so they're kept to what's safe in one
+fn check_name(name: &str) -> Result<(), String> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ' ';
+    match name.is_empty() || !name.chars().all(allowed) || name.starts_with(' ') {
+        true => Err(format!(
+            "`{}` can't be a name, use letters, digits, spaces, - and _",
+            name
+        )),
+        false => Ok(()),
+    }
+}
+
+// the char each kind's cells are written with, its name when that's a single char no other kind
+// or cell uses and a spare one when it isn't
+fn cell_codes(pieces: &PieceSet) -> Vec<char> {
+    let own: Vec<Option<char>> = (0..pieces.len())
+        .map(|kind| {
+            let mut name = pieces.shape(kind).name.chars();
+            match (name.next(), name.next()) {
+                (Some(c), None) if c != EMPTY && c != GARBAGE => Some(c),
+                _ => None,
+            }
+        })
+        .collect();
+    let unique = |c: char| own.iter().filter(|o| **o == Some(c)).count() == 1;
+    let mut spare = SPARE_CODES.chars().filter(|c| !own.contains(&Some(*c)));
+    own.iter()
+        .map(|o| match o {
+            Some(c) if unique(*c) => *c,
+            _ => spare.next().unwrap_or(GARBAGE),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an I lying on garbage with a gap, 10 by 20
+    fn position(pieces: &PieceSet) -> Position {
+        let mut tiles = vec![vec![None; 10]; 20];
+        tiles[19] = vec![Some(Block::Garbage); 10];
+        tiles[19][4] = None;
+        tiles[18][..4].fill(Some(pieces.shape(0).block));
+        Position::from_tiles(pieces, &tiles, " TSZ ", "L")
+    }
+
+    fn parsed(position: &Position) -> Result<Position, String> {
+        Position::parse(&toml::to_string(position).unwrap())
+    }
+
+    #[test]
+    fn positions_read_back_the_same() {
+        let pieces = PieceSet::tetrominoes();
+        let position = position(&pieces);
+        assert_eq!(position.queue, "TSZ");
+        assert_eq!(position.rows[18], "IIII......");
+        assert_eq!(position.rows[19], "####.#####");
+        assert_eq!(parsed(&position).unwrap(), position);
+        assert_eq!(position.queue_kinds().unwrap(), vec![6, 4, 5]);
+        assert_eq!(position.held_kind().unwrap(), Some(3));
+    }
+
+    #[test]
+    fn names_that_dont_fit_a_cell_get_spare_codes() {
+        let pieces = PieceSet::pentominoes();
+        let codes = cell_codes(&pieces);
+        // F, F', I, L, J, N, N', P, P' and T
+        assert_eq!(codes[..10].iter().collect::<String>(), "FAILJNBPCT");
+        let mut tiles = vec![vec![None; 10]; 20];
+        for (kind, tile) in tiles[19].iter_mut().enumerate() {
+            *tile = Some(pieces.shape(kind).block);
+        }
+        let position = Position::from_tiles(&pieces, &tiles, "F' X", "");
+        assert_eq!(position.rows[19], "FAILJNBPCT");
+        assert_eq!(parsed(&position).unwrap().tiles().unwrap(), tiles);
+    }
+
+    #[test]
+    fn broken_positions_are_refused() {
+        let pieces = PieceSet::tetrominoes();
+        let mut uneven = position(&pieces);
+        uneven.rows[3].push('.');
+        assert!(parsed(&uneven).is_err());
+        let mut unknown = position(&pieces);
+        unknown.rows[0] = "....X.....".to_string();
+        assert!(parsed(&unknown).is_err());
+        let mut two_held = position(&pieces);
+        two_held.hold = "LJ".to_string();
+        assert!(parsed(&two_held).is_err());
+        let mut short = position(&pieces);
+        short.rows.truncate(2);
+        assert!(parsed(&short).is_err());
+    }
+
+    #[test]
+    fn names_are_kept_to_safe_file_names() {
+        assert!(check_name("tki 3-opener_2").is_ok());
+        for name in [
+            "", " lead", "../up", "a/b", "dot.toml",
+        ] {
+            assert!(check_name(name).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use crate::config;
+use crate::engine::{Action, Command, Game, GameSettings, Mode, Timing};
+use crate::garbage;
+use crate::garbage::GarbageStyle;
+use crate::piece_set::PieceSet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+// bumped whenever the engine changes how the same inputs play out
+pub const REPLAY_VERSION: u32 = 5;
+
+// the result of a game, what a score record claims and what re-simulating a replay produces
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    pub score: u32,
+    pub lines: u32,
+    pub level: u8,
+    pub time_ms: u64,
+}
+
+impl Summary {
+    pub fn of(game: &Game) -> Self {
+        Self {
+            score: game.score(),
+            lines: game.lines(),
+            level: game.level(),
+            time_ms: game.clock_ms(),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.time_ms / 1000;
+        write!(
+            f,
+            "score {}, {} lines, level {} in {:02}:{:02}.{:03}",
+            self.score,
+            self.lines,
+            self.level,
+            seconds / 60,
+            seconds % 60,
+            self.time_ms % 1000
+        )
+    }
+}
+
+// one input at a point on the game clock, stored as "<ms> <action>" or "<ms> garbage <lines>" to
+// keep replay files compact
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Input {
+    pub ms: u64,
+    pub command: Command,
+}
+
+impl From<Input> for String {
+    fn from(input: Input) -> String {
+        match input.command {
+            Command::Act(action) => format!("{} {}", input.ms, action.name()),
+            Command::Garbage(lines) => format!("{} garbage {}", input.ms, lines),
+        }
+    }
+}
+
+impl TryFrom<String> for Input {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let (ms, command) = s
+            .split_once(' ')
+            .ok_or_else(|| format!("bad input `{}`, expected `<ms> <action>`", s))?;
+        let command = match command.split_once(' ') {
+            Some(("garbage", lines)) => Command::Garbage(
+                garbage::parse_attack(lines)
+                    .ok_or_else(|| format!("bad garbage lines in input `{}`", s))?,
+            ),
+            _ => Command::Act(
+                Action::from_name(command)
+                    .ok_or_else(|| format!("unknown action in input `{}`", s))?,
+            ),
+        };
+        Ok(Input {
+            ms: ms
+                .parse()
+                .map_err(|_| format!("bad time in input `{}`", s))?,
+            command,
+        })
+    }
+}
+
+// everything needed to play a game again through the engine, plus the result it claims. other
+// versions are refused rather than migrated since the same inputs don't play out the same on this
+// engine, so every field is required
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Replay {
+    pub version: u32,
+    pub mode: Mode,
+    pub seed: u64,
+    pub starting_level: u8,
+    pub timing: Timing,
+    pub width: usize,
+    pub height: usize,
+    pub pieces: PieceSet,
+    pub garbage_style: GarbageStyle,
+    pub inputs: Vec<Input>,
+    pub summary: Summary,
+}
+
+impl Replay {
+    pub fn from_game(game: &Game, inputs: Vec<Input>) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            mode: game.mode(),
+            seed: game.seed(),
+            starting_level: game.starting_level(),
+            timing: game.settings().timing,
+            width: game.width(),
+            height: game.height(),
+            pieces: game.piece_set().clone(),
+            garbage_style: game.settings().garbage_style,
+            inputs,
+            summary: Summary::of(game),
+        }
+    }
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+    pub fn parse(content: &str) -> Result<Replay, String> {
+        let replay: Replay = toml::from_str(content).map_err(|e| e.to_string())?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!("unsupported replay version {}", replay.version));
+        }
+        if !(config::MIN_STARTING_LEVEL..=config::MAX_STARTING_LEVEL)
+            .contains(&replay.starting_level)
+        {
+            return Err(format!("invalid starting level {}", replay.starting_level));
+        }
+        config::validate_board_size(replay.width, replay.height)?;
+        if replay.width < replay.pieces.min_board_width() {
+            return Err(format!(
+                "the {} piece set doesn't fit a board {} wide",
+                replay.pieces.name, replay.width
+            ));
+        }
+        Ok(replay)
+    }
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Replay::parse(&content)
+    }
+
+    pub fn settings(&self) -> GameSettings {
+        GameSettings {
+            mode: self.mode,
+            starting_level: self.starting_level,
+            seed: self.seed,
+            timing: self.timing,
+            width: self.width,
+            height: self.height,
+            pieces: Arc::new(self.pieces.clone()),
+            garbage_style: self.garbage_style,
+        }
+    }
+
+    // plays the inputs through a fresh engine and reports how the game really ended
+    pub fn simulate(&self) -> Result<Summary, String> {
+        let mut game = Game::new(self.settings());
+        let mut last_ms = 0;
+        for (n, input) in self.inputs.iter().enumerate() {
+            if input.ms < last_ms {
+                return Err(format!("input {} goes back in time", n + 1));
+            }
+            last_ms = input.ms;
+            game.advance_to(input.ms);
+            if game.is_over() {
+                return Err(format!("input {} comes after the game ended", n + 1));
+            }
+            game.handle(input.command);
+        }
+        game.advance_to(self.summary.time_ms);
+        if !game.is_over() {
+            return Err(format!(
+                "the game is still running at the claimed end ({})",
+                self.summary
+            ));
+        }
+        Ok(Summary::of(&game))
+    }
+
+    // checks that the claimed summary is what the inputs actually produce
+    pub fn verify(&self) -> Result<Summary, String> {
+        let actual = self.simulate()?;
+        if actual != self.summary {
+            return Err(format!(
+                "replay claims {} but plays out as {}",
+                self.summary, actual
+            ));
+        }
+        Ok(actual)
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+
+    // a marathon game of hard drops every 300ms with some garbage, played until it tops out
+    fn played_out() -> Replay {
+        let mut game = Game::new(GameSettings {
+            mode: Mode::Marathon,
+            starting_level: 1,
+            seed: 7,
+            timing: Mode::Marathon.timing(),
+            width: board::BOARD_WIDTH,
+            height: board::BOARD_HEIGHT,
+            pieces: Arc::new(PieceSet::default()),
+            garbage_style: GarbageStyle::Clean,
+        });
+        let mut inputs = Vec::new();
+        let mut ms = 0;
+        while !game.is_over() {
+            ms += 300;
+            game.advance_to(ms);
+            if game.is_over() {
+                break;
+            }
+            let command = match inputs.len() % 5 {
+                4 => Command::Garbage(2),
+                _ => Command::Act(Action::HardDrop),
+            };
+            game.handle(command);
+            inputs.push(Input { ms, command });
+        }
+        Replay::from_game(&game, inputs)
+    }
+
+    #[test]
+    fn saved_replays_verify() {
+        let replay = Replay::parse(&played_out().to_toml().unwrap()).unwrap();
+        assert_eq!(replay.verify().unwrap(), replay.summary);
+    }
+
+    #[test]
+    fn a_wrong_summary_is_caught() {
+        let mut replay = played_out();
+        replay.summary.score += 100;
+        assert!(replay.verify().is_err());
+    }
+
+    #[test]
+    fn other_versions_and_missing_fields_are_refused() {
+        let content = played_out().to_toml().unwrap();
+        let old = content.replacen(&format!("version = {}", REPLAY_VERSION), "version = 4", 1);
+        assert!(Replay::parse(&old).is_err());
+        let without_width: String = content
+            .lines()
+            .filter(|l| !l.starts_with("width ="))
+            .map(|l| format!("{}\n", l))
+            .collect();
+        assert!(Replay::parse(&without_width).is_err());
+    }
+
+    #[test]
+    fn inputs_read_back() {
+        for text in [
+            "120 hard_drop",
+            "4000 garbage 3",
+            "0 rotate_180",
+        ] {
+            let input = Input::try_from(text.to_string()).unwrap();
+            assert_eq!(String::from(input), text);
+        }
+        assert!(Input::try_from("12 fly".to_string()).is_err());
+        assert!(Input::try_from("4000 garbage 4294967295".to_string()).is_err());
+        assert!(Input::try_from("soon hard_drop".to_string()).is_err());
+    }
+}
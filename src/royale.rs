@@ -0,0 +1,1070 @@
+// battle royale, up to 16 players on a server that plays every game itself
+//
+// `tetrs server` runs without a ui. players connect with `tetrs battle`, say `tetrs <version>` as
+// in a networked versus game and then `enter <name>`. everyone waits in the lobby until at least
+// two players are there and all of them are ready, then the same game starts for all of them.
+// each player plays on their own board and sends the server everything it records, which the
+// server plays through an engine of its own per player. attacks, knock outs and placements are
+// decided there and never taken from a client. from a player to the server:
+//
+//   enter <name>                joins the lobby, names are letters, digits, `-` and `_`
+//   ready <0|1>                 ready to start, or not anymore
+//   target <strategy>           random, attackers, kos or badges
+//   input <ms> <input>          an input the way replays store it, including `garbage <lines>` for
+//                               attack taken in from the server
+//   clock <ms>                  the game clock moved on without any input
+//   bye
+//
+// and from the server to players:
+//
+//   lobby <you> <entries>       everyone connected as `<id>:<name>:<waiting|ready|playing>`
+//                               separated by commas, sent whenever it changes and right before a
+//                               game starts
+//   start ...                   a game begins for everyone playing, as in versus
+//   garbage <lines>             attack sent to this player, to be echoed back as an input
+//   frame / delta <id> ...      the other players' boards, as sent to versus spectators
+//   aim <target> <attackers>    who this player attacks and who attacks them, 0 and `-` for none
+//   ko <id> <place> <by>        a player was knocked out by the last one to attack them, 0 if
+//                               nobody did
+//   result <id>                 the winner, 0 when the last players went out together, then
+//                               everyone is back in the lobby
+//   bye                         the server is full or stopping
+use crate::board::Board;
+use crate::engine::Command;
+use crate::engine::Game;
+use crate::net::Connection;
+use crate::net::HANDSHAKE_TIMEOUT;
+use crate::net::MatchSettings;
+use crate::net::Message;
+use crate::net::Snapshot;
+use crate::replay::Input;
+use crate::tile::Block;
+use crate::tile::Tile;
+use cursive::Printer;
+use cursive::View;
+use cursive::theme::BaseColor;
+use cursive::theme::Color;
+use cursive::theme::ColorStyle;
+use rand::seq::IndexedRandom;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+pub const MAX_PLAYERS: usize = 16;
+pub const DEFAULT_MAX_PLAYERS: usize = 8;
+const MAX_NAME_LEN: usize = 16;
+// how often the server takes in inputs and sends out boards
+const STEP: Duration = Duration::from_millis(10);
+// how often a player reports their clock while nothing is pressed
+const CLOCK_INTERVAL_MS: u64 = 100;
+
+// who a player's attacks go to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Targeting {
+    #[default]
+    Random, // someone else for every attack
+    Attackers, // everyone attacking this player, all at once
+    Kos,       // whoever is closest to topping out
+    Badges,    // whoever has the most badges
+}
+
+impl Targeting {
+    pub const ALL: [Targeting; 4] = [
+        Targeting::Random,
+        Targeting::Attackers,
+        Targeting::Kos,
+        Targeting::Badges,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Targeting::Random => "random",
+            Targeting::Attackers => "attackers",
+            Targeting::Kos => "kos",
+            Targeting::Badges => "badges",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Targeting> {
+        Targeting::ALL.into_iter().find(|t| t.name() == name)
+    }
+    pub fn title(&self) -> &'static str {
+        match self {
+            Targeting::Random => "Random",
+            Targeting::Attackers => "Attackers",
+            Targeting::Kos => "KOs",
+            Targeting::Badges => "Badges",
+        }
+    }
+    // the one after this, back to the first after the last
+    pub fn next(&self) -> Targeting {
+        let idx = Targeting::ALL.iter().position(|t| t == self).unwrap_or(0);
+        Targeting::ALL[(idx + 1) % Targeting::ALL.len()]
+    }
+}
+
+// a knock out is worth a badge plus every badge the knocked out player had, and badges make attacks
+// stronger
+pub fn badge_bonus_percent(badges: u32) -> u32 {
+    match badges {
+        0..=1 => 0,
+        2..=5 => 25,
+        6..=13 => 50,
+        14..=29 => 75,
+        _ => 100,
+    }
+}
+
+pub fn attack_with_badges(lines: u32, badges: u32) -> u32 {
+    lines + lines * badge_bonus_percent(badges) / 100
+}
+
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// where someone in the lobby is at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Seat {
+    Waiting,
+    Ready,
+    Playing,
+}
+
+impl Seat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Seat::Waiting => "waiting",
+            Seat::Ready => "ready",
+            Seat::Playing => "playing",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Seat> {
+        [
+            Seat::Waiting,
+            Seat::Ready,
+            Seat::Playing,
+        ]
+        .into_iter()
+        .find(|s| s.name() == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LobbyEntry {
+    pub id: usize,
+    pub name: String,
+    pub seat: Seat,
+}
+
+impl LobbyEntry {
+    pub(crate) fn parse(entry: &str) -> Option<LobbyEntry> {
+        let mut fields = entry.split(':');
+        let (Some(id), Some(name), Some(seat), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return None;
+        };
+        if !valid_name(name) {
+            return None;
+        }
+        Some(LobbyEntry {
+            id: id.parse().ok()?,
+            name: name.to_string(),
+            seat: Seat::from_name(seat)?,
+        })
+    }
+}
+
+impl fmt::Display for LobbyEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.id, self.name, self.seat.name())
+    }
+}
+
+struct Player {
+    id: usize,
+    name: String,
+    conn: Connection,
+    ready: bool,
+    targeting: Targeting,
+    left: bool, // the connection is gone, dropped at the end of the step
+    round: Option<Round>,
+}
+
+// a player's part in the game going on
+struct Round {
+    game: Game,
+    out: bool,
+    unacked: u32, // attack sent that the player hasn't echoed back yet
+    target: Option<usize>,
+    badges: u32,
+    last_attacker: Option<usize>,
+    seen: Option<Snapshot>,           // the board as the others last saw it
+    aim: Option<(usize, Vec<usize>)>, // as the player was last told
+}
+
+impl Round {
+    fn new(game: Game) -> Self {
+        Round {
+            game,
+            out: false,
+            unacked: 0,
+            target: None,
+            badges: 0,
+            last_attacker: None,
+            seen: None,
+            aim: None,
+        }
+    }
+    // how close the player is to topping out, the stack plus what's about to come up under it
+    fn danger(&self) -> usize {
+        let tiles = self.game.tiles();
+        let stack = tiles
+            .iter()
+            .position(|row| row.iter().any(|t| t.is_some()))
+            .map(|top| tiles.len() - top)
+            .unwrap_or(0);
+        stack + self.game.pending_garbage() as usize
+    }
+}
+
+// everyone connected and the game they're playing, stepped by serve
+struct Server {
+    settings: MatchSettings, // a new seed is picked for every game
+    max_players: usize,
+    players: Vec<Player>,
+    next_id: usize,
+    playing: bool,
+}
+
+// runs the server until the process is stopped
+pub fn serve(listener: TcpListener, settings: MatchSettings, max_players: usize) -> ! {
+    let (entrants_tx, entrants) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let entrants_tx = entrants_tx.clone();
+            // a client that takes its time doesn't keep anyone else waiting
+            thread::spawn(move || greet(stream, &entrants_tx));
+        }
+    });
+    let mut server = Server::new(settings, max_players);
+    loop {
+        while let Ok((conn, name)) = entrants.try_recv() {
+            server.add(conn, name);
+        }
+        server.step();
+        thread::sleep(STEP);
+    }
+}
+
+fn greet(stream: TcpStream, entrants: &Sender<(Connection, String)>) {
+    let Ok(mut conn) = Connection::new(stream) else {
+        return;
+    };
+    if conn.handshake().is_err() {
+        return;
+    }
+    if let Ok(Message::Enter(name)) = conn.recv_timeout(HANDSHAKE_TIMEOUT) {
+        let _ = entrants.send((conn, name));
+    }
+}
+
+impl Server {
+    fn new(settings: MatchSettings, max_players: usize) -> Self {
+        Server {
+            settings,
+            max_players,
+            players: Vec::new(),
+            next_id: 1,
+            playing: false,
+        }
+    }
+    fn add(&mut self, mut conn: Connection, name: String) {
+        if self.players.len() >= self.max_players {
+            println!("tetrs: turned {} away, the server is full", name);
+            let _ = conn.send(&Message::Bye);
+            return;
+        }
+        println!("tetrs: {} joined", name);
+        self.players.push(Player {
+            id: self.next_id,
+            name,
+            conn,
+            ready: false,
+            targeting: Targeting::default(),
+            left: false,
+            round: None,
+        });
+        self.next_id += 1;
+        self.send_lobby();
+    }
+
+    // takes in everything the players sent and moves the game along
+    fn step(&mut self) {
+        let mut lobby_changed = false;
+        for i in 0..self.players.len() {
+            while let Some(message) = self.players[i].conn.try_recv() {
+                match message {
+                    Message::Ready(ready) if !self.playing => {
+                        self.players[i].ready = ready;
+                        lobby_changed = true;
+                    }
+                    Message::Target(targeting) => self.players[i].targeting = targeting,
+                    Message::Input(input) => self.take_input(i, input),
+                    Message::Clock(ms) => {
+                        if let Some(round) = self.players[i].round.as_mut() {
+                            round.game.advance_to(ms);
+                        }
+                    }
+                    Message::Bye => {
+                        self.players[i].left = true;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if self.playing {
+            lobby_changed |= self.play_step();
+        }
+        for player in self.players.iter().filter(|p| p.left) {
+            println!("tetrs: {} left", player.name);
+            lobby_changed = true;
+        }
+        self.players.retain(|p| !p.left);
+        if !self.playing && self.players.len() >= 2 && self.players.iter().all(|p| p.ready) {
+            self.start();
+        } else if lobby_changed {
+            self.send_lobby();
+        }
+    }
+    // plays an input through the server's copy of the player's game, the same way a replay is
+    // simulated
+    fn take_input(&mut self, i: usize, input: Input) {
+        let player = &mut self.players[i];
+        let Some(round) = player.round.as_mut() else {
+            return;
+        };
+        if round.out || input.ms < round.game.clock_ms() {
+            return;
+        }
+        if let Command::Garbage(lines) = input.command {
+            // only attack that was actually sent can be taken in, anything else means the two
+            // games don't match anymore
+            if lines > round.unacked {
+                println!("tetrs: {} took in garbage that was never sent", player.name);
+                player.left = true;
+                return;
+            }
+            round.unacked -= lines;
+        }
+        round.game.advance_to(input.ms);
+        round.game.handle(input.command);
+    }
+
+    fn start(&mut self) {
+        self.settings.seed = rand::random();
+        self.playing = true;
+        for player in self.players.iter_mut() {
+            player.round = Some(Round::new(Game::new(self.settings.game_settings())));
+        }
+        println!("tetrs: starting a game with {} players", self.players.len());
+        // the lobby tells everyone who's playing before the game starts
+        self.send_lobby();
+        self.broadcast(&Message::Start(self.settings.clone()), None);
+    }
+    // returns true when the game ended
+    fn play_step(&mut self) -> bool {
+        for i in 0..self.players.len() {
+            if self.players[i].left && self.is_alive(i) {
+                self.knock_out(i);
+            }
+        }
+        for i in 0..self.players.len() {
+            if self.is_alive(i) {
+                self.retarget(i, false);
+            }
+        }
+        for i in 0..self.players.len() {
+            if !self.is_alive(i) {
+                continue;
+            }
+            let Some(round) = self.players[i].round.as_mut() else {
+                continue;
+            };
+            let lines = round.game.take_outgoing_garbage();
+            if lines > 0 {
+                let lines = attack_with_badges(lines, round.badges);
+                self.attack(i, lines);
+            }
+        }
+        for i in 0..self.players.len() {
+            let topped_out = self.players[i]
+                .round
+                .as_ref()
+                .is_some_and(|r| !r.out && r.game.is_over());
+            if topped_out {
+                self.knock_out(i);
+            }
+        }
+        self.send_boards();
+        self.send_aims();
+
+        let alive: Vec<usize> = (0..self.players.len())
+            .filter(|i| self.is_alive(*i))
+            .collect();
+        if alive.len() > 1 {
+            return false;
+        }
+        let winner = alive.first().map(|i| self.players[*i].id).unwrap_or(0);
+        match alive.first() {
+            Some(i) => println!("tetrs: {} won", self.players[*i].name),
+            None => println!("tetrs: nobody won"),
+        }
+        self.broadcast(&Message::Result(winner), None);
+        self.playing = false;
+        for player in self.players.iter_mut() {
+            player.round = None;
+            player.ready = false;
+        }
+        true
+    }
+
+    fn is_alive(&self, i: usize) -> bool {
+        self.players[i].round.as_ref().is_some_and(|r| !r.out)
+    }
+    fn index_of(&self, id: usize) -> Option<usize> {
+        self.players.iter().position(|p| p.id == id)
+    }
+    // picks who player i aims at, a random target is only picked again when fresh or when the old
+    // one is out
+    fn retarget(&mut self, i: usize, fresh: bool) {
+        let me = self.players[i].id;
+        let others: Vec<usize> = (0..self.players.len())
+            .filter(|j| *j != i && self.is_alive(*j))
+            .collect();
+        let current = self.players[i]
+            .round
+            .as_ref()
+            .and_then(|r| r.target)
+            .and_then(|id| self.index_of(id))
+            .filter(|j| others.contains(j));
+        let mut rng = rand::rng();
+        let random = |rng: &mut _| others.choose(rng).copied();
+        let target = match self.players[i].targeting {
+            Targeting::Random => match (fresh, current) {
+                (false, Some(j)) => Some(j),
+                _ => random(&mut rng),
+            },
+            Targeting::Attackers => others
+                .iter()
+                .copied()
+                .find(|j| self.players[*j].round.as_ref().and_then(|r| r.target) == Some(me))
+                .or(current)
+                .or_else(|| random(&mut rng)),
+            Targeting::Kos => others
+                .iter()
+                .copied()
+                .max_by_key(|j| self.players[*j].round.as_ref().map(|r| r.danger())),
+            Targeting::Badges => {
+                let most = others
+                    .iter()
+                    .copied()
+                    .max_by_key(|j| self.players[*j].round.as_ref().map(|r| r.badges));
+                match most.and_then(|j| self.players[j].round.as_ref()) {
+                    Some(round) if round.badges > 0 => most,
+                    _ => current.or_else(|| random(&mut rng)),
+                }
+            }
+        };
+        let target = target.map(|j| self.players[j].id);
+        if let Some(round) = self.players[i].round.as_mut() {
+            round.target = target;
+        }
+    }
+    // player i attacking with lines
+    fn attack(&mut self, i: usize, lines: u32) {
+        let me = self.players[i].id;
+        let targets: Vec<usize> = match self.players[i].targeting {
+            // everyone attacking this player gets it back in full
+            Targeting::Attackers => {
+                let attackers: Vec<usize> = (0..self.players.len())
+                    .filter(|j| {
+                        self.is_alive(*j)
+                            && self.players[*j].round.as_ref().and_then(|r| r.target) == Some(me)
+                    })
+                    .collect();
+                match attackers.is_empty() {
+                    true => self.current_target(i).into_iter().collect(),
+                    false => attackers,
+                }
+            }
+            Targeting::Random => {
+                self.retarget(i, true);
+                self.current_target(i).into_iter().collect()
+            }
+            Targeting::Kos | Targeting::Badges => self.current_target(i).into_iter().collect(),
+        };
+        for j in targets {
+            let target = &mut self.players[j];
+            if target.conn.send(&Message::Garbage(lines)).is_err() {
+                target.left = true;
+                continue;
+            }
+            if let Some(round) = target.round.as_mut() {
+                round.unacked += lines;
+                round.last_attacker = Some(me);
+            }
+        }
+    }
+    fn current_target(&self, i: usize) -> Option<usize> {
+        let id = self.players[i].round.as_ref()?.target?;
+        self.index_of(id).filter(|j| self.is_alive(*j))
+    }
+    fn knock_out(&mut self, i: usize) {
+        let place = (0..self.players.len())
+            .filter(|j| self.is_alive(*j))
+            .count();
+        let Some(round) = self.players[i].round.as_mut() else {
+            return;
+        };
+        round.out = true;
+        let badges = round.badges;
+        let by = round.last_attacker.unwrap_or(0);
+        let knocked_out_by = self
+            .index_of(by)
+            .filter(|k| self.is_alive(*k))
+            .and_then(|k| self.players[k].round.as_mut());
+        if let Some(round) = knocked_out_by {
+            round.badges += badges + 1;
+        }
+        println!(
+            "tetrs: {} was knocked out in place {}",
+            self.players[i].name, place
+        );
+        let id = self.players[i].id;
+        self.broadcast(
+            &Message::Ko {
+                player: id,
+                place,
+                by,
+            },
+            None,
+        );
+    }
+
+    // everyone sees every board but their own, whole the first time and only what changed after
+    fn send_boards(&mut self) {
+        for i in 0..self.players.len() {
+            let id = self.players[i].id;
+            let Some(round) = self.players[i].round.as_mut() else {
+                continue;
+            };
+            let board = Snapshot::of(&round.game);
+            let message = match &round.seen {
+                Some(old) if *old == board => continue,
+                Some(old) => Message::Delta(id, board.delta_from(old)),
+                None => Message::Frame(id, board.clone()),
+            };
+            round.seen = Some(board);
+            self.broadcast(&message, Some(i));
+        }
+    }
+    fn send_aims(&mut self) {
+        for i in 0..self.players.len() {
+            if !self.is_alive(i) {
+                continue;
+            }
+            let me = self.players[i].id;
+            let attackers: Vec<usize> = self
+                .players
+                .iter()
+                .filter(|p| {
+                    p.round
+                        .as_ref()
+                        .is_some_and(|r| !r.out && r.target == Some(me))
+                })
+                .map(|p| p.id)
+                .collect();
+            let player = &mut self.players[i];
+            let Some(round) = player.round.as_mut() else {
+                continue;
+            };
+            let aim = (round.target.unwrap_or(0), attackers);
+            if round.aim.as_ref() == Some(&aim) {
+                continue;
+            }
+            let message = Message::Aim {
+                target: aim.0,
+                attackers: aim.1.clone(),
+            };
+            round.aim = Some(aim);
+            if player.conn.send(&message).is_err() {
+                player.left = true;
+            }
+        }
+    }
+    fn send_lobby(&mut self) {
+        let entries: Vec<LobbyEntry> = self
+            .players
+            .iter()
+            .map(|p| LobbyEntry {
+                id: p.id,
+                name: p.name.clone(),
+                seat: match (p.round.is_some(), p.ready) {
+                    (true, _) => Seat::Playing,
+                    (false, true) => Seat::Ready,
+                    (false, false) => Seat::Waiting,
+                },
+            })
+            .collect();
+        for player in self.players.iter_mut() {
+            if player
+                .conn
+                .send(&Message::Lobby(player.id, entries.clone()))
+                .is_err()
+            {
+                player.left = true;
+            }
+        }
+    }
+    // to everyone but the player at except
+    fn broadcast(&mut self, message: &Message, except: Option<usize>) {
+        for (i, player) in self.players.iter_mut().enumerate() {
+            if Some(i) != except && player.conn.send(message).is_err() {
+                player.left = true;
+            }
+        }
+    }
+}
+
+// a player's side of the connection to a server
+pub struct RoyaleClient {
+    conn: Connection,
+    you: usize,
+    lobby: Vec<LobbyEntry>,
+    names: BTreeMap<usize, String>, // everyone seen, kept after they leave
+    targeting: Targeting,
+    next_game: Option<MatchSettings>,
+    roster: Vec<usize>, // who plays in the current game, you included
+    boards: BTreeMap<usize, Snapshot>,
+    places: BTreeMap<usize, usize>,
+    badges: BTreeMap<usize, u32>,
+    target: usize,
+    attackers: Vec<usize>,
+    winner: Option<usize>,
+    sent_inputs: usize,
+    sent_clock: u64,
+    closed: bool,
+}
+
+impl RoyaleClient {
+    pub fn enter(addr: impl ToSocketAddrs, name: &str) -> io::Result<RoyaleClient> {
+        let mut conn = Connection::new(TcpStream::connect(addr)?)?;
+        conn.handshake()?;
+        conn.send(&Message::Enter(name.to_string()))?;
+        match conn.recv_timeout(HANDSHAKE_TIMEOUT)? {
+            Message::Lobby(you, lobby) => {
+                let mut client = RoyaleClient {
+                    conn,
+                    you,
+                    lobby: Vec::new(),
+                    names: BTreeMap::new(),
+                    targeting: Targeting::default(),
+                    next_game: None,
+                    roster: Vec::new(),
+                    boards: BTreeMap::new(),
+                    places: BTreeMap::new(),
+                    badges: BTreeMap::new(),
+                    target: 0,
+                    attackers: Vec::new(),
+                    winner: None,
+                    sent_inputs: 0,
+                    sent_clock: 0,
+                    closed: false,
+                };
+                client.set_lobby(lobby);
+                Ok(client)
+            }
+            Message::Bye => Err(io::Error::other("the server is full")),
+            _ => Err(io::Error::other("that isn't a battle royale server")),
+        }
+    }
+    fn set_lobby(&mut self, lobby: Vec<LobbyEntry>) {
+        for entry in lobby.iter() {
+            self.names.insert(entry.id, entry.name.clone());
+        }
+        self.lobby = lobby;
+    }
+
+    pub fn you(&self) -> usize {
+        self.you
+    }
+    pub fn lobby(&self) -> &[LobbyEntry] {
+        &self.lobby
+    }
+    pub fn name(&self, id: usize) -> &str {
+        self.names.get(&id).map(|n| n.as_str()).unwrap_or("?")
+    }
+    pub fn targeting(&self) -> Targeting {
+        self.targeting
+    }
+    pub fn roster(&self) -> &[usize] {
+        &self.roster
+    }
+    pub fn board(&self, id: usize) -> Option<&Snapshot> {
+        self.boards.get(&id)
+    }
+    // where a knocked out player placed
+    pub fn place(&self, id: usize) -> Option<usize> {
+        self.places.get(&id).copied()
+    }
+    pub fn badges(&self, id: usize) -> u32 {
+        self.badges.get(&id).copied().unwrap_or(0)
+    }
+    // who you attack, 0 for nobody
+    pub fn target(&self) -> usize {
+        self.target
+    }
+    pub fn attackers(&self) -> &[usize] {
+        &self.attackers
+    }
+    pub fn alive(&self) -> usize {
+        self.roster.len() - self.places.len()
+    }
+    // the winner once the game is over, 0 when nobody won
+    pub fn winner(&self) -> Option<usize> {
+        self.winner
+    }
+    // everyone who played by place, the winner first
+    pub fn standings(&self) -> Vec<(usize, usize)> {
+        let mut standings: Vec<(usize, usize)> = self
+            .roster
+            .iter()
+            .map(|id| (self.place(*id).unwrap_or(1), *id))
+            .collect();
+        standings.sort();
+        standings
+    }
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn set_ready(&mut self, ready: bool) {
+        self.send(&Message::Ready(ready));
+    }
+    pub fn set_targeting(&mut self, targeting: Targeting) {
+        self.targeting = targeting;
+        self.send(&Message::Target(targeting));
+    }
+    // the settings of a game that just started, everything about the last one is forgotten
+    pub fn take_next_game(&mut self) -> Option<MatchSettings> {
+        let settings = self.next_game.take()?;
+        self.roster = self
+            .lobby
+            .iter()
+            .filter(|e| e.seat == Seat::Playing)
+            .map(|e| e.id)
+            .collect();
+        self.boards.clear();
+        self.places.clear();
+        self.badges.clear();
+        self.target = 0;
+        self.attackers.clear();
+        self.winner = None;
+        self.sent_inputs = 0;
+        self.sent_clock = 0;
+        Some(settings)
+    }
+    // sends what was pressed on the board since the last call and returns the attacks that came in
+    pub fn exchange(&mut self, board: &Board) -> Vec<u32> {
+        let inputs = board.inputs();
+        for input in inputs[self.sent_inputs.min(inputs.len())..].iter() {
+            self.send(&Message::Input(*input));
+            self.sent_clock = input.ms;
+        }
+        self.sent_inputs = inputs.len();
+        // a game that ended is reported right away so the server sees it top out
+        let clock_ms = board.game().clock_ms();
+        if clock_ms >= self.sent_clock + CLOCK_INTERVAL_MS
+            || (board.is_over() && clock_ms > self.sent_clock)
+        {
+            self.send(&Message::Clock(clock_ms));
+            self.sent_clock = clock_ms;
+        }
+        self.poll()
+    }
+    // handles everything the server sent since the last call, returns the attacks among it
+    pub fn poll(&mut self) -> Vec<u32> {
+        let mut attacks = Vec::new();
+        while let Some(message) = self.conn.try_recv() {
+            match message {
+                Message::Lobby(_, lobby) => self.set_lobby(lobby),
+                Message::Start(settings) if settings.validate().is_ok() => {
+                    self.next_game = Some(settings)
+                }
+                Message::Garbage(lines) => attacks.push(lines),
+                Message::Frame(id, snapshot) => {
+                    self.boards.insert(id, snapshot);
+                }
+                Message::Delta(id, delta) => {
+                    if let Some(board) = self.boards.get_mut(&id) {
+                        board.apply(&delta);
+                    }
+                }
+                Message::Aim { target, attackers } => {
+                    self.target = target;
+                    self.attackers = attackers;
+                }
+                Message::Ko { player, place, by } => {
+                    self.places.insert(player, place);
+                    if by != 0 {
+                        let badges = self.badges(player) + 1;
+                        *self.badges.entry(by).or_default() += badges;
+                    }
+                }
+                Message::Result(winner) => self.winner = Some(winner),
+                Message::Bye => {
+                    self.closed = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        attacks
+    }
+    pub fn leave(&mut self) {
+        self.send(&Message::Bye);
+        self.closed = true;
+    }
+    fn send(&mut self, message: &Message) {
+        if self.closed {
+            return;
+        }
+        if self.conn.send(message).is_err() {
+            self.closed = true;
+        }
+    }
+}
+
+// another player's board at a glance, two rows to a line under their name
+pub struct MiniBoardView {
+    label: String,
+    snapshot: Option<Snapshot>,
+    out: bool, // drawn in gray once knocked out
+    width: usize,
+    height: usize,
+}
+
+impl MiniBoardView {
+    pub fn new(width: usize, height: usize) -> Self {
+        MiniBoardView {
+            label: String::new(),
+            snapshot: None,
+            out: false,
+            width,
+            height,
+        }
+    }
+    pub fn set_label(&mut self, label: String) {
+        self.label = label;
+    }
+    pub fn set_snapshot(&mut self, snapshot: Option<Snapshot>) {
+        self.snapshot = snapshot;
+    }
+    pub fn set_out(&mut self, out: bool) {
+        self.out = out;
+    }
+    fn tile(&self, row: usize, col: usize) -> Tile {
+        self.snapshot
+            .as_ref()
+            .and_then(|s| s.rows.get(row))
+            .and_then(|r| r.get(col))
+            .copied()
+            .flatten()
+    }
+    fn color(&self, tile: Tile) -> Color {
+        match (tile, self.out) {
+            (None, _) => Color::Dark(BaseColor::Black),
+            (Some(_), true) => Block::Garbage.get_color(),
+            (Some(block), false) => block.get_color(),
+        }
+    }
+}
+
+impl View for MiniBoardView {
+    fn required_size(&mut self, _constraint: cursive::XY<usize>) -> cursive::XY<usize> {
+        (self.width, self.height.div_ceil(2) + 1).into()
+    }
+    fn draw(&self, printer: &Printer) {
+        let label: String = self.label.chars().take(self.width).collect();
+        printer.print((0, 0), &label);
+        for line in 0..self.height.div_ceil(2) {
+            for col in 0..self.width {
+                let top = self.color(self.tile(line * 2, col));
+                let bottom = match line * 2 + 1 < self.height {
+                    true => self.color(self.tile(line * 2 + 1, col)),
+                    false => Color::Dark(BaseColor::Black),
+                };
+                printer.with_style(ColorStyle::new(top, bottom), |p| {
+                    p.print((col, line + 1), "▀")
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use crate::engine::Action;
+    use crate::garbage::GarbageStyle;
+    use std::time::Instant;
+
+    // steps the server until the condition holds, clients only hear back once it has stepped
+    fn step_until(server: &mut Server, what: &str, mut done: impl FnMut(&mut Server) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(server) {
+            assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+            server.step();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn send_input(client: &mut RoyaleClient, ms: u64, command: Command) {
+        client.send(&Message::Input(Input { ms, command }));
+    }
+
+    #[test]
+    fn badges_rise_with_knock_outs() {
+        assert_eq!(attack_with_badges(4, 1), 4);
+        assert_eq!(attack_with_badges(4, 2), 5);
+        assert_eq!(attack_with_badges(4, 6), 6);
+        assert_eq!(attack_with_badges(4, 30), 8);
+    }
+
+    #[test]
+    fn a_game_of_four_over_localhost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (entrants_tx, entrants) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(4) {
+                greet(stream.unwrap(), &entrants_tx);
+            }
+        });
+        let settings = MatchSettings {
+            seed: 0,
+            starting_level: 1,
+            width: board::BOARD_WIDTH,
+            height: board::BOARD_HEIGHT,
+            pieces: "tetrominoes".to_string(),
+            garbage_style: GarbageStyle::Clean,
+        };
+        let mut server = Server::new(settings, DEFAULT_MAX_PLAYERS);
+        let entering: Vec<_> = ["ann", "bo", "cy", "dee"]
+            .into_iter()
+            .map(|name| thread::spawn(move || RoyaleClient::enter(addr, name).unwrap()))
+            .collect();
+        step_until(&mut server, "everyone to enter", |server| {
+            while let Ok((conn, name)) = entrants.try_recv() {
+                server.add(conn, name);
+            }
+            server.players.len() == 4
+        });
+        let mut clients: Vec<RoyaleClient> =
+            entering.into_iter().map(|t| t.join().unwrap()).collect();
+        let ids: Vec<usize> = clients.iter().map(|c| c.you()).collect();
+        let (a, b, c, d) = (ids[0], ids[1], ids[2], ids[3]);
+        let index = |server: &Server, id| server.index_of(id).unwrap();
+        let target = |server: &Server, id| server.players[index(server, id)].round.as_ref()?.target;
+
+        for client in clients.iter_mut() {
+            client.set_ready(true);
+        }
+        step_until(&mut server, "the game to start", |server| server.playing);
+        step_until(&mut server, "everyone to hear of it", |_| {
+            clients.iter_mut().all(|client| {
+                client.poll();
+                client.take_next_game().is_some()
+            })
+        });
+        assert_eq!(clients[0].roster().len(), 4);
+
+        // a stack makes cy the one closest to topping out, so that's who ann's kos aims at
+        clients[0].set_targeting(Targeting::Kos);
+        clients[2].set_targeting(Targeting::Attackers);
+        send_input(&mut clients[2], 500, Command::Act(Action::HardDrop));
+        step_until(&mut server, "ann to aim at cy", |server| {
+            let ann = &server.players[index(server, a)];
+            ann.targeting == Targeting::Kos && target(server, a) == Some(c)
+        });
+        // random only ever picks someone else
+        assert!(target(&server, b).is_some_and(|t| t != b));
+        let from = index(&server, a);
+        server.attack(from, 2);
+        let mut attacks = Vec::new();
+        step_until(&mut server, "cy to be attacked", |_| {
+            attacks.extend(clients[2].poll());
+            // attackers aims back at someone attacking
+            let aim = (clients[2].target(), clients[2].attackers());
+            !attacks.is_empty() && aim.1.contains(&a) && aim.1.contains(&aim.0)
+        });
+        assert_eq!(attacks, vec![2]);
+
+        // cy takes the attack in and tops out, ann was the last to attack so gets the badge
+        send_input(&mut clients[2], 1000, Command::Garbage(2));
+        for n in 0..40 {
+            send_input(
+                &mut clients[2],
+                1500 + n * 500,
+                Command::Act(Action::HardDrop),
+            );
+        }
+        step_until(&mut server, "cy to be knocked out", |_| {
+            clients[0].poll();
+            clients[0].place(c).is_some()
+        });
+        assert_eq!(clients[0].place(c), Some(4));
+        assert_eq!(clients[0].badges(a), 1);
+        let ann = &server.players[index(&server, a)];
+        assert_eq!(ann.round.as_ref().unwrap().badges, 1);
+
+        // ann holds the only badge, so that's who dee's badges aims at
+        clients[3].set_targeting(Targeting::Badges);
+        step_until(&mut server, "dee to aim at ann", |server| {
+            let dee = &server.players[index(server, d)];
+            dee.targeting == Targeting::Badges && target(server, d) == Some(a)
+        });
+
+        // bo claims garbage nobody sent, which the server won't take, then dee leaves
+        send_input(&mut clients[1], 500, Command::Garbage(5));
+        step_until(&mut server, "bo to be dropped", |server| {
+            server.index_of(b).is_none()
+        });
+        clients[3].leave();
+        step_until(&mut server, "the game to end", |_| {
+            clients[0].poll();
+            clients[0].winner().is_some()
+        });
+        assert_eq!(clients[0].winner(), Some(a));
+        assert_eq!(clients[0].place(b), Some(3));
+        assert_eq!(clients[0].place(d), Some(2));
+        assert_eq!(clients[0].badges(a), 1);
+        assert!(!server.playing);
+    }
+}
@@ -1,39 +1,474 @@
+use std::ffi::OsString;
+use std::fmt;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{env, fs};
 
-#[cfg(target_os = "windows")]
+#[cfg(windows)]
 fn home_dir() -> Option<PathBuf> {
-    env::var("USERPROFILE").ok().map(PathBuf::from)
+    env_dir("USERPROFILE")
 }
 
-#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[cfg(unix)]
 fn home_dir() -> Option<PathBuf> {
-    env::var("HOME").ok().map(PathBuf::from)
+    env_dir("HOME")
 }
 
-const CONFIG_FILENAME: &str = ".tetrs";
+#[cfg(not(any(unix, windows)))]
+fn home_dir() -> Option<PathBuf> {
+    None
+}
+
+const APP_DIRNAME: &str = "tetrs";
+const HIGH_SCORE_FILENAME: &str = "highscore";
+const SETTINGS_FILENAME: &str = "config.toml";
+const SCORES_FILENAME: &str = "scores.toml";
+const REPLAYS_DIRNAME: &str = "replays";
+const POSITIONS_DIRNAME: &str = "positions";
+
+// pre-xdg locations, relative to the home dir
+const LEGACY_HIGH_SCORE_FILENAME: &str = ".tetrs";
+const LEGACY_SETTINGS_FILENAME: &str = ".tetrs.toml";
+
+// overrides both the config and data dirs, handy for testing or portable installs
+const TETRS_HOME_VAR: &str = "TETRS_HOME";
+
+// env var holding a usable path, per the xdg spec relative paths are ignored
+fn env_dir(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+}
 
-fn config_path() -> io::Result<PathBuf> {
+fn no_home_dir_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "Could not determine home directory",
+    )
+}
+
+// $TETRS_HOME, else $XDG_CONFIG_HOME/tetrs, else the platform default
+pub fn config_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = env_dir(TETRS_HOME_VAR) {
+        return Ok(dir);
+    }
+    if let Some(dir) = env_dir("XDG_CONFIG_HOME") {
+        return Ok(dir.join(APP_DIRNAME));
+    }
+    #[cfg(windows)]
+    if let Some(dir) = env_dir("APPDATA") {
+        return Ok(dir.join(APP_DIRNAME));
+    }
     match home_dir() {
-        Some(mut path) => {
-            path.push(CONFIG_FILENAME);
-            Ok(path)
+        Some(home) => Ok(home.join(".config").join(APP_DIRNAME)),
+        None => Err(no_home_dir_error()),
+    }
+}
+
+// $TETRS_HOME, else $XDG_DATA_HOME/tetrs, else the platform default
+// scores, replays and saves all live under here
+pub fn data_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = env_dir(TETRS_HOME_VAR) {
+        return Ok(dir);
+    }
+    if let Some(dir) = env_dir("XDG_DATA_HOME") {
+        return Ok(dir.join(APP_DIRNAME));
+    }
+    #[cfg(windows)]
+    if let Some(dir) = env_dir("LOCALAPPDATA") {
+        return Ok(dir.join(APP_DIRNAME));
+    }
+    match home_dir() {
+        Some(home) => Ok(home.join(".local").join("share").join(APP_DIRNAME)),
+        None => Err(no_home_dir_error()),
+    }
+}
+
+// `<path><suffix>`, used for the temp, backup and quarantine siblings of a save file
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+fn backup_path(path: &Path) -> PathBuf {
+    sibling_path(path, ".bak")
+}
+
+// unique per process and per write, so two writers (or two instances) never share a temp file
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn temp_path(path: &Path) -> PathBuf {
+    let count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    sibling_path(path, &format!(".{}.{}.tmp", std::process::id(), count))
+}
+
+// the first free `.corrupt`, `.corrupt.1`, ... so an earlier quarantined file is never lost
+fn quarantine_path(path: &Path) -> PathBuf {
+    let mut candidate = sibling_path(path, ".corrupt");
+    let mut n = 1;
+    while candidate.exists() {
+        candidate = sibling_path(path, &format!(".corrupt.{}", n));
+        n += 1;
+    }
+    candidate
+}
+
+// all persistence goes through here: write to a temp file, fsync it, keep a backup of the
+// previous version and then rename over the original, so a crash or a full disk mid-write can
+// never leave a half written file behind
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = temp_path(path);
+    if let Err(e) = replace_via(&tmp_path, path, content) {
+        let _ = fs::remove_file(&tmp_path); // don't leave a stray temp file behind
+        return Err(e);
+    }
+    sync_dir(path.parent())
+}
+
+fn replace_via(tmp_path: &Path, path: &Path, content: &str) -> io::Result<()> {
+    {
+        let mut file = fs::File::create(tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?; // ensure flushed to disk before it becomes visible
+    }
+    if path.is_file() {
+        fs::copy(path, backup_path(path))?;
+    }
+    fs::rename(tmp_path, path)
+}
+
+// the rename itself only survives a crash once the directory entry is flushed too
+#[cfg(unix)]
+fn sync_dir(dir: Option<&Path>) -> io::Result<()> {
+    match dir {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::File::open(dir)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: Option<&Path>) -> io::Result<()> {
+    Ok(()) // directories can't be opened for syncing here, rename is as good as it gets
+}
+
+// a save file that exists but could not be understood
+#[derive(Debug)]
+pub struct CorruptFile {
+    pub path: PathBuf,
+    pub moved_to: PathBuf,
+    pub reason: String,
+    pub restored_from_backup: bool,
+}
+
+impl fmt::Display for CorruptFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is corrupt ({}).\nIt was moved to {}",
+            self.path.display(),
+            self.reason,
+            self.moved_to.display()
+        )?;
+        match self.restored_from_backup {
+            true => write!(f, " and the previous backup was restored."),
+            false => write!(f, " and no usable backup was found."),
+        }
+    }
+}
+
+pub struct Loaded<T> {
+    pub value: Option<T>, // None when there was nothing (usable) on disk
+    pub corruption: Option<CorruptFile>,
+}
+
+// counterpart to `write_atomic`: a missing file is fine, but a corrupt one is moved aside (so
+// the next write can't clobber it) and the backup is tried instead, the caller gets told either
+// way so it can tell the player
+pub fn read_checked<T, F>(path: &Path, parse: F) -> io::Result<Loaded<T>>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    let reason = match read_and_parse(path, &parse)? {
+        None => {
+            return Ok(Loaded {
+                value: None,
+                corruption: None,
+            });
+        }
+        Some(Ok(value)) => {
+            return Ok(Loaded {
+                value: Some(value),
+                corruption: None,
+            });
         }
-        None => Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "Could not determine home directory",
-        )),
+        Some(Err(reason)) => reason,
+    };
+    let moved_to = quarantine_path(path);
+    fs::rename(path, &moved_to)?;
+    let backup = backup_path(path);
+    let value = match read_and_parse(&backup, &parse)? {
+        Some(Ok(value)) => {
+            fs::copy(&backup, path)?;
+            Some(value)
+        }
+        _ => None,
+    };
+    Ok(Loaded {
+        corruption: Some(CorruptFile {
+            path: path.to_path_buf(),
+            moved_to,
+            reason,
+            restored_from_backup: value.is_some(),
+        }),
+        value,
+    })
+}
+
+// None if the file doesn't exist
+fn read_and_parse<T, F>(path: &Path, parse: &F) -> io::Result<Option<Result<T, String>>>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    match fs::read(path) {
+        Ok(bytes) => match String::from_utf8(bytes) {
+            Ok(content) => Ok(Some(parse(&content))),
+            Err(_) => Ok(Some(Err("not valid text".to_string()))),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
     }
 }
-pub fn write_config(content: &str) -> io::Result<()> {
-    let path = config_path()?;
-    let mut file = fs::File::create(&path)?; // truncates existing
-    file.write_all(content.as_bytes())?;
-    file.sync_all()?; // ensure flushed to disk
+// set once at launch when a config file is given explicitly
+static SETTINGS_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_settings_path(path: PathBuf) {
+    let _ = SETTINGS_PATH_OVERRIDE.set(path);
+}
+pub fn settings_path() -> io::Result<PathBuf> {
+    match SETTINGS_PATH_OVERRIDE.get() {
+        Some(path) => Ok(path.clone()),
+        None => Ok(config_dir()?.join(SETTINGS_FILENAME)),
+    }
+}
+fn high_score_path() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join(HIGH_SCORE_FILENAME))
+}
+pub fn scores_path() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join(SCORES_FILENAME))
+}
+pub fn replays_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join(REPLAYS_DIRNAME))
+}
+pub fn positions_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join(POSITIONS_DIRNAME))
+}
+pub fn write_high_score(score: u32) -> io::Result<()> {
+    write_atomic(&high_score_path()?, &score.to_string())
+}
+pub fn read_high_score() -> io::Result<Loaded<u32>> {
+    read_checked(&high_score_path()?, |content| {
+        content
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| format!("not a score: {}", e))
+    })
+}
+pub fn write_settings(content: &str) -> io::Result<()> {
+    write_atomic(&settings_path()?, content)
+}
+pub fn read_settings<T, F>(parse: F) -> io::Result<Loaded<T>>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    read_checked(&settings_path()?, parse)
+}
+
+// moves files from the old `~/.tetrs*` locations into the xdg dirs, only when the new file does
+// not exist yet so an already migrated install is never overwritten
+pub fn migrate_legacy_files() -> io::Result<()> {
+    let home = match home_dir() {
+        Some(home) => home,
+        None => return Ok(()), // nothing to migrate from
+    };
+    let migrations = [
+        (home.join(LEGACY_HIGH_SCORE_FILENAME), high_score_path()?),
+        (home.join(LEGACY_SETTINGS_FILENAME), settings_path()?),
+    ];
+    for (legacy, new) in migrations.iter() {
+        if !legacy.is_file() || new.exists() {
+            continue;
+        }
+        write_atomic(new, &fs::read_to_string(legacy)?)?;
+        fs::remove_file(legacy)?;
+    }
     Ok(())
 }
-pub fn read_config() -> io::Result<String> {
-    let path = config_path()?;
-    fs::read_to_string(path)
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::sync::Mutex;
+
+    // the env is shared by the whole test binary, so the tests that touch it take turns
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+    const VARS: [&str; 4] = [
+        TETRS_HOME_VAR,
+        "XDG_CONFIG_HOME",
+        "XDG_DATA_HOME",
+        "HOME",
+    ];
+
+    fn set(var: &str, value: impl AsRef<OsStr>) {
+        // SAFETY: only ever called while holding ENV_LOCK
+        unsafe { env::set_var(var, value) }
+    }
+
+    // runs `test` in a fresh temp dir with none of the dir vars set, and puts them back after
+    fn in_temp_dir<F: FnOnce(&Path)>(name: &str, test: F) {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let saved: Vec<_> = VARS.iter().map(|var| (var, env::var_os(var))).collect();
+        for var in VARS {
+            // SAFETY: as above
+            unsafe { env::remove_var(var) }
+        }
+        let dir = env::temp_dir().join(format!("tetrs-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        test(&dir);
+        let _ = fs::remove_dir_all(&dir);
+        for (var, value) in saved {
+            match value {
+                Some(value) => set(var, value),
+                // SAFETY: as above
+                None => unsafe { env::remove_var(var) },
+            }
+        }
+    }
+
+    #[test]
+    fn tetrs_home_wins_over_xdg() {
+        in_temp_dir("home-wins", |dir| {
+            set(TETRS_HOME_VAR, dir);
+            set("XDG_CONFIG_HOME", dir.join("config"));
+            set("XDG_DATA_HOME", dir.join("data"));
+            assert_eq!(config_dir().unwrap(), dir);
+            assert_eq!(data_dir().unwrap(), dir);
+        });
+    }
+
+    #[test]
+    fn xdg_dirs_get_an_app_dir() {
+        in_temp_dir("xdg", |dir| {
+            set("XDG_CONFIG_HOME", dir.join("config"));
+            set("XDG_DATA_HOME", dir.join("data"));
+            assert_eq!(config_dir().unwrap(), dir.join("config").join("tetrs"));
+            assert_eq!(scores_path().unwrap(), dir.join("data/tetrs/scores.toml"));
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_xdg_dirs_fall_back_to_home() {
+        in_temp_dir("relative", |dir| {
+            set("HOME", dir);
+            set("XDG_CONFIG_HOME", "config");
+            set(TETRS_HOME_VAR, "portable");
+            assert_eq!(config_dir().unwrap(), dir.join(".config/tetrs"));
+            assert_eq!(data_dir().unwrap(), dir.join(".local/share/tetrs"));
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn legacy_files_move_once() {
+        in_temp_dir("migrate", |dir| {
+            set("HOME", dir);
+            fs::write(dir.join(".tetrs"), "1200").unwrap();
+            fs::write(dir.join(".tetrs.toml"), "music_on = false\n").unwrap();
+            migrate_legacy_files().unwrap();
+            assert!(!dir.join(".tetrs").exists());
+            assert!(!dir.join(".tetrs.toml").exists());
+            assert_eq!(read_high_score().unwrap().value, Some(1200));
+            assert_eq!(
+                fs::read_to_string(dir.join(".config/tetrs/config.toml")).unwrap(),
+                "music_on = false\n"
+            );
+
+            // an already migrated install is left alone, and so is the stray legacy file
+            fs::write(dir.join(".tetrs"), "5").unwrap();
+            migrate_legacy_files().unwrap();
+            assert_eq!(read_high_score().unwrap().value, Some(1200));
+            assert!(dir.join(".tetrs").exists());
+        });
+    }
+
+    #[test]
+    fn writes_keep_a_backup_and_no_temp_files() {
+        in_temp_dir("write", |dir| {
+            set(TETRS_HOME_VAR, dir);
+            write_high_score(100).unwrap();
+            write_high_score(200).unwrap();
+            assert_eq!(fs::read_to_string(dir.join("highscore")).unwrap(), "200");
+            assert_eq!(
+                fs::read_to_string(dir.join("highscore.bak")).unwrap(),
+                "100"
+            );
+            assert_eq!(fs::read_dir(dir).unwrap().count(), 2);
+        });
+    }
+
+    #[test]
+    fn a_corrupt_file_is_quarantined_and_the_backup_restored() {
+        in_temp_dir("restore", |dir| {
+            set(TETRS_HOME_VAR, dir);
+            write_high_score(100).unwrap();
+            write_high_score(200).unwrap();
+            fs::write(dir.join("highscore"), "garbage").unwrap();
+
+            let loaded = read_high_score().unwrap();
+            assert_eq!(loaded.value, Some(100));
+            let corruption = loaded.corruption.unwrap();
+            assert!(corruption.restored_from_backup);
+            assert_eq!(corruption.moved_to, dir.join("highscore.corrupt"));
+            assert_eq!(fs::read_to_string(&corruption.moved_to).unwrap(), "garbage");
+            assert_eq!(fs::read_to_string(dir.join("highscore")).unwrap(), "100");
+        });
+    }
+
+    #[test]
+    fn earlier_quarantined_files_are_kept() {
+        in_temp_dir("quarantine", |dir| {
+            set(TETRS_HOME_VAR, dir);
+            for garbage in ["first", "second"] {
+                fs::write(dir.join("highscore"), garbage).unwrap();
+                let loaded = read_high_score().unwrap();
+                assert_eq!(loaded.value, None);
+                assert!(!loaded.corruption.unwrap().restored_from_backup);
+            }
+            let first = fs::read_to_string(dir.join("highscore.corrupt")).unwrap();
+            let second = fs::read_to_string(dir.join("highscore.corrupt.1")).unwrap();
+            assert_eq!((first.as_str(), second.as_str()), ("first", "second"));
+            assert!(read_high_score().unwrap().corruption.is_none());
+        });
+    }
+
+    #[test]
+    fn a_broken_config_falls_back_to_its_backup() {
+        in_temp_dir("config", |dir| {
+            set(TETRS_HOME_VAR, dir);
+            write_settings("music_on = false\n").unwrap();
+            write_settings("music_on = \"maybe\"\n").unwrap();
+
+            let loaded = crate::config::load().unwrap();
+            assert_eq!(loaded.value.map(|c| c.music_on), Some(false));
+            assert!(loaded.corruption.unwrap().restored_from_backup);
+            assert!(dir.join("config.toml.corrupt").is_file());
+        });
+    }
 }
@@ -0,0 +1,122 @@
+use crate::engine::{Mode, SPRINT_LINES};
+use crate::replay::{self, Replay, Summary};
+use crate::save::{self, Loaded};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// one finished game on the leaderboard, tied to its replay by the replay's hash. the hash is an
+// integrity check and not tamper-proofing: it isn't keyed, so it catches a replay that got
+// corrupted or mixed up with another, but anyone editing the replay on purpose can rewrite the
+// hash in the score file too. what a record is worth comes from `verify_record`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScoreRecord {
+    #[serde(default)]
+    pub mode: Mode,
+    pub played_at: u64, // unix seconds
+    pub replay: String, // file name in the replays dir
+    pub replay_sha256: String,
+    pub summary: Summary,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScoreFile {
+    #[serde(default)]
+    scores: Vec<ScoreRecord>,
+}
+
+pub fn load() -> io::Result<Loaded<Vec<ScoreRecord>>> {
+    let loaded = save::read_checked(&save::scores_path()?, |content| {
+        toml::from_str::<ScoreFile>(content)
+            .map(|f| f.scores)
+            .map_err(|e| e.to_string())
+    })?;
+    Ok(loaded)
+}
+
+// saves the replay and adds its record to the leaderboard
+pub fn record(replay: &Replay) -> io::Result<ScoreRecord> {
+    let played_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let content = replay.to_toml().map_err(io::Error::other)?;
+    let replay_name = format!("{}-{:016x}.toml", played_at, replay.seed);
+    save::write_atomic(&save::replays_dir()?.join(&replay_name), &content)?;
+
+    let record = ScoreRecord {
+        mode: replay.mode,
+        played_at,
+        replay: replay_name,
+        replay_sha256: replay::sha256_hex(content.as_bytes()),
+        summary: replay.summary,
+    };
+    // a corrupt score file has already been moved aside by `load`, so start over from whatever
+    // could be recovered
+    let mut scores = load()?.value.unwrap_or_default();
+    scores.push(record.clone());
+    let content = toml::to_string(&ScoreFile { scores }).map_err(io::Error::other)?;
+    save::write_atomic(&save::scores_path()?, &content)?;
+    Ok(record)
+}
+
+// the records of one mode, best first: fastest finished sprints, highest scores otherwise
+pub fn leaderboard(records: &[ScoreRecord], mode: Mode) -> Vec<ScoreRecord> {
+    let mut board: Vec<ScoreRecord> = records.iter().filter(|r| r.mode == mode).cloned().collect();
+    match mode {
+        Mode::Sprint => board.sort_by_key(|r| (r.summary.lines < SPRINT_LINES, r.summary.time_ms)),
+        Mode::Marathon | Mode::Ultra => board.sort_by_key(|r| std::cmp::Reverse(r.summary.score)),
+    }
+    board
+}
+
+// the one guarantee a score record gives: its replay is re-simulated from the seed and has to play
+// out to exactly the recorded result, a made up score can't pass this however the files were
+// edited. the hash is only compared first to give a clearer message for a damaged replay
+pub fn verify_record(record: &ScoreRecord) -> Result<Summary, String> {
+    let path = save::replays_dir()
+        .map_err(|e| e.to_string())?
+        .join(&record.replay);
+    let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    verify_against_record(record, &bytes)
+}
+
+// verifies a replay file on its own, and against its score record when there is one
+pub fn verify_replay_file(path: &Path) -> Result<Summary, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string());
+    let records = load().map_err(|e| e.to_string())?.value.unwrap_or_default();
+    match records
+        .iter()
+        .find(|r| Some(&r.replay) == file_name.as_ref())
+    {
+        Some(record) => verify_against_record(record, &bytes),
+        None => parse_replay(bytes)?.verify(),
+    }
+}
+
+fn verify_against_record(record: &ScoreRecord, replay_bytes: &[u8]) -> Result<Summary, String> {
+    if replay::sha256_hex(replay_bytes) != record.replay_sha256 {
+        return Err(
+            "replay failed its integrity check, it changed since the score was recorded"
+                .to_string(),
+        );
+    }
+    let replay = parse_replay(replay_bytes.to_vec())?;
+    if replay.summary != record.summary {
+        return Err(format!(
+            "score record claims {} but its replay claims {}",
+            record.summary, replay.summary
+        ));
+    }
+    replay.verify()
+}
+
+fn parse_replay(bytes: Vec<u8>) -> Result<Replay, String> {
+    let content = String::from_utf8(bytes).map_err(|_| "replay is not valid text".to_string())?;
+    Replay::parse(&content)
+}
@@ -1,30 +1,99 @@
 use crate::audio;
+use crate::board;
 use crate::board::Board;
 use crate::board::BoardSettings;
+use crate::board::Hints;
+use crate::board::PracticeSetup;
+use crate::bot::BotSpec;
+use crate::bot::Difficulty;
+use crate::config;
+use crate::config::Config;
+use crate::controls;
+use crate::controls::BindError;
+use crate::controls::KeyBindings;
+use crate::controls::VersusControls;
+use crate::editor::Editor;
+use crate::engine::Action;
+use crate::engine::Mode;
+use crate::engine::Timing;
+use crate::fumen;
+use crate::garbage::GarbageMeter;
+use crate::garbage::GarbageStyle;
 use crate::ids;
+use crate::net::MatchSettings;
+use crate::net::OpponentView;
+use crate::net::Session;
+use crate::net::Spectator;
+use crate::piece::Piece;
 use crate::piece::PieceView;
+use crate::piece_set::PieceSet;
+use crate::position;
+use crate::position::Position;
+use crate::replay::Replay;
+use crate::royale::MiniBoardView;
+use crate::royale::RoyaleClient;
+use crate::royale::Seat;
+use crate::royale::Targeting;
 use crate::save;
 use crate::text_art;
 use cursive::Cursive;
 use cursive::CursiveRunnable;
 use cursive::event::Event;
+use cursive::event::EventResult;
+use cursive::event::EventTrigger;
 use cursive::event::Key;
 use cursive::theme::BaseColor;
 use cursive::theme::Effect;
 use cursive::traits::*;
+use cursive::views::Checkbox;
 use cursive::views::DummyView;
+use cursive::views::EditView;
 use cursive::views::HideableView;
 use cursive::views::OnEventView;
 use cursive::views::PaddedView;
+use cursive::views::SelectView;
 use cursive::views::TextView;
 use cursive::views::{Button, Dialog, LinearLayout};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::sync::OnceLock;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
 
 // static atomic state (needed for referncing in cursive callbacks)
 static LEVEL: AtomicU8 = AtomicU8::new(1);
 static GHOST_PIECE_ON: AtomicBool = AtomicBool::new(true);
+static MUSIC_ON: AtomicBool = AtomicBool::new(true);
 static HIGH_SCORE: AtomicU32 = AtomicU32::new(0);
+static MODE: Mutex<Mode> = Mutex::new(Mode::Marathon);
+// timing overrides from the config, None keeps the mode's own value
+static ENTRY_DELAY_MS: Mutex<Option<u64>> = Mutex::new(None);
+static LINE_CLEAR_DELAY_MS: Mutex<Option<u64>> = Mutex::new(None);
+// width and visible height of the board
+static BOARD_SIZE: Mutex<(usize, usize)> = Mutex::new((board::BOARD_WIDTH, board::BOARD_HEIGHT));
+static PIECE_SET: LazyLock<Mutex<Arc<PieceSet>>> =
+    LazyLock::new(|| Mutex::new(Arc::new(PieceSet::default())));
+static KEY_BINDINGS: LazyLock<Mutex<KeyBindings>> =
+    LazyLock::new(|| Mutex::new(KeyBindings::default()));
+static VERSUS_CONTROLS: LazyLock<Mutex<VersusControls>> =
+    LazyLock::new(|| Mutex::new(VersusControls::default()));
+// only set when launched with a fixed seed, every game of the session then uses it
+static FIXED_SEED: OnceLock<u64> = OnceLock::new();
+// the config as it is on disk, command line overrides are left out so they don't get saved
+static SAVED_CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+// the connection of a networked game, for as long as it lasts
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+// the connection of a game being watched
+static SPECTATOR: Mutex<Option<Spectator>> = Mutex::new(None);
+// the connection to a battle royale server
+static ROYALE: Mutex<Option<RoyaleClient>> = Mutex::new(None);
+static BOT_DIFFICULTY: Mutex<Difficulty> = Mutex::new(Difficulty::Medium);
+static HINTS: Mutex<Hints> = Mutex::new(Hints::Off);
+// only set when launched with a bot, otherwise the built in one plays at BOT_DIFFICULTY
+static BOT: OnceLock<BotSpec> = OnceLock::new();
 pub fn get_starting_level() -> u8 {
     LEVEL.load(Ordering::Relaxed)
 }
@@ -40,83 +109,1334 @@ pub fn get_ghost_piece_on() -> bool {
 pub fn set_ghost_piece_on(v: bool) {
     GHOST_PIECE_ON.store(v, Ordering::Relaxed)
 }
+pub fn get_music_on() -> bool {
+    MUSIC_ON.load(Ordering::Relaxed)
+}
+
+pub fn set_music_on(v: bool) {
+    MUSIC_ON.store(v, Ordering::Relaxed)
+}
 pub fn get_high_score() -> u32 {
     HIGH_SCORE.load(Ordering::Relaxed)
 }
 
-pub fn set_high_score(v: u32) {
-    HIGH_SCORE.store(v, Ordering::Relaxed);
+pub fn set_high_score(v: u32) {
+    HIGH_SCORE.store(v, Ordering::Relaxed);
+}
+
+pub fn get_mode() -> Mode {
+    *MODE.lock().unwrap()
+}
+
+pub fn set_mode(v: Mode) {
+    *MODE.lock().unwrap() = v;
+}
+
+pub fn get_board_size() -> (usize, usize) {
+    *BOARD_SIZE.lock().unwrap()
+}
+
+pub fn set_board_size(width: usize, height: usize) {
+    *BOARD_SIZE.lock().unwrap() = (width, height);
+}
+
+pub fn get_piece_set() -> Arc<PieceSet> {
+    PIECE_SET.lock().unwrap().clone()
+}
+
+pub fn set_piece_set(v: PieceSet) {
+    *PIECE_SET.lock().unwrap() = Arc::new(v);
+}
+
+// the delays a game of this mode is played with
+pub fn get_timing(mode: Mode) -> Timing {
+    let defaults = mode.timing();
+    Timing {
+        entry_delay_ms: ENTRY_DELAY_MS
+            .lock()
+            .unwrap()
+            .unwrap_or(defaults.entry_delay_ms),
+        line_clear_delay_ms: LINE_CLEAR_DELAY_MS
+            .lock()
+            .unwrap()
+            .unwrap_or(defaults.line_clear_delay_ms),
+    }
+}
+
+pub fn get_key_bindings() -> KeyBindings {
+    KEY_BINDINGS.lock().unwrap().clone()
+}
+
+pub fn get_bot_difficulty() -> Difficulty {
+    *BOT_DIFFICULTY.lock().unwrap()
+}
+pub fn set_bot_difficulty(v: Difficulty) {
+    *BOT_DIFFICULTY.lock().unwrap() = v;
+}
+pub fn get_hints() -> Hints {
+    *HINTS.lock().unwrap()
+}
+pub fn set_hints(v: Hints) {
+    *HINTS.lock().unwrap() = v;
+}
+pub fn get_bot_spec() -> BotSpec {
+    BOT.get()
+        .cloned()
+        .unwrap_or_else(|| BotSpec::Builtin(get_bot_difficulty()))
+}
+
+pub fn set_key_bindings(v: KeyBindings) {
+    *KEY_BINDINGS.lock().unwrap() = v;
+}
+
+pub fn set_versus_controls(v: VersusControls) {
+    *VERSUS_CONTROLS.lock().unwrap() = v;
+}
+
+// the keys of a player, 0 when playing alone and 1 or 2 in a versus game
+pub fn get_player_key_bindings(player: usize) -> KeyBindings {
+    match player {
+        0 => get_key_bindings(),
+        _ => VERSUS_CONTROLS.lock().unwrap().player(player).clone(),
+    }
+}
+
+pub fn action_for_event(player: usize, event: &Event) -> Option<Action> {
+    match player {
+        0 => KEY_BINDINGS.lock().unwrap().action_for(event),
+        _ => VERSUS_CONTROLS
+            .lock()
+            .unwrap()
+            .player(player)
+            .action_for(event),
+    }
+}
+
+// how the binary was launched, anything set here wins over the config file for this session
+#[derive(Default)]
+pub struct LaunchOptions {
+    pub mode: Option<Mode>,
+    pub starting_level: Option<u8>,
+    pub seed: Option<u64>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub pieces: Option<String>, // built in set name or set file
+    pub no_music: bool,
+    pub no_ghost: bool,
+    pub config_path: Option<PathBuf>,
+    pub replay: Option<Replay>, // watch this instead of showing the title menu
+    pub bot: Option<BotSpec>,   // plays Watch Bot and Versus Bot
+    pub network: Option<Network>, // play a networked game instead of showing the title menu
+}
+
+// how to get into a networked game, this happens before the ui starts
+pub enum Network {
+    Host { port: u16 },
+    Join { addr: String },
+    Watch { addr: String },
+    Battle { addr: String, name: String },
+}
+
+pub fn run(options: LaunchOptions) {
+    if let Some(path) = options.config_path {
+        save::set_settings_path(path);
+    }
+    // carry over files from before the xdg layout, not fatal if it fails
+    if let Err(e) = save::migrate_legacy_files() {
+        log_err!("could not migrate old save files: {}", e);
+    }
+    // load user settings, a broken config is set aside by the loader so it can't be clobbered
+    let mut save_file_notices = Vec::new();
+    let config = match config::load() {
+        Ok(loaded) => {
+            if let Some(c) = loaded.corruption {
+                save_file_notices.push(format!("Your config file {}", c));
+            }
+            loaded.value.unwrap_or_default()
+        }
+        Err(e) => {
+            save_file_notices.push(format!("Could not read your config file: {}", e));
+            Config::default()
+        }
+    };
+    apply_config(&config);
+    *SAVED_CONFIG.lock().unwrap() = Some(config);
+    if let Some(mode) = options.mode {
+        set_mode(mode);
+    }
+    if let Some(level) = options.starting_level {
+        set_level(level);
+    }
+    let (width, height) = get_board_size();
+    set_board_size(
+        options.width.unwrap_or(width),
+        options.height.unwrap_or(height),
+    );
+    let pieces = options.pieces.unwrap_or_else(|| {
+        SAVED_CONFIG
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|c| c.pieces.clone())
+            .unwrap_or_default()
+    });
+    match PieceSet::resolve(&pieces) {
+        Ok(set) => set_piece_set(set),
+        Err(e) => {
+            eprintln!("tetrs: could not load piece set {}", e);
+            std::process::exit(1);
+        }
+    }
+    if get_board_size().0 < get_piece_set().min_board_width() {
+        eprintln!(
+            "tetrs: the {} piece set needs a board at least {} wide",
+            get_piece_set().name,
+            get_piece_set().min_board_width()
+        );
+        std::process::exit(1);
+    }
+    if let Some(seed) = options.seed {
+        let _ = FIXED_SEED.set(seed);
+    }
+    if options.no_music {
+        set_music_on(false);
+    }
+    if options.no_ghost {
+        set_ghost_piece_on(false);
+    }
+    if let Some(bot) = options.bot {
+        let _ = BOT.set(bot);
+    }
+    if let Some(Err(e)) = options.network.map(connect) {
+        eprintln!("tetrs: {}", e);
+        std::process::exit(1);
+    }
+
+    let mut siv = CursiveRunnable::default();
+    let mut theme = siv.current_theme().clone();
+    theme.palette = cursive::theme::Palette::retro();
+    siv.set_theme(theme);
+
+    // fetch high score from disk
+    save_file_notices.extend(load_high_score_from_disk());
+    // init title menu, or go straight to the replay
+    match options.replay {
+        Some(replay) => watch_replay(&mut siv, replay),
+        None if SESSION.lock().unwrap().is_some() => show_online_game(&mut siv),
+        None if SPECTATOR.lock().unwrap().is_some() => show_spectator(&mut siv),
+        None if ROYALE.lock().unwrap().is_some() => show_royale_lobby(&mut siv),
+        None => show_title_menu(&mut siv),
+    }
+    // let the player know if their save data had to be recovered
+    if !save_file_notices.is_empty() {
+        siv.add_layer(
+            Dialog::around(TextView::new(save_file_notices.join("\n\n")))
+                .dismiss_button("Ok")
+                .title("Save File Problem"),
+        );
+    }
+    // play music on seperate audio thread
+    let _ = audio::play(audio::THEME_FAST, true);
+    if !get_music_on() {
+        let _ = audio::pause();
+    }
+    // init cursive
+    const FPS: u32 = 60;
+    siv.set_fps(FPS);
+    siv.run();
+    leave_session();
+    // save highscore on program close
+    save_high_score_to_disk(get_high_score());
+}
+
+pub fn show_title_menu(s: &mut Cursive) {
+    use cursive::theme::Color;
+    let title_logo_view = LinearLayout::horizontal()
+        .child(TextView::new(text_art::TETRS_T).style(Color::Dark(BaseColor::Red)))
+        .child(TextView::new(text_art::TETRS_E).style(Color::Dark(BaseColor::Yellow)))
+        .child(TextView::new(text_art::TETRS_T).style(Color::Dark(BaseColor::Green)))
+        .child(TextView::new(text_art::TETRS_R).style(Color::Dark(BaseColor::Cyan)))
+        .child(TextView::new(text_art::TETRS_S).style(Color::Dark(BaseColor::Magenta)));
+
+    let settings_button = get_settings_button();
+    // settings holder
+    let starting_score_container =
+        HideableView::new(TextView::new("1").with_name(ids::STARTING_LEVEL)).hidden();
+
+    let buttons = LinearLayout::vertical()
+        .child(Button::new("Play", &play))
+        .child(Button::new("Practice", practice))
+        .child(Button::new("Versus", play_versus))
+        .child(Button::new("Versus Bot", play_versus_bot))
+        .child(Button::new("Watch Bot", watch_bot))
+        .child(Button::new("Finesse Trainer", finesse_trainer))
+        .child(Button::new("Board Editor", board_editor))
+        .child(Button::new("Controls", |s| {
+            controls_menu_popup(s);
+        }))
+        .child(settings_button)
+        .child(get_quit_button());
+    let title_view = OnEventView::new(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(PaddedView::lrtb(0, 0, 1, 1, title_logo_view))
+                .child(buttons)
+                .child(starting_score_container),
+        )
+        .title("Tetrs | Rust Edition"),
+    )
+    .on_event(Event::Key(Key::Esc), |s| {
+        s.quit();
+    });
+    s.add_layer(title_view);
+}
+
+// a game of the mode as the settings stand, callers override what their game does differently
+fn current_board_settings(mode: Mode) -> BoardSettings {
+    let (width, height) = get_board_size();
+    BoardSettings {
+        mode,
+        starting_level: get_starting_level(),
+        ghost_piece_on: get_ghost_piece_on(),
+        high_score: get_high_score(),
+        seed: FIXED_SEED.get().copied(),
+        timing: get_timing(mode),
+        width,
+        height,
+        pieces: get_piece_set(),
+        garbage_style: GarbageStyle::default(),
+    }
+}
+
+// a networked game is played the way the host set it up, only the look is the player's own
+fn match_board_settings(settings: &MatchSettings) -> BoardSettings {
+    let game = settings.game_settings();
+    BoardSettings {
+        mode: game.mode,
+        starting_level: game.starting_level,
+        ghost_piece_on: get_ghost_piece_on(),
+        high_score: get_high_score(),
+        seed: Some(game.seed),
+        timing: game.timing,
+        width: game.width,
+        height: game.height,
+        pieces: game.pieces,
+        garbage_style: game.garbage_style,
+    }
+}
+
+fn play(siv: &mut Cursive) {
+    siv.pop_layer();
+    let settings = current_board_settings(get_mode());
+    let title = format!("Tetrs | {}", settings.mode.title());
+    show_game(siv, Board::new(settings), &title);
+}
+
+// marathon with undo and redo, gravity off and a queue to pick from the pause menu, nothing from it
+// is recorded
+fn practice(siv: &mut Cursive) {
+    siv.pop_layer();
+    let settings = current_board_settings(Mode::Marathon);
+    show_game(siv, Board::practice(settings), "Tetrs | Practice");
+}
+
+// two players on one keyboard, both get the same pieces and the last one standing wins
+fn play_versus(siv: &mut Cursive) {
+    siv.pop_layer();
+    start_versus(siv, false);
+}
+
+// the same with the bot on the right, the player keeps their own keys
+fn play_versus_bot(siv: &mut Cursive) {
+    siv.pop_layer();
+    start_versus(siv, true);
+}
+
+// the bot plays alone with the current settings, nothing it scores is kept
+fn watch_bot(siv: &mut Cursive) {
+    siv.pop_layer();
+    let settings = current_board_settings(get_mode());
+    let mode = settings.mode;
+    match with_bot(Board::new(settings)) {
+        Ok((board, name)) => {
+            let title = format!("Tetrs | {} {}", name, mode.title());
+            show_game(siv, board, &title);
+        }
+        Err(e) => bot_error_popup(siv, &e),
+    }
+}
+
+// placing one piece at a time on a target, the target is where the hints outline goes
+fn finesse_trainer(siv: &mut Cursive) {
+    siv.pop_layer();
+    let settings = current_board_settings(get_mode());
+    show_game(siv, Board::trainer(settings), "Tetrs | Finesse Trainer");
+}
+
+fn board_editor(siv: &mut Cursive) {
+    siv.pop_layer();
+    let (width, height) = get_board_size();
+    show_editor(siv, Editor::new(get_piece_set(), width, height), "", "", "");
+}
+
+// the editor with its palette, the queue and hold to play the position with and a name to save it
+// under
+fn show_editor(siv: &mut Cursive, editor: Editor, queue: &str, hold: &str, name: &str) {
+    let palette = editor.palette().on_select(|s, brush| {
+        let brush = *brush;
+        s.call_on_name(ids::EDITOR, |e: &mut Editor| e.set_brush(brush));
+    });
+    let field = |content: &str, id: &str| EditView::new().content(content).with_name(id);
+    let side = LinearLayout::vertical()
+        .child(TextView::new("Brush"))
+        .child(palette.with_name(ids::EDITOR_PALETTE))
+        .child(DummyView)
+        .child(TextView::new("Queue, the first is in play"))
+        .child(field(queue, ids::EDITOR_QUEUE))
+        .child(TextView::new("Hold"))
+        .child(field(hold, ids::EDITOR_HOLD))
+        .child(TextView::new("Name"))
+        .child(field(name, ids::EDITOR_NAME))
+        .child(DummyView)
+        .child(TextView::new(
+            "Arrows move, space paints, delete erases, [ and ] pick the brush. The mouse paints \
+             with left and erases with right",
+        ))
+        .child(TextView::new("").with_name(ids::EDITOR_STATUS))
+        .fixed_width(30);
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::horizontal()
+                    .child(editor.with_name(ids::EDITOR))
+                    .child(DummyView)
+                    .child(side),
+            )
+            .button("Play From Here", play_from_editor)
+            .button("Save", save_from_editor)
+            .button("Load", load_into_editor_popup)
+            .button("Fumen", editor_fumen_popup)
+            .button("Clear", |s| {
+                s.call_on_name(ids::EDITOR, |e: &mut Editor| e.clear());
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+                show_title_menu(s);
+            })
+            .title("Tetrs | Board Editor"),
+        )
+        .on_event(Event::Key(Key::Esc), |s| {
+            s.pop_layer();
+            show_title_menu(s);
+        }),
+    );
+}
+
+// the position in the editor with its queue and hold
+fn editor_position(s: &mut Cursive) -> Option<Position> {
+    let text = |s: &mut Cursive, id: &str| {
+        s.call_on_name(id, |e: &mut EditView| e.get_content())
+            .unwrap_or_default()
+    };
+    let (queue, hold) = (text(s, ids::EDITOR_QUEUE), text(s, ids::EDITOR_HOLD));
+    s.call_on_name(ids::EDITOR, |e: &mut Editor| e.position(&queue, &hold))
+}
+
+fn set_editor_status(s: &mut Cursive, status: String) {
+    s.call_on_name(ids::EDITOR_STATUS, |t: &mut TextView| t.set_content(status));
+}
+
+// practice from the position, on a board its size with its pieces
+fn play_from_editor(s: &mut Cursive) {
+    let Some(position) = editor_position(s) else {
+        return;
+    };
+    let settings = BoardSettings {
+        width: position.width(),
+        height: position.height(),
+        pieces: Arc::new(position.pieces.clone()),
+        ..current_board_settings(Mode::Marathon)
+    };
+    match Board::from_position(settings, position) {
+        Ok(board) => {
+            s.pop_layer();
+            show_game(s, board, "Tetrs | Practice");
+        }
+        Err(e) => set_editor_status(s, e),
+    }
+}
+
+fn save_from_editor(s: &mut Cursive) {
+    let name = s
+        .call_on_name(ids::EDITOR_NAME, |e: &mut EditView| e.get_content())
+        .unwrap_or_default();
+    let Some(position) = editor_position(s) else {
+        return;
+    };
+    let status = match position.save(name.trim()) {
+        Ok(()) => format!("Saved {}", name.trim()),
+        Err(e) => e,
+    };
+    set_editor_status(s, status);
+}
+
+// the position in the editor as a fumen, or an empty one to paste into when it can't be one
+fn editor_fumen_popup(s: &mut Cursive) {
+    let text = editor_position(s)
+        .and_then(|position| fumen::page_of_position(&position).ok())
+        .map(|page| fumen::encode(&[page]))
+        .unwrap_or_default();
+    fumen_popup(s, &text, true);
+}
+
+// the saved positions to pick one from, it replaces what's in the editor
+fn load_into_editor_popup(s: &mut Cursive) {
+    let names = match position::list() {
+        Ok(names) if names.is_empty() => {
+            set_editor_status(s, "No positions saved yet".to_string());
+            return;
+        }
+        Ok(names) => names,
+        Err(e) => {
+            set_editor_status(s, e.to_string());
+            return;
+        }
+    };
+    let list = SelectView::new()
+        .with_all_str(names)
+        .on_submit(|s, name: &str| {
+            s.pop_layer(); // list
+            let loaded = Position::load(name)
+                .and_then(|position| Ok((Editor::from_position(&position)?, position)));
+            match loaded {
+                Ok((editor, position)) => {
+                    s.pop_layer(); // editor
+                    show_editor(s, editor, &position.queue, &position.hold, name);
+                }
+                Err(e) => set_editor_status(s, e),
+            }
+        })
+        .scrollable();
+    s.add_layer(
+        Dialog::around(list)
+            .dismiss_button("Cancel")
+            .title("Load Position"),
+    );
+}
+
+// the bot from the command line or the settings, a bot program is started for the board here
+fn with_bot(board: Board) -> Result<(Board, String), String> {
+    let bot = get_bot_spec().start(&board.game().settings(), rand::random(), false)?;
+    let name = bot.name();
+    Ok((board.with_bot(bot), name))
+}
+
+fn bot_error_popup(s: &mut Cursive, error: &str) {
+    s.add_layer(
+        Dialog::around(TextView::new(error))
+            .button("Ok", |s| {
+                s.pop_layer();
+                show_title_menu(s);
+            })
+            .title("Bot Problem"),
+    );
+}
+
+// the players are 1 and 2, or 0 (the solo keys) and the bot as 2
+fn start_versus(siv: &mut Cursive, against_bot: bool) {
+    let seed = FIXED_SEED.get().copied().unwrap_or_else(rand::random);
+    let settings = || BoardSettings {
+        seed: Some(seed),
+        // a versus game only ends when someone tops out
+        ..current_board_settings(Mode::Marathon)
+    };
+    let right = Board::versus(settings(), 2);
+    let (players, right, title) = match against_bot {
+        true => match with_bot(right) {
+            Ok((board, name)) => ([0, 2], board, format!("Tetrs | Versus {}", name)),
+            Err(e) => return bot_error_popup(siv, &e),
+        },
+        false => ([1, 2], right, "Tetrs | Versus".to_string()),
+    };
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::horizontal()
+                    .child(player_panels(
+                        Board::versus(settings(), players[0]),
+                        players[0],
+                    ))
+                    .child(DummyView::new())
+                    .child(player_panels(right, 2)),
+            )
+            .title(title),
+        )
+        .on_pre_event_inner(EventTrigger::any(), move |view, event| {
+            route_versus_event(view, event, players)
+        })
+        .on_event(Event::Key(Key::Esc), |s| {
+            pause_menu_popup(s);
+        }),
+    );
+}
+
+// only the focused view gets events, so both boards are handed every refresh and every key here
+// and each one acts on its own keys, then attack is passed across
+fn route_versus_event(
+    view: &mut Dialog,
+    event: &Event,
+    players: [usize; 2],
+) -> Option<EventResult> {
+    if *event == Event::Key(Key::Esc) {
+        return None; // pause menu
+    }
+    let on_board = |view: &mut Dialog, player: usize, f: &mut dyn FnMut(&mut Board)| {
+        view.call_on_name(&ids::for_player(ids::BOARD, player), |b: &mut Board| f(b));
+    };
+    let is_over = |view: &mut Dialog, player: usize| {
+        let mut over = true;
+        on_board(view, player, &mut |b| over = b.is_over());
+        over
+    };
+    // both boards stay as they ended once the game is decided
+    if is_over(view, players[0]) || is_over(view, players[1]) {
+        return Some(EventResult::Consumed(None));
+    }
+    let mut result = EventResult::Ignored;
+    for player in players {
+        on_board(view, player, &mut |b| {
+            let r = b.on_event(event.clone());
+            result = std::mem::replace(&mut result, EventResult::Ignored).and(r);
+        });
+    }
+    let mut sent = [0, 0];
+    for (side, player) in players.into_iter().enumerate() {
+        on_board(view, player, &mut |b| {
+            sent[side] = b.take_outgoing_garbage()
+        });
+    }
+    on_board(view, players[0], &mut |b| b.receive_garbage(sent[1]));
+    on_board(view, players[1], &mut |b| b.receive_garbage(sent[0]));
+
+    let against_bot = players[0] == 0;
+    let winner = match (is_over(view, players[0]), is_over(view, players[1])) {
+        (false, false) => {
+            return match result {
+                EventResult::Ignored => None,
+                result => Some(result),
+            };
+        }
+        (true, true) => None, // topped out together
+        (true, false) => Some(players[1]),
+        (false, true) => Some(players[0]),
+    };
+    Some(result.and(EventResult::with_cb(move |s| {
+        versus_over_popup(s, winner, against_bot);
+    })))
+}
+
+fn versus_over_popup(s: &mut Cursive, winner: Option<usize>, against_bot: bool) {
+    let title = match (winner, against_bot) {
+        (None, _) => "Draw!".to_string(),
+        (Some(0), true) => "You Win!".to_string(),
+        (Some(_), true) => "The Bot Wins!".to_string(),
+        (Some(player), false) => format!("Player {} Wins!", player),
+    };
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(Button::new("Rematch", move |s| {
+                    s.pop_layer();
+                    s.pop_layer();
+                    start_versus(s, against_bot);
+                }))
+                .child(Button::new("Return to Title", |s| {
+                    s.pop_layer();
+                    s.pop_layer();
+                    show_title_menu(s);
+                })),
+        )
+        .title(title),
+    );
+}
+
+// blocks until the other side is there
+fn connect(network: Network) -> Result<(), String> {
+    let session = match network {
+        Network::Host { port } => {
+            let pieces = get_piece_set();
+            if PieceSet::by_name(&pieces.name).as_ref() != Some(&*pieces) {
+                return Err(format!(
+                    "networked games can only use a built in piece set, not {}",
+                    pieces.name
+                ));
+            }
+            let settings = MatchSettings {
+                seed: FIXED_SEED.get().copied().unwrap_or_else(rand::random),
+                starting_level: get_starting_level(),
+                width: get_board_size().0,
+                height: get_board_size().1,
+                pieces: pieces.name.clone(),
+                garbage_style: GarbageStyle::default(),
+            };
+            let listener = TcpListener::bind(("0.0.0.0", port))
+                .map_err(|e| format!("could not listen on port {}: {}", port, e))?;
+            println!("tetrs: waiting for someone to join on port {}...", port);
+            Session::host(listener, settings)
+                .map_err(|e| format!("could not start the game: {}", e))?
+        }
+        Network::Join { addr } => {
+            println!("tetrs: joining {}...", addr);
+            Session::join(addr.as_str()).map_err(|e| format!("could not join {}: {}", addr, e))?
+        }
+        Network::Watch { addr } => {
+            println!("tetrs: waiting for the game at {} to start...", addr);
+            let spectator = Spectator::watch(addr.as_str())
+                .map_err(|e| format!("could not watch {}: {}", addr, e))?;
+            *SPECTATOR.lock().unwrap() = Some(spectator);
+            return Ok(());
+        }
+        Network::Battle { addr, name } => {
+            println!("tetrs: entering the battle at {}...", addr);
+            let client = RoyaleClient::enter(addr.as_str(), &name)
+                .map_err(|e| format!("could not enter {}: {}", addr, e))?;
+            *ROYALE.lock().unwrap() = Some(client);
+            return Ok(());
+        }
+    };
+    *SESSION.lock().unwrap() = Some(session);
+    Ok(())
+}
+
+fn leave_session() {
+    if let Some(mut session) = SESSION.lock().unwrap().take() {
+        session.leave();
+    }
+    if let Some(mut client) = ROYALE.lock().unwrap().take() {
+        client.leave();
+    }
+}
+
+// the local board next to the other side's
+fn show_online_game(siv: &mut Cursive) {
+    let Some(settings) = SESSION
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|s| s.settings().clone())
+    else {
+        return;
+    };
+    let board = Board::versus(match_board_settings(&settings), 0);
+    let opponent = Dialog::around(
+        LinearLayout::vertical()
+            .child(
+                TextView::new("Waiting...")
+                    .center()
+                    .with_name(ids::OPPONENT_STATS),
+            )
+            .child(
+                LinearLayout::horizontal()
+                    .child(GarbageMeter::new(settings.height).with_name(ids::OPPONENT_METER))
+                    .child(
+                        OpponentView::new(settings.width, settings.height).with_name(ids::OPPONENT),
+                    ),
+            ),
+    )
+    .title("Opponent");
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::horizontal()
+                    .child(player_panels(board, 0))
+                    .child(DummyView::new())
+                    .child(opponent),
+            )
+            .title("Tetrs | Online Versus"),
+        )
+        .on_pre_event_inner(EventTrigger::any(), route_online_event)
+        .on_event(Event::Key(Key::Esc), |s| {
+            pause_menu_popup(s);
+        }),
+    );
+}
+
+// like a local versus game, except the other board is across the connection
+fn route_online_event(view: &mut Dialog, event: &Event) -> Option<EventResult> {
+    if *event == Event::Key(Key::Esc) {
+        return None; // pause menu
+    }
+    let mut session = SESSION.lock().unwrap();
+    let session = session.as_mut()?;
+    let is_over = |view: &mut Dialog| {
+        view.call_on_name(ids::BOARD, |b: &mut Board| b.is_over())
+            .unwrap_or(true)
+    };
+    // the board stays as it ended once the game is decided
+    if is_over(view) || session.opponent_over() || session.is_closed() {
+        return Some(EventResult::Consumed(None));
+    }
+    let result = view.call_on_name(ids::BOARD, |b: &mut Board| {
+        let result = b.on_event(event.clone());
+        let attack = b.take_outgoing_garbage();
+        for lines in session.exchange(b.game(), attack) {
+            b.receive_garbage(lines);
+        }
+        result
+    })?;
+    let opponent = session.opponent().cloned();
+    if let Some(snapshot) = opponent.as_ref() {
+        let stats = format!(
+            "Score {}  Lines {}",
+            snapshot.stats.score, snapshot.stats.lines
+        );
+        view.call_on_name(ids::OPPONENT_STATS, |t: &mut TextView| t.set_content(stats));
+        view.call_on_name(ids::OPPONENT_METER, |m: &mut GarbageMeter| {
+            m.set_pending(snapshot.stats.pending)
+        });
+    }
+    view.call_on_name(ids::OPPONENT, |o: &mut OpponentView| {
+        o.set_snapshot(opponent)
+    });
+
+    let outcome = match (is_over(view), session.opponent_over(), session.is_closed()) {
+        (true, _, _) => "You Lose!",
+        (false, true, _) => "You Win!",
+        (false, false, true) => "Opponent Left",
+        (false, false, false) => {
+            return match result {
+                EventResult::Ignored => None,
+                result => Some(result),
+            };
+        }
+    };
+    let can_rematch = !session.is_closed();
+    Some(result.and(EventResult::with_cb(move |s| {
+        online_over_popup(s, outcome, can_rematch);
+    })))
+}
+
+fn online_over_popup(s: &mut Cursive, title: &str, can_rematch: bool) {
+    let mut buttons = LinearLayout::vertical();
+    if can_rematch {
+        buttons.add_child(Button::new("Rematch", |s| {
+            if let Some(session) = SESSION.lock().unwrap().as_mut() {
+                session.ask_rematch();
+            }
+            s.pop_layer();
+            rematch_popup(s);
+        }));
+    }
+    buttons.add_child(Button::new("Return to Title", |s| {
+        leave_session();
+        s.pop_layer();
+        s.pop_layer();
+        show_title_menu(s);
+    }));
+    s.add_layer(Dialog::around(buttons).title(title));
+}
+
+// waits for the other side to want a rematch too, checking on every refresh
+fn rematch_popup(s: &mut Cursive) {
+    s.add_layer(
+        OnEventView::new(
+            Dialog::around(TextView::new("Waiting for the other player..."))
+                .button("Give Up", |s| {
+                    leave_session();
+                    s.pop_layer();
+                    s.pop_layer();
+                    show_title_menu(s);
+                })
+                .title("Rematch"),
+        )
+        .on_pre_event(Event::Refresh, |s| {
+            let (next_game, closed) = match SESSION.lock().unwrap().as_mut() {
+                Some(session) => {
+                    session.poll();
+                    (session.take_next_game(), session.is_closed())
+                }
+                None => (None, true),
+            };
+            if next_game.is_some() {
+                s.pop_layer();
+                s.pop_layer();
+                show_online_game(s);
+            } else if closed {
+                s.pop_layer();
+                online_over_popup(s, "Opponent Left", false);
+            }
+        }),
+    );
+}
+
+// both boards of a game someone is hosting, nothing here can be touched
+fn show_spectator(siv: &mut Cursive) {
+    let Some(settings) = SPECTATOR
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|s| s.settings().clone())
+    else {
+        return;
+    };
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::vertical()
+                    .child(
+                        TextView::new("Live")
+                            .center()
+                            .with_name(ids::SPECTATOR_STATUS),
+                    )
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(spectated_panels(&settings, 1))
+                            .child(DummyView::new())
+                            .child(spectated_panels(&settings, 2)),
+                    ),
+            )
+            .title("Tetrs | Spectating")
+            .button("Stop Watching", |s| s.quit()),
+        )
+        .on_pre_event(Event::Refresh, update_spectator)
+        .on_event(Event::Key(Key::Esc), |s| s.quit()),
+    );
+}
+
+// views of the next queue, the next piece first
+const SPECTATED_NEXT: [&str; 4] = [
+    ids::NEXT_PIECE,
+    ids::PIECE_IN_2,
+    ids::PIECE_IN_3,
+    ids::PIECE_IN_4,
+];
+
+// one side of a game being watched, drawn small so both fit
+fn spectated_panels(settings: &MatchSettings, player: usize) -> Dialog {
+    let id = |id: &str| ids::for_player(id, player);
+    let preview_size = PieceSet::by_name(&settings.pieces)
+        .unwrap_or_default()
+        .preview_size();
+    let preview = |name: String| {
+        let mut view = PieceView::new(preview_size);
+        view.set_scale(false);
+        view.with_name(name)
+    };
+    let mut next = LinearLayout::vertical().child(TextView::new("Next").center());
+    for name in SPECTATED_NEXT {
+        next.add_child(preview(id(name)));
+    }
+    Dialog::around(
+        LinearLayout::vertical()
+            .child(
+                TextView::new("Score 0  Lines 0  Level 1")
+                    .center()
+                    .with_name(id(ids::OPPONENT_STATS)),
+            )
+            .child(
+                LinearLayout::horizontal()
+                    .child(
+                        LinearLayout::vertical()
+                            .child(TextView::new("Hold").center())
+                            .child(preview(id(ids::HELD_PIECE))),
+                    )
+                    .child(DummyView::new())
+                    .child(GarbageMeter::new(settings.height).with_name(id(ids::OPPONENT_METER)))
+                    .child(
+                        OpponentView::new(settings.width, settings.height)
+                            .with_name(id(ids::OPPONENT)),
+                    )
+                    .child(DummyView::new())
+                    .child(next),
+            ),
+    )
+    .title(match player {
+        1 => "Player 1 (Host)",
+        _ => "Player 2",
+    })
+}
+
+// takes in whatever the host sent and redraws both sides
+fn update_spectator(s: &mut Cursive) {
+    let mut spectator = SPECTATOR.lock().unwrap();
+    let Some(spectator) = spectator.as_mut() else {
+        return;
+    };
+    spectator.poll();
+    let set = PieceSet::by_name(&spectator.settings().pieces).unwrap_or_default();
+    // kinds the set doesn't have are left empty rather than trusted
+    let piece = |kind: usize| (kind < set.len()).then(|| Piece::new(&set, kind));
+    for player in 1..=2 {
+        let id = |id: &str| ids::for_player(id, player);
+        let board = spectator.board(player).clone();
+        let stats = format!(
+            "Score {}  Lines {}  Level {}",
+            board.stats.score, board.stats.lines, board.stats.level
+        );
+        s.call_on_name(&id(ids::OPPONENT_STATS), |t: &mut TextView| {
+            t.set_content(stats)
+        });
+        s.call_on_name(&id(ids::HELD_PIECE), |v: &mut PieceView| {
+            v.set_piece_optional(board.stats.held.and_then(&piece))
+        });
+        for (i, name) in SPECTATED_NEXT.iter().enumerate() {
+            let kind = board.stats.next.get(i).copied();
+            s.call_on_name(&id(name), |v: &mut PieceView| {
+                v.set_piece_optional(kind.and_then(&piece))
+            });
+        }
+        s.call_on_name(&id(ids::OPPONENT_METER), |m: &mut GarbageMeter| {
+            m.set_pending(board.stats.pending)
+        });
+        s.call_on_name(&id(ids::OPPONENT), |o: &mut OpponentView| {
+            o.set_snapshot(Some(board))
+        });
+    }
+    let status = match (spectator.winner(), spectator.is_closed()) {
+        (Some(0), _) => "Nobody Won".to_string(),
+        (Some(winner), _) => format!("Player {} Wins!", winner),
+        (None, true) => "The Host Left".to_string(),
+        (None, false) => "Live".to_string(),
+    };
+    s.call_on_name(ids::SPECTATOR_STATUS, |t: &mut TextView| {
+        t.set_content(status)
+    });
+}
+
+// everyone on the server, the game starts once they're all ready
+fn show_royale_lobby(s: &mut Cursive) {
+    let targeting = ROYALE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|c| c.targeting())
+        .unwrap_or_default();
+    let targeting_button =
+        Button::new("Change Targeting", |s| {
+            let mut targeting_dialog = Dialog::around(TextView::new("Make selection:").center());
+            for targeting in Targeting::ALL {
+                targeting_dialog = targeting_dialog.button(targeting.title(), move |s| {
+                    if let Some(client) = ROYALE.lock().unwrap().as_mut() {
+                        client.set_targeting(targeting);
+                    }
+                    s.call_on_name(ids::TARGETING_PREVIEW, |t: &mut TextView| {
+                        t.set_content(format!(" {}", targeting.title()))
+                    });
+                    s.pop_layer();
+                });
+            }
+            s.add_layer(
+                OnEventView::new(targeting_dialog.title("Select Targeting | ESC to close"))
+                    .on_event(Event::Key(Key::Esc), |s| {
+                        s.pop_layer();
+                    }),
+            );
+        });
+    s.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::vertical()
+                    .child(TextView::new("").with_name(ids::ROYALE_LOBBY))
+                    .child(DummyView::new())
+                    .child(
+                        LinearLayout::horizontal().child(targeting_button).child(
+                            TextView::new(format!(" {}", targeting.title()))
+                                .with_name(ids::TARGETING_PREVIEW),
+                        ),
+                    )
+                    .child(Button::new("Ready", toggle_royale_ready))
+                    .child(Button::new("Return to Title", |s| {
+                        leave_session();
+                        s.pop_layer();
+                        show_title_menu(s);
+                    })),
+            )
+            .title("Tetrs | Battle Royale"),
+        )
+        .on_pre_event(Event::Refresh, update_royale_lobby),
+    );
+}
+
+fn toggle_royale_ready(_s: &mut Cursive) {
+    let mut client = ROYALE.lock().unwrap();
+    let Some(client) = client.as_mut() else {
+        return;
+    };
+    let ready = client
+        .lobby()
+        .iter()
+        .any(|e| e.id == client.you() && e.seat == Seat::Ready);
+    client.set_ready(!ready);
+}
+
+fn update_royale_lobby(s: &mut Cursive) {
+    let mut client = ROYALE.lock().unwrap();
+    let Some(client) = client.as_mut() else {
+        return;
+    };
+    client.poll();
+    if client.is_closed() {
+        s.add_layer(
+            Dialog::around(TextView::new("The server went away."))
+                .button("Return to Title", |s| {
+                    leave_session();
+                    s.pop_layer();
+                    s.pop_layer();
+                    show_title_menu(s);
+                })
+                .title("Disconnected"),
+        );
+        return;
+    }
+    if let Some(settings) = client.take_next_game() {
+        s.pop_layer();
+        show_royale_game(s, client, &settings);
+        return;
+    }
+    let mut text = format!("Players ({})\n", client.lobby().len());
+    for entry in client.lobby() {
+        let you = match entry.id == client.you() {
+            true => " (you)",
+            false => "",
+        };
+        text += &format!(
+            "  {:<22}{}\n",
+            format!("{}{}", entry.name, you),
+            entry.seat.name()
+        );
+    }
+    text += match client.lobby().iter().any(|e| e.seat == Seat::Playing) {
+        true => "\nA game is going on, you're in the next one",
+        false => "\nThe game starts once at least two players are all ready",
+    };
+    s.call_on_name(ids::ROYALE_LOBBY, |t: &mut TextView| t.set_content(text));
 }
 
-pub fn run() {
-    let mut siv = CursiveRunnable::default();
-    let mut theme = siv.current_theme().clone();
-    theme.palette = cursive::theme::Palette::retro();
-    siv.set_theme(theme);
+// how many other boards go in a row beside the local one
+const MINI_BOARDS_PER_ROW: usize = 4;
 
-    // fetch high score from disk
-    set_high_score(get_high_score_from_disk());
-    // init title menu
-    show_title_menu(&mut siv);
-    // play music on seperate audio thread
-    let _ = audio::play(audio::THEME_FAST, true);
-    // init cursive
-    const FPS: u32 = 60;
-    siv.set_fps(FPS);
-    siv.run();
-    // save highscore on program close
-    save_high_score_to_disk(get_high_score());
+// the local board between everyone else's, half on each side
+fn show_royale_game(s: &mut Cursive, client: &RoyaleClient, settings: &MatchSettings) {
+    let board = Board::versus(match_board_settings(settings), 0);
+    let others: Vec<usize> = client
+        .roster()
+        .iter()
+        .copied()
+        .filter(|id| *id != client.you())
+        .collect();
+    let (left, right) = others.split_at(others.len().div_ceil(2));
+    let grid = |ids: &[usize]| {
+        let mut grid = LinearLayout::vertical();
+        for row in ids.chunks(MINI_BOARDS_PER_ROW) {
+            let mut line = LinearLayout::horizontal();
+            for id in row {
+                let mut view = MiniBoardView::new(settings.width, settings.height);
+                view.set_label(client.name(*id).to_string());
+                line.add_child(PaddedView::lrtb(
+                    1,
+                    1,
+                    0,
+                    1,
+                    view.with_name(ids::for_player(ids::MINI_BOARD, *id)),
+                ));
+            }
+            grid.add_child(line);
+        }
+        grid
+    };
+    s.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::vertical()
+                    .child(TextView::new("").center().with_name(ids::ROYALE_STATUS))
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(grid(left))
+                            .child(player_panels(board, 0))
+                            .child(grid(right)),
+                    ),
+            )
+            .title("Tetrs | Battle Royale"),
+        )
+        .on_pre_event_inner(EventTrigger::any(), route_royale_event)
+        .on_event(Event::Key(Key::Esc), |s| {
+            pause_menu_popup(s);
+        }),
+    );
 }
 
-pub fn show_title_menu(s: &mut Cursive) {
-    use cursive::theme::Color;
-    let title_logo_view = LinearLayout::horizontal()
-        .child(TextView::new(text_art::TETRS_T).style(Color::Dark(BaseColor::Red)))
-        .child(TextView::new(text_art::TETRS_E).style(Color::Dark(BaseColor::Yellow)))
-        .child(TextView::new(text_art::TETRS_T).style(Color::Dark(BaseColor::Green)))
-        .child(TextView::new(text_art::TETRS_R).style(Color::Dark(BaseColor::Cyan)))
-        .child(TextView::new(text_art::TETRS_S).style(Color::Dark(BaseColor::Magenta)));
+// the local board plays on while the server decides what happens to it
+fn route_royale_event(view: &mut Dialog, event: &Event) -> Option<EventResult> {
+    if *event == Event::Key(Key::Esc) {
+        return None; // pause menu
+    }
+    let mut client = ROYALE.lock().unwrap();
+    let client = client.as_mut()?;
+    if client.winner().is_some() {
+        return Some(EventResult::Consumed(None));
+    }
+    if *event == Event::Key(Key::Tab) {
+        client.set_targeting(client.targeting().next());
+        update_royale_status(view, client);
+        return Some(EventResult::Consumed(None));
+    }
+    let was_out = client.place(client.you()).is_some();
+    let result = view.call_on_name(ids::BOARD, |b: &mut Board| {
+        let result = b.on_event(event.clone());
+        // the server works out the attack from the inputs itself
+        b.take_outgoing_garbage();
+        for lines in client.exchange(b) {
+            b.receive_garbage(lines);
+        }
+        result
+    })?;
+    for id in client.roster().to_vec() {
+        if id == client.you() {
+            continue;
+        }
+        let marker = match (client.target() == id, client.attackers().contains(&id)) {
+            (true, true) => "<>",
+            (true, false) => "> ",
+            (false, true) => "< ",
+            (false, false) => "",
+        };
+        let label = match (client.place(id), client.badges(id)) {
+            (Some(place), _) => format!("#{} {}", place, client.name(id)),
+            (None, 0) => format!("{}{}", marker, client.name(id)),
+            (None, badges) => format!("{}{} *{}", marker, client.name(id), badges),
+        };
+        let snapshot = client.board(id).cloned();
+        let out = client.place(id).is_some();
+        view.call_on_name(
+            &ids::for_player(ids::MINI_BOARD, id),
+            |m: &mut MiniBoardView| {
+                m.set_label(label);
+                m.set_snapshot(snapshot);
+                m.set_out(out);
+            },
+        );
+    }
+    update_royale_status(view, client);
 
-    let settings_button = get_settings_button();
-    // settings holder
-    let starting_score_container =
-        HideableView::new(TextView::new("1").with_name(ids::STARTING_LEVEL)).hidden();
+    if let Some(winner) = client.winner() {
+        let title = match winner == client.you() {
+            true => "You Win!",
+            false => "Game Over",
+        };
+        let standings: Vec<String> = client
+            .standings()
+            .into_iter()
+            .map(|(place, id)| format!("{:>2}. {}", place, client.name(id)))
+            .collect();
+        let standings = standings.join("\n");
+        return Some(result.and(EventResult::with_cb(move |s| {
+            royale_over_popup(s, title, &standings);
+        })));
+    }
+    if client.is_closed() {
+        return Some(result.and(EventResult::with_cb(|s| {
+            royale_over_popup(s, "Disconnected", "The server went away.");
+        })));
+    }
+    match (was_out, client.place(client.you())) {
+        (false, Some(place)) => Some(result.and(EventResult::with_cb(move |s| {
+            s.add_layer(
+                Dialog::around(TextView::new(format!("You placed #{}", place)))
+                    .button("Keep Watching", |s| {
+                        s.pop_layer();
+                    })
+                    .title("Knocked Out"),
+            );
+        }))),
+        _ => match result {
+            EventResult::Ignored => None,
+            result => Some(result),
+        },
+    }
+}
 
-    let buttons = LinearLayout::vertical()
-        .child(Button::new("Play", &play))
-        .child(Button::new("Controls", |s| {
-            controls_menu_popup(s);
-        }))
-        .child(settings_button)
-        .child(get_quit_button());
-    let title_view = OnEventView::new(
+fn update_royale_status(view: &mut Dialog, client: &RoyaleClient) {
+    let status = format!(
+        "Targeting {} (Tab)  Badges {}  Attacked by {}  Alive {}/{}",
+        client.targeting().title(),
+        client.badges(client.you()),
+        client.attackers().len(),
+        client.alive(),
+        client.roster().len()
+    );
+    view.call_on_name(ids::ROYALE_STATUS, |t: &mut TextView| t.set_content(status));
+}
+
+fn royale_over_popup(s: &mut Cursive, title: &str, text: &str) {
+    let closed = ROYALE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_none_or(|c| c.is_closed());
+    let mut buttons = LinearLayout::vertical();
+    if !closed {
+        buttons.add_child(Button::new("Back to Lobby", |s| {
+            s.pop_layer();
+            s.pop_layer();
+            show_royale_lobby(s);
+        }));
+    }
+    buttons.add_child(Button::new("Return to Title", |s| {
+        leave_session();
+        s.pop_layer();
+        s.pop_layer();
+        show_title_menu(s);
+    }));
+    s.add_layer(
         Dialog::around(
             LinearLayout::vertical()
-                .child(PaddedView::lrtb(0, 0, 1, 1, title_logo_view))
-                .child(buttons)
-                .child(starting_score_container),
+                .child(TextView::new(text))
+                .child(DummyView::new())
+                .child(buttons),
         )
-        .title("Tetrs | Rust Edition"),
-    )
-    .on_event(Event::Key(Key::Esc), |s| {
-        s.quit();
-    });
-    s.add_layer(title_view);
+        .title(title),
+    );
 }
 
-fn play(siv: &mut Cursive) {
-    siv.pop_layer();
+fn watch_replay(siv: &mut Cursive, replay: Replay) {
+    let title = format!("Tetrs | {} Replay", replay.mode.title());
+    show_game(
+        siv,
+        Board::from_replay(replay, get_ghost_piece_on()),
+        &title,
+    );
+}
+
+// lays out the board with its side panels
+fn show_game(siv: &mut Cursive, board: Board, title: &str) {
+    siv.add_layer(
+        OnEventView::new(Dialog::around(player_panels(board, 0)).title(title)).on_event(
+            Event::Key(Key::Esc),
+            |s| {
+                pause_menu_popup(s);
+            },
+        ),
+    );
+}
+
+// a board with its stats, previews and garbage meter, named for the player they belong to
+fn player_panels(board: Board, player: usize) -> LinearLayout {
+    let id = |id: &str| ids::for_player(id, player);
+    let preview_size = board.preview_size();
+    let rows = board.get_settings().height;
     let high_score_label = TextView::new("High Score")
         .center()
         .style(Effect::Underline);
-    let high_score = TextView::new("00000").center().with_name(ids::HIGH_SCORE);
+    let high_score = TextView::new("00000")
+        .center()
+        .with_name(id(ids::HIGH_SCORE));
     let score_label = TextView::new("Score").center().style(Effect::Underline);
-    let score = TextView::new("00000").center().with_name(ids::SCORE);
+    let score = TextView::new("00000").center().with_name(id(ids::SCORE));
 
     let lines_label = TextView::new("Lines").center().style(Effect::Underline);
-    let lines = TextView::new("00000").center().with_name(ids::LINES);
+    let lines = TextView::new("00000").center().with_name(id(ids::LINES));
     let level_label = TextView::new("Level").center().style(Effect::Underline);
-    let level = TextView::new("00000").center().with_name(ids::LEVEL);
+    let level = TextView::new("00000").center().with_name(id(ids::LEVEL));
 
     let score_view = Dialog::around(
         LinearLayout::vertical()
@@ -129,29 +1449,35 @@ fn play(siv: &mut Cursive) {
             .child(level_label)
             .child(level),
     );
-    let action_bubble =
-        Dialog::around(TextView::new("...").center().with_name(ids::ACTION)).title("Last Action");
+    let action_bubble = Dialog::around(TextView::new("...").center().with_name(id(ids::ACTION)))
+        .title("Last Action");
     let right_stack = Dialog::around(
         LinearLayout::vertical()
             .child(score_view)
-            .child(Dialog::around(PieceView::new().with_name(ids::NEXT_PIECE)).title("Next Piece"))
+            .child(
+                Dialog::around(PieceView::new(preview_size).with_name(id(ids::NEXT_PIECE)))
+                    .title("Next Piece"),
+            )
             .child(
                 HideableView::new(
-                    Dialog::around(PieceView::new().with_name(ids::PIECE_IN_2)).title("In 2"),
+                    Dialog::around(PieceView::new(preview_size).with_name(id(ids::PIECE_IN_2)))
+                        .title("In 2"),
                 )
-                .with_name(ids::HIDE_IN_2),
+                .with_name(id(ids::HIDE_IN_2)),
             )
             .child(
                 HideableView::new(
-                    Dialog::around(PieceView::new().with_name(ids::PIECE_IN_3)).title("In 3"),
+                    Dialog::around(PieceView::new(preview_size).with_name(id(ids::PIECE_IN_3)))
+                        .title("In 3"),
                 )
-                .with_name(ids::HIDE_IN_3),
+                .with_name(id(ids::HIDE_IN_3)),
             )
             .child(
                 HideableView::new(
-                    Dialog::around(PieceView::new().with_name(ids::PIECE_IN_4)).title("In 4"),
+                    Dialog::around(PieceView::new(preview_size).with_name(id(ids::PIECE_IN_4)))
+                        .title("In 4"),
                 )
-                .with_name(ids::HIDE_IN_4),
+                .with_name(id(ids::HIDE_IN_4)),
             ),
     )
     .title(" menu [esc] ")
@@ -162,41 +1488,50 @@ fn play(siv: &mut Cursive) {
         .child(
             TextView::new("00:00")
                 .style(Effect::Underline)
-                .with_name(ids::ELAPSED),
+                .with_name(id(ids::ELAPSED)),
         );
     let singles_view = LinearLayout::horizontal()
         .child(TextView::new("Singles: ").style(Effect::Underline))
         .child(
             TextView::new("0")
                 .style(Effect::Underline)
-                .with_name(ids::SINGLES),
+                .with_name(id(ids::SINGLES)),
         );
     let doubles_view = LinearLayout::horizontal()
         .child(TextView::new("Doubles: ").style(Effect::Underline))
         .child(
             TextView::new("0")
                 .style(Effect::Underline)
-                .with_name(ids::DOUBLES),
+                .with_name(id(ids::DOUBLES)),
         );
     let triples_view = LinearLayout::horizontal()
         .child(TextView::new("Triples: ").style(Effect::Underline))
         .child(
             TextView::new("0")
                 .style(Effect::Underline)
-                .with_name(ids::TRIPLES),
+                .with_name(id(ids::TRIPLES)),
         );
     let tetrses_view = LinearLayout::horizontal()
         .child(TextView::new("Tetrses: ").style(Effect::Underline))
         .child(
             TextView::new("0")
                 .style(Effect::Underline)
-                .with_name(ids::TETRSES),
+                .with_name(id(ids::TETRSES)),
+        );
+
+    // presses beyond the fewest that would have placed the pieces the same way
+    let finesse_view = LinearLayout::horizontal()
+        .child(TextView::new("Finesse: ").style(Effect::Underline))
+        .child(
+            TextView::new("0")
+                .style(Effect::Underline)
+                .with_name(id(ids::FINESSE)),
         );
 
     let tetrs_rate = TextView::new("0%")
         .center()
         .style(Effect::Underline)
-        .with_name(ids::TETRS_RATE);
+        .with_name(id(ids::TETRS_RATE));
 
     let stats_view = Dialog::around(
         LinearLayout::vertical()
@@ -204,82 +1539,330 @@ fn play(siv: &mut Cursive) {
             .child(singles_view)
             .child(doubles_view)
             .child(triples_view)
-            .child(tetrses_view),
+            .child(tetrses_view)
+            .child(finesse_view),
     )
     .title("Stats");
 
     let left_stack = Dialog::around(
         LinearLayout::vertical()
-            .child(Dialog::around(PieceView::new().with_name(ids::HELD_PIECE)).title("hold [c]"))
+            .child(
+                Dialog::around(PieceView::new(preview_size).with_name(id(ids::HELD_PIECE))).title(
+                    format!(
+                        "hold [{}]",
+                        get_player_key_bindings(player).describe(Action::Hold)
+                    ),
+                ),
+            )
             .child(action_bubble)
             .child(stats_view)
-            .child(Dialog::around(tetrs_rate).title("Tetrs Rate")),
+            .child(Dialog::around(tetrs_rate).title("Tetrs Rate"))
+            .child(
+                HideableView::new(
+                    Dialog::around(TextView::new("").with_name(id(ids::HINTS))).title("Hints"),
+                )
+                .hidden()
+                .with_name(id(ids::HIDE_HINTS)),
+            ),
     );
 
-    let settings = BoardSettings {
-        starting_level: get_starting_level(),
-        ghost_piece_on: get_ghost_piece_on(),
-        high_score: get_high_score(),
+    LinearLayout::horizontal()
+        .child(left_stack)
+        .child(DummyView::new())
+        .child(GarbageMeter::new(rows).with_name(id(ids::GARBAGE_METER)))
+        .child(board.with_name(id(ids::BOARD)))
+        .child(DummyView::new())
+        .child(right_stack)
+}
+
+// helprs
+fn pause_menu_popup(s: &mut Cursive) {
+    let mut buttons = LinearLayout::vertical()
+        .child(Button::new("Resume", |s| {
+            s.pop_layer();
+        }))
+        .child(Button::new("Controls", |s| {
+            controls_menu_popup(s);
+        }));
+    let practicing = s
+        .call_on_name(ids::BOARD, |b: &mut Board| b.practice_setup().is_some())
+        .unwrap_or(false);
+    if practicing {
+        buttons.add_child(Button::new("Practice Setup", practice_setup_popup));
+    }
+    let page = s
+        .call_on_name(ids::BOARD, |b: &mut Board| b.page())
+        .flatten();
+    if let Some((page, count)) = page {
+        buttons.add_child(TextView::new(format!("Page {} of {}", page + 1, count)).center());
+        buttons.add_child(Button::new("Previous Page", |s| turn_page(s, -1)));
+        buttons.add_child(Button::new("Next Page", |s| turn_page(s, 1)));
+    }
+    buttons.add_child(Button::new("Export Fumen", export_fumen_popup));
+    buttons.add_child(Button::new("Return to Title", |s| {
+        leave_session();
+        s.pop_layer();
+        s.pop_layer();
+        show_title_menu(s);
+    }));
+    s.add_layer(
+        OnEventView::new(Dialog::around(buttons).title("Pause Menu")).on_event(
+            Event::Key(Key::Esc),
+            |s| {
+                s.pop_layer();
+            },
+        ),
+    );
+}
+// the pieces a practice game deals and its gravity, taken up from the next piece
+fn practice_setup_popup(s: &mut Cursive) {
+    let Some(Some(setup)) = s.call_on_name(ids::BOARD, |b: &mut Board| b.practice_setup()) else {
+        return;
     };
-    let board = Board::new(settings);
-    siv.add_layer(
-        OnEventView::new(
-            Dialog::around(
-                LinearLayout::horizontal()
-                    .child(left_stack)
-                    .child(DummyView::new())
-                    .child(board.with_name(ids::BOARD))
-                    .child(DummyView::new())
-                    .child(right_stack),
-            )
-            .title("Tetrs"),
+    let checkbox = |label: &str, checked: bool, id: &str| {
+        LinearLayout::horizontal()
+            .child(Checkbox::new().with_checked(checked).with_name(id))
+            .child(TextView::new(format!(" {}", label)))
+    };
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "Next pieces, like TIJLOSZ (empty for random)",
+                ))
+                .child(
+                    EditView::new()
+                        .content(setup.queue)
+                        .on_submit(|s, _| apply_practice_setup(s))
+                        .with_name(ids::PRACTICE_QUEUE),
+                )
+                .child(checkbox(
+                    "Repeat them instead of random pieces after",
+                    setup.repeat,
+                    ids::PRACTICE_REPEAT,
+                ))
+                .child(checkbox("Gravity", setup.gravity, ids::PRACTICE_GRAVITY))
+                .child(TextView::new("").with_name(ids::PRACTICE_ERROR)),
         )
-        .on_event(Event::Key(Key::Esc), |s| {
-            pause_menu_popup(s);
-        }),
+        .button("Apply", apply_practice_setup)
+        .dismiss_button("Cancel")
+        .title("Practice Setup"),
     );
 }
 
-// helprs
-fn pause_menu_popup(s: &mut Cursive) {
+// back to the game when the queue makes sense for the piece set
+fn apply_practice_setup(s: &mut Cursive) {
+    let queue = s
+        .call_on_name(ids::PRACTICE_QUEUE, |e: &mut EditView| e.get_content())
+        .unwrap_or_default();
+    let checked = |s: &mut Cursive, id: &str| {
+        s.call_on_name(id, |c: &mut Checkbox| c.is_checked())
+            .unwrap_or(false)
+    };
+    let setup = PracticeSetup {
+        queue: queue.to_string(),
+        repeat: checked(s, ids::PRACTICE_REPEAT),
+        gravity: checked(s, ids::PRACTICE_GRAVITY),
+    };
+    match s.call_on_name(ids::BOARD, |b: &mut Board| b.set_practice_setup(setup)) {
+        Some(Err(e)) => {
+            s.call_on_name(ids::PRACTICE_ERROR, |t: &mut TextView| t.set_content(e));
+        }
+        _ => {
+            s.pop_layer(); // setup
+            s.pop_layer(); // pause menu
+        }
+    }
+}
+
+// back to the game on the page before or after
+fn turn_page(s: &mut Cursive, step: isize) {
+    if let Some(Err(e)) = s.call_on_name(ids::BOARD, |b: &mut Board| b.turn_page(step)) {
+        s.add_layer(Dialog::info(e).title("Fumen Problem"));
+        return;
+    }
+    s.pop_layer(); // pause menu
+}
+
+// the board as it is now, with the piece in play, to copy out
+fn export_fumen_popup(s: &mut Cursive) {
+    let page = s
+        .call_on_name(ids::BOARD, |b: &mut Board| fumen::page_of(b.game()))
+        .unwrap_or_else(|| Err("there's no board".to_string()));
+    match page {
+        Ok(page) => fumen_popup(s, &fumen::encode(&[page]), false),
+        Err(e) => s.add_layer(Dialog::info(e).title("Fumen Problem")),
+    }
+}
+
+// a fumen to copy out or paste in, which can be played through or edited when it's from the editor
+fn fumen_popup(s: &mut Cursive, text: &str, from_editor: bool) {
+    let mut dialog = Dialog::around(
+        LinearLayout::vertical()
+            .child(
+                EditView::new()
+                    .content(text)
+                    .with_name(ids::FUMEN)
+                    .fixed_width(60),
+            )
+            .child(TextView::new("").with_name(ids::FUMEN_ERROR)),
+    )
+    .title("Fumen");
+    if from_editor {
+        dialog.add_button("Practice", practice_fumen);
+        dialog.add_button("Edit", edit_fumen);
+    }
+    s.add_layer(dialog.dismiss_button("Close"));
+}
+
+// the fumen in the popup as positions, or why it isn't one
+fn pasted_fumen(s: &mut Cursive) -> Option<Vec<Position>> {
+    let text = s
+        .call_on_name(ids::FUMEN, |e: &mut EditView| e.get_content())
+        .unwrap_or_default();
+    match fumen::decode(&text).and_then(|pages| fumen::positions(&pages)) {
+        Ok(positions) => Some(positions),
+        Err(e) => {
+            s.call_on_name(ids::FUMEN_ERROR, |t: &mut TextView| t.set_content(e));
+            None
+        }
+    }
+}
+
+// every page of the fumen in turn, from the first
+fn practice_fumen(s: &mut Cursive) {
+    let Some(positions) = pasted_fumen(s) else {
+        return;
+    };
+    let first = &positions[0];
+    let settings = BoardSettings {
+        width: first.width(),
+        height: first.height(),
+        pieces: Arc::new(first.pieces.clone()),
+        ..current_board_settings(Mode::Marathon)
+    };
+    match Board::from_pages(settings, positions) {
+        Ok(board) => {
+            s.pop_layer(); // fumen
+            s.pop_layer(); // editor
+            show_game(s, board, "Tetrs | Practice");
+        }
+        Err(e) => {
+            s.call_on_name(ids::FUMEN_ERROR, |t: &mut TextView| t.set_content(e));
+        }
+    }
+}
+
+// the first page of the fumen into the editor
+fn edit_fumen(s: &mut Cursive) {
+    let Some(positions) = pasted_fumen(s) else {
+        return;
+    };
+    let position = &positions[0];
+    match Editor::from_position(position) {
+        Ok(editor) => {
+            s.pop_layer(); // fumen
+            s.pop_layer(); // editor
+            show_editor(s, editor, &position.queue, &position.hold, "");
+        }
+        Err(e) => {
+            s.call_on_name(ids::FUMEN_ERROR, |t: &mut TextView| t.set_content(e));
+        }
+    }
+}
+
+// lists every action with its keys, built from the live bindings so it is always up to date
+fn controls_menu_popup(s: &mut Cursive) {
+    let bindings = get_key_bindings();
+    let mut rows = LinearLayout::vertical();
+    for action in Action::ALL {
+        rows.add_child(
+            LinearLayout::horizontal()
+                .child(TextView::new(format!("{:>12} | ", action.title())))
+                .child(TextView::new(format!("{:<16}", bindings.describe(action))))
+                .child(Button::new("Add Key", move |s| {
+                    key_capture_popup(s, action);
+                }))
+                .child(DummyView)
+                .child(Button::new("Clear", move |s| {
+                    let mut bindings = get_key_bindings();
+                    bindings.clear(action);
+                    update_key_bindings(s, bindings);
+                })),
+        );
+    }
+    s.add_layer(
+        Dialog::around(rows)
+            .button("Reset Defaults", |s| {
+                update_key_bindings(s, KeyBindings::default());
+            })
+            .dismiss_button("Close")
+            .title("Controls"),
+    );
+}
+// waits for the next key press and binds it to the action
+fn key_capture_popup(s: &mut Cursive, action: Action) {
     s.add_layer(
         OnEventView::new(
-            Dialog::around(
-                LinearLayout::vertical()
-                    .child(Button::new("Resume", |s| {
+            Dialog::around(TextView::new(format!(
+                "Press a key for {} (Esc to cancel)",
+                action.title()
+            )))
+            .title("Add Key"),
+        )
+        .on_pre_event_inner(EventTrigger::any(), move |_, event| {
+            match event {
+                Event::Key(Key::Esc) => Some(EventResult::with_cb(|s| {
+                    s.pop_layer();
+                })),
+                // refreshes, mouse events and the like aren't key presses, let them through
+                _ if controls::key_name(event).is_none() => None,
+                _ => {
+                    let key = event.clone();
+                    Some(EventResult::with_cb(move |s| {
                         s.pop_layer();
+                        bind_key(s, action, key.clone());
                     }))
-                    .child(Button::new("Controls", |s| {
-                        controls_menu_popup(s);
-                    }))
-                    .child(Button::new("Return to Title", |s| {
-                        s.pop_layer();
-                        s.pop_layer();
-                        show_title_menu(s);
-                    })),
-            )
-            .title("Pause Menu"),
-        )
-        .on_event(Event::Key(Key::Esc), |s| {
-            s.pop_layer();
+                }
+            }
         }),
     );
 }
-fn controls_menu_popup(s: &mut Cursive) {
-    s.add_layer(
-        Dialog::around(TextView::new(
-            r#"
-  <- / ->  | move piece left / right 
- up arrow  | instantly drop piece 
-down arrow | fast fall piece
-     z     | rotate piece left 
-     x     | rotate piece right 
-     c     | hold
-"#,
-        ))
-        .dismiss_button("Close")
-        .title("Controls"),
-    );
+fn bind_key(s: &mut Cursive, action: Action, key: Event) {
+    let mut bindings = get_key_bindings();
+    let key_name = controls::key_name(&key).unwrap_or_default();
+    match bindings.bind(action, key.clone()) {
+        Ok(()) => update_key_bindings(s, bindings),
+        Err(BindError::Unbindable) => {
+            s.add_layer(Dialog::info(format!("`{}` can't be bound", key_name)).title("Add Key"));
+        }
+        Err(BindError::Conflict(other)) => {
+            s.add_layer(
+                Dialog::around(TextView::new(format!(
+                    "`{}` is already bound to {}.",
+                    key_name,
+                    other.title()
+                )))
+                .button("Rebind", move |s| {
+                    s.pop_layer();
+                    let mut bindings = get_key_bindings();
+                    let _ = bindings.rebind(action, key.clone());
+                    update_key_bindings(s, bindings);
+                })
+                .button("Cancel", |s| {
+                    s.pop_layer();
+                })
+                .title("Key In Use"),
+            );
+        }
+    }
+}
+// stores and saves new bindings, then redraws the controls screen (which must be on top) with them
+fn update_key_bindings(s: &mut Cursive, bindings: KeyBindings) {
+    set_key_bindings(bindings.clone());
+    s.pop_layer();
+    controls_menu_popup(s);
+    save_setting_to_disk(s, |c| c.controls = bindings);
 }
 fn get_quit_button() -> Button {
     Button::new("Quit", |s| {
@@ -296,6 +1879,10 @@ fn get_quit_button() -> Button {
     })
 }
 
+fn get_mode_string() -> String {
+    String::from(" ") + get_mode().title()
+}
+
 fn get_ghost_piece_string() -> String {
     match get_ghost_piece_on() {
         true => "   On".to_string(),
@@ -303,8 +1890,16 @@ fn get_ghost_piece_string() -> String {
     }
 }
 
+fn get_hints_string() -> String {
+    String::from(" ") + get_hints().title()
+}
+
+fn get_bot_difficulty_string() -> String {
+    String::from(" ") + get_bot_difficulty().title()
+}
+
 fn get_audio_on_off_string() -> String {
-    match !audio::get_is_paused() {
+    match get_music_on() {
         true => "         On".to_string(),
         false => "        Off".to_string(),
     }
@@ -313,66 +1908,99 @@ fn get_audio_on_off_string() -> String {
 pub fn get_settings_button() -> Button {
     Button::new("Settings", move |s| {
         let starting_level_button = Button::new("Change Starting Level", |s| {
+            let mut level_dialog = Dialog::around(TextView::new("Make selection:").center());
+            for lvl in config::MIN_STARTING_LEVEL..=config::MAX_STARTING_LEVEL {
+                level_dialog = level_dialog.button(lvl.to_string(), move |s| {
+                    set_level(lvl);
+                    s.pop_layer();
+                    save_setting_to_disk(s, |c| c.starting_level = lvl);
+                });
+            }
             s.add_layer(
-                // this is ugly, try to refactor
-                OnEventView::new(
-                    Dialog::around(TextView::new("Make selection:").center())
-                        .button("1", |s| {
-                            set_level(1);
-                            s.pop_layer();
-                        })
-                        .button("2", |s| {
-                            set_level(2);
-                            s.pop_layer();
-                        })
-                        .button("3", |s| {
-                            set_level(3);
-                            s.pop_layer();
-                        })
-                        .button("4", |s| {
-                            set_level(4);
-                            s.pop_layer();
-                        })
-                        .button("5", |s| {
-                            set_level(5);
-                            s.pop_layer();
-                        })
-                        .button("6", |s| {
-                            set_level(6);
-                            s.pop_layer();
-                        })
-                        .button("7", |s| {
-                            set_level(7);
-                            s.pop_layer();
-                        })
-                        .button("8", |s| {
-                            set_level(8);
-                            s.pop_layer();
-                        })
-                        .button("9", |s| {
-                            set_level(9);
-                            s.pop_layer();
-                        })
-                        .title("Select a Level | ESC to close"),
-                )
-                .on_event(Event::Key(Key::Esc), |s| {
+                OnEventView::new(level_dialog.title("Select a Level | ESC to close")).on_event(
+                    Event::Key(Key::Esc),
+                    |s| {
+                        s.pop_layer();
+                    },
+                ),
+            );
+        });
+        let mode_button = Button::new("Change Mode", |s| {
+            let mut mode_dialog = Dialog::around(TextView::new("Make selection:").center());
+            for mode in Mode::ALL {
+                mode_dialog = mode_dialog.button(mode.title(), move |s| {
+                    set_mode(mode);
+                    s.pop_layer();
+                    save_setting_to_disk(s, |c| c.mode = mode);
+                });
+            }
+            s.add_layer(
+                OnEventView::new(mode_dialog.title("Select a Mode | ESC to close")).on_event(
+                    Event::Key(Key::Esc),
+                    |s| {
+                        s.pop_layer();
+                    },
+                ),
+            );
+        });
+        let hints_button = Button::new("Change Hints", |s| {
+            let mut hints_dialog = Dialog::around(TextView::new("Make selection:").center());
+            for hints in Hints::ALL {
+                hints_dialog = hints_dialog.button(hints.title(), move |s| {
+                    set_hints(hints);
+                    s.pop_layer();
+                    save_setting_to_disk(s, |c| c.hints = hints);
+                });
+            }
+            s.add_layer(
+                OnEventView::new(hints_dialog.title("Select Hints | ESC to close")).on_event(
+                    Event::Key(Key::Esc),
+                    |s| {
+                        s.pop_layer();
+                    },
+                ),
+            );
+        });
+        let bot_difficulty_button = Button::new("Change Bot Difficulty", |s| {
+            let mut difficulty_dialog = Dialog::around(TextView::new("Make selection:").center());
+            for difficulty in Difficulty::ALL {
+                difficulty_dialog = difficulty_dialog.button(difficulty.title(), move |s| {
+                    set_bot_difficulty(difficulty);
                     s.pop_layer();
-                }),
+                    save_setting_to_disk(s, |c| c.bot_difficulty = difficulty);
+                });
+            }
+            s.add_layer(
+                OnEventView::new(difficulty_dialog.title("Select a Difficulty | ESC to close"))
+                    .on_event(Event::Key(Key::Esc), |s| {
+                        s.pop_layer();
+                    }),
             );
         });
-        let toggle_ghost_piece_button = Button::new("Toggle Ghost Piece", |_s| {
+        let toggle_ghost_piece_button = Button::new("Toggle Ghost Piece", |s| {
             // toggle
             set_ghost_piece_on(!get_ghost_piece_on());
+            save_setting_to_disk(s, |c| c.ghost_piece_on = get_ghost_piece_on());
         });
-        let toggle_audio_button = Button::new("Toggle Music", |_s| {
+        let toggle_audio_button = Button::new("Toggle Music", |s| {
             // toggle
-            let _ = audio::toggle();
+            set_music_on(!get_music_on());
+            let _ = match get_music_on() {
+                true => audio::resume(),
+                false => audio::pause(),
+            };
+            save_setting_to_disk(s, |c| c.music_on = get_music_on());
         });
         s.add_layer(
             OnEventView::new(
                 Dialog::around(
                     LinearLayout::vertical()
                         .child(DummyView)
+                        .child(
+                            LinearLayout::horizontal().child(mode_button).child(
+                                TextView::new(get_mode_string()).with_name(ids::MODE_PREVIEW),
+                            ),
+                        )
                         .child(
                             LinearLayout::horizontal()
                                 .child(starting_level_button)
@@ -393,12 +2021,28 @@ pub fn get_settings_button() -> Button {
                                     TextView::new(get_ghost_piece_string())
                                         .with_name(ids::GHOST_PIECE_ON_OFF),
                                 ),
+                        )
+                        .child(
+                            LinearLayout::horizontal().child(hints_button).child(
+                                TextView::new(get_hints_string()).with_name(ids::HINTS_PREVIEW),
+                            ),
+                        )
+                        .child(
+                            LinearLayout::horizontal()
+                                .child(bot_difficulty_button)
+                                .child(
+                                    TextView::new(get_bot_difficulty_string())
+                                        .with_name(ids::BOT_DIFFICULTY_PREVIEW),
+                                ),
                         ),
                 )
                 .dismiss_button("Close")
                 .title("Settings"),
             )
             .on_event(Event::Refresh, |s| {
+                s.call_on_name(ids::MODE_PREVIEW, |t: &mut TextView| {
+                    t.set_content(get_mode_string());
+                });
                 s.call_on_name(ids::STARTING_LEVEL_PREVIEW, |t: &mut TextView| {
                     t.set_content(String::from(" ") + &get_starting_level().to_string());
                 });
@@ -408,6 +2052,12 @@ pub fn get_settings_button() -> Button {
                 s.call_on_name(ids::AUDIO_ON_OFF, |t: &mut TextView| {
                     t.set_content(get_audio_on_off_string());
                 });
+                s.call_on_name(ids::HINTS_PREVIEW, |t: &mut TextView| {
+                    t.set_content(get_hints_string());
+                });
+                s.call_on_name(ids::BOT_DIFFICULTY_PREVIEW, |t: &mut TextView| {
+                    t.set_content(get_bot_difficulty_string());
+                });
             })
             .on_event(Event::Key(Key::Esc), |s| {
                 s.pop_layer();
@@ -416,15 +2066,56 @@ pub fn get_settings_button() -> Button {
     })
 }
 //helpers
-fn get_high_score_from_disk() -> u32 {
-    match save::read_config() {
-        Ok(hs_str) => match hs_str.parse::<u32>() {
-            Ok(x) => x,
-            Err(_) => 0,
-        },
-        Err(_) => 0,
+fn apply_config(config: &Config) {
+    set_mode(config.mode);
+    set_level(config.starting_level);
+    set_ghost_piece_on(config.ghost_piece_on);
+    set_music_on(config.music_on);
+    set_key_bindings(config.controls.clone());
+    set_versus_controls(config.versus_controls.clone());
+    *ENTRY_DELAY_MS.lock().unwrap() = config.entry_delay_ms;
+    *LINE_CLEAR_DELAY_MS.lock().unwrap() = config.line_clear_delay_ms;
+    set_board_size(config.board_width, config.board_height);
+    set_bot_difficulty(config.bot_difficulty);
+    set_hints(config.hints);
+}
+// called whenever a setting changes so nothing is lost on exit, only the changed setting is
+// written so command line overrides stay out of the file
+fn save_setting_to_disk<F>(s: &mut Cursive, change: F)
+where
+    F: FnOnce(&mut Config),
+{
+    let result = {
+        let mut saved = SAVED_CONFIG.lock().unwrap();
+        let config = saved.get_or_insert_with(Config::default);
+        change(config);
+        config::save(config)
+    };
+    // the setting still applies for this session, the player just needs to know it won't stick
+    if let Err(e) = result {
+        s.add_layer(
+            Dialog::around(TextView::new(format!(
+                "Could not save your settings: {}",
+                e
+            )))
+            .dismiss_button("Ok")
+            .title("Save File Problem"),
+        );
+    }
+}
+// loads the high score into the global state, returns a message for the player if anything went
+// wrong on the way
+fn load_high_score_from_disk() -> Option<String> {
+    match save::read_high_score() {
+        Ok(loaded) => {
+            set_high_score(loaded.value.unwrap_or(0));
+            loaded
+                .corruption
+                .map(|c| format!("Your high score file {}", c))
+        }
+        Err(e) => Some(format!("Could not read your high score: {}", e)),
     }
 }
 fn save_high_score_to_disk(score: u32) {
-    let _ = save::write_config(&score.to_string());
+    let _ = save::write_high_score(score);
 }
@@ -3,7 +3,7 @@ use cursive::theme::Color;
 
 pub type Tile = Option<Block>;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Block {
     Red,
     Green,
@@ -15,6 +15,8 @@ pub enum Block {
     White,
     Orange,
     Gray,
+    Garbage,         // rows that came up from below
+    Rgb(u8, u8, u8), // for pieces from a piece set
 }
 
 impl Block {
@@ -30,6 +32,8 @@ impl Block {
             Block::White => Color::Dark(BaseColor::White),
             Block::Orange => Color::RgbLowRes(4, 2, 0),
             Block::Gray => Color::RgbLowRes(1, 1, 1),
+            Block::Garbage => Color::RgbLowRes(2, 2, 2),
+            Block::Rgb(r, g, b) => Color::Rgb(*r, *g, *b),
         }
     }
 }